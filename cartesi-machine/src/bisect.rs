@@ -0,0 +1,464 @@
+//! Bisection over mcycles to find where two executions diverge.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::configuration::RuntimeConfig;
+use crate::errors::{BisectError, MachineError};
+use crate::hash::Hash;
+use crate::{BreakReason, Machine};
+
+/// Source of machine root hashes indexed by mcycle
+pub trait HashSource {
+    /// Root hash of the machine state at `mcycle`
+    fn root_hash_at(&mut self, mcycle: u64) -> Result<Hash, BisectError>;
+}
+
+/// Machine stored in a directory, run forward on demand
+///
+/// Moving backwards reloads the machine from the directory.
+pub struct StoredMachine {
+    path: PathBuf,
+    runtime: RuntimeConfig,
+    machine: Option<Machine>,
+}
+
+impl StoredMachine {
+    /// Uses the machine stored at `path`
+    pub fn new(path: &Path, runtime: RuntimeConfig) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            runtime,
+            machine: None,
+        }
+    }
+
+    /// Stores a live machine at `checkpoint` so that it can be reloaded when
+    /// moving backwards
+    pub fn from_machine(
+        machine: Machine,
+        checkpoint: &Path,
+        runtime: RuntimeConfig,
+    ) -> Result<Self, MachineError> {
+        machine.store(checkpoint)?;
+
+        Ok(Self {
+            path: checkpoint.to_path_buf(),
+            runtime,
+            machine: Some(machine),
+        })
+    }
+
+    /// Reloads the machine if it is past `mcycle` and `uarch_cycle`, without
+    /// running it forward
+    pub(crate) fn rewind_to(
+        &mut self,
+        mcycle: u64,
+        uarch_cycle: u64,
+    ) -> Result<&mut Machine, MachineError> {
+        let rewind = match &self.machine {
+            Some(machine) => {
+                let current = machine.read_mcycle()?;
+                current > mcycle || (current == mcycle && machine.read_uarch_cycle()? > uarch_cycle)
+            }
+            None => true,
+        };
+
+        if rewind {
+            self.machine = None;
+            self.machine = Some(Machine::load(&self.path, self.runtime.clone())?);
+        }
+
+        Ok(self.machine.as_mut().unwrap())
+    }
+
+    fn machine_at(&mut self, mcycle: u64) -> Result<&mut Machine, MachineError> {
+        let machine = self.rewind_to(mcycle, 0)?;
+
+        while machine.read_mcycle()? < mcycle {
+            match machine.run(mcycle)? {
+                BreakReason::YieldedAutomatically => continue,
+                _ => break,
+            }
+        }
+
+        Ok(machine)
+    }
+}
+
+impl HashSource for StoredMachine {
+    fn root_hash_at(&mut self, mcycle: u64) -> Result<Hash, BisectError> {
+        Ok(self.machine_at(mcycle)?.get_root_hash()?)
+    }
+}
+
+/// Root hashes recorded from a previous execution
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HashTrace {
+    /// Root hash at each recorded mcycle
+    pub hashes: BTreeMap<u64, Hash>,
+}
+
+impl HashSource for HashTrace {
+    fn root_hash_at(&mut self, mcycle: u64) -> Result<Hash, BisectError> {
+        self.hashes
+            .get(&mcycle)
+            .cloned()
+            .ok_or(BisectError::MissingHash { mcycle })
+    }
+}
+
+/// Cycles around the first divergence of two executions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Last mcycle at which both executions agree
+    pub last_agreeing: u64,
+    /// Root hash both executions have at `last_agreeing`
+    pub agreeing_hash: Hash,
+    /// First mcycle at which the executions disagree
+    pub first_disagreeing: u64,
+    /// Root hash of the left execution at `first_disagreeing`
+    pub left_hash: Hash,
+    /// Root hash of the right execution at `first_disagreeing`
+    pub right_hash: Hash,
+}
+
+/// Binary search for the first mcycle at which two hash sources disagree
+pub struct Bisector<L, R> {
+    left: L,
+    right: R,
+    cache: BTreeMap<u64, (Hash, Hash)>,
+}
+
+impl<L: HashSource, R: HashSource> Bisector<L, R> {
+    /// Compares `left` against `right`
+    pub fn new(left: L, right: R) -> Self {
+        Self {
+            left,
+            right,
+            cache: BTreeMap::new(),
+        }
+    }
+
+    /// Root hashes of both sources at `mcycle`
+    pub fn hashes_at(&mut self, mcycle: u64) -> Result<(Hash, Hash), BisectError> {
+        if let Some(hashes) = self.cache.get(&mcycle) {
+            return Ok(hashes.clone());
+        }
+
+        let hashes = (
+            self.left.root_hash_at(mcycle)?,
+            self.right.root_hash_at(mcycle)?,
+        );
+
+        self.cache.insert(mcycle, hashes.clone());
+        Ok(hashes)
+    }
+
+    /// Every pair of hashes computed so far, by mcycle
+    pub fn cache(&self) -> &BTreeMap<u64, (Hash, Hash)> {
+        &self.cache
+    }
+
+    /// Finds the first divergence in `[start, end]`
+    ///
+    /// The sources must agree at `start` and disagree at `end`, which must
+    /// come after `start`.
+    pub fn find(&mut self, start: u64, end: u64) -> Result<Divergence, BisectError> {
+        if end <= start {
+            return Err(BisectError::EmptyRange { start, end });
+        }
+
+        let (left, right) = self.hashes_at(start)?;
+
+        if left != right {
+            return Err(BisectError::InitialDivergence { mcycle: start });
+        }
+
+        let mut agreeing_hash = left;
+        let (mut left_hash, mut right_hash) = self.hashes_at(end)?;
+
+        if left_hash == right_hash {
+            return Err(BisectError::NoDivergence { mcycle: end });
+        }
+
+        let mut low = start;
+        let mut high = end;
+
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            let (left, right) = self.hashes_at(middle)?;
+
+            if left == right {
+                low = middle;
+                agreeing_hash = left;
+            } else {
+                high = middle;
+                left_hash = left;
+                right_hash = right;
+            }
+        }
+
+        Ok(Divergence {
+            last_agreeing: low,
+            agreeing_hash,
+            first_disagreeing: high,
+            left_hash,
+            right_hash,
+        })
+    }
+}
+
+/// State hashes after each micro-step of a single mcycle
+///
+/// The last transition is the uarch reset that completes the mcycle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UarchHashes {
+    /// mcycle being stepped through
+    pub mcycle: u64,
+    /// uarch cycle before the first micro-step
+    pub uarch_cycle: u64,
+    /// Root hash before the first micro-step
+    pub initial: Hash,
+    /// Root hash after each micro-step, until the microarchitecture halts
+    pub steps: Vec<Hash>,
+    /// Root hash after the uarch reset
+    pub reset: Hash,
+}
+
+/// Transition between two consecutive states of a [`UarchHashes`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UarchTransition {
+    /// Micro-step starting at `uarch_cycle`, proven with `log_uarch_step`
+    Step { uarch_cycle: u64 },
+    /// Uarch reset, proven with `log_uarch_reset`
+    Reset,
+}
+
+/// First micro-step at which two [`UarchHashes`] disagree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UarchDivergence {
+    /// Transition taken by the left execution
+    pub transition: UarchTransition,
+    /// Root hash both executions have before the transition
+    pub agreeing_hash: Hash,
+    /// Root hash of the left execution after the transition
+    pub left_hash: Hash,
+    /// Root hash of the right execution after its corresponding transition
+    pub right_hash: Hash,
+}
+
+impl UarchHashes {
+    /// Steps the microarchitecture until it halts, then resets it
+    pub fn from_machine(machine: &mut Machine) -> Result<Self, MachineError> {
+        let mcycle = machine.read_mcycle()?;
+        let uarch_cycle = machine.read_uarch_cycle()?;
+        let initial = machine.get_root_hash()?;
+        let mut steps = Vec::new();
+
+        while !machine.read_uarch_halt_flag()? {
+            let cycle = machine.read_uarch_cycle()?;
+            machine.run_uarch(cycle + 1)?;
+            steps.push(machine.get_root_hash()?);
+        }
+
+        machine.reset_uarch()?;
+
+        Ok(Self {
+            mcycle,
+            uarch_cycle,
+            initial,
+            steps,
+            reset: machine.get_root_hash()?,
+        })
+    }
+
+    /// Every root hash in order, from the initial state to the one after the reset
+    pub fn hashes(&self) -> Vec<Hash> {
+        let mut hashes = Vec::with_capacity(self.steps.len() + 2);
+        hashes.push(self.initial.clone());
+        hashes.extend(self.steps.iter().cloned());
+        hashes.push(self.reset.clone());
+        hashes
+    }
+
+    fn transition(&self, index: usize) -> UarchTransition {
+        if index <= self.steps.len() {
+            UarchTransition::Step {
+                uarch_cycle: self.uarch_cycle + index as u64 - 1,
+            }
+        } else {
+            UarchTransition::Reset
+        }
+    }
+
+    /// Finds the first transition after which `self` and `other` disagree
+    ///
+    /// Executions that take a different number of micro-steps disagree at
+    /// the latest when one of them resets while the other steps.
+    pub fn first_divergence(&self, other: &UarchHashes) -> Result<UarchDivergence, BisectError> {
+        if self.mcycle != other.mcycle {
+            return Err(BisectError::McycleMismatch {
+                left: self.mcycle,
+                right: other.mcycle,
+            });
+        }
+
+        let left = self.hashes();
+        let right = other.hashes();
+
+        if left[0] != right[0] {
+            return Err(BisectError::InitialDivergence {
+                mcycle: self.mcycle,
+            });
+        }
+
+        let index = (1..left.len().min(right.len()))
+            .find(|&index| {
+                left[index] != right[index]
+                    || (self.transition(index) == UarchTransition::Reset)
+                        != (other.transition(index) == UarchTransition::Reset)
+            })
+            .ok_or(BisectError::NoDivergence {
+                mcycle: self.mcycle,
+            })?;
+
+        Ok(UarchDivergence {
+            transition: self.transition(index),
+            agreeing_hash: left[index - 1].clone(),
+            left_hash: left[index].clone(),
+            right_hash: right[index].clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::keccak;
+
+    /// Trace with `hash(mcycle)` at every mcycle in `0..=end`
+    fn trace(end: u64, hash: impl Fn(u64) -> Hash) -> HashTrace {
+        HashTrace {
+            hashes: (0..=end).map(|mcycle| (mcycle, hash(mcycle))).collect(),
+        }
+    }
+
+    fn diverging_at(at: u64) -> Bisector<HashTrace, HashTrace> {
+        Bisector::new(
+            trace(100, |mcycle| keccak(&mcycle.to_le_bytes())),
+            trace(100, |mcycle| {
+                keccak(&mcycle.to_le_bytes()[..if mcycle < at { 8 } else { 7 }])
+            }),
+        )
+    }
+
+    #[test]
+    fn finds_first_divergence() {
+        for at in [1, 2, 37, 99, 100] {
+            let divergence = diverging_at(at).find(0, 100).unwrap();
+
+            assert_eq!(divergence.last_agreeing, at - 1);
+            assert_eq!(divergence.first_disagreeing, at);
+            assert_eq!(divergence.agreeing_hash, keccak(&(at - 1).to_le_bytes()));
+            assert_eq!(divergence.left_hash, keccak(&at.to_le_bytes()));
+        }
+    }
+
+    #[test]
+    fn rejects_empty_range() {
+        let mut bisector = diverging_at(10);
+
+        assert!(matches!(
+            bisector.find(20, 5),
+            Err(BisectError::EmptyRange { start: 20, end: 5 })
+        ));
+        assert!(matches!(
+            bisector.find(20, 20),
+            Err(BisectError::EmptyRange { start: 20, end: 20 })
+        ));
+        assert!(bisector.cache().is_empty());
+    }
+
+    #[test]
+    fn rejects_ranges_without_a_single_divergence() {
+        let mut bisector = diverging_at(10);
+
+        assert!(matches!(
+            bisector.find(10, 20),
+            Err(BisectError::InitialDivergence { mcycle: 10 })
+        ));
+        assert!(matches!(
+            bisector.find(0, 9),
+            Err(BisectError::NoDivergence { mcycle: 9 })
+        ));
+        assert!(matches!(
+            bisector.find(0, 101),
+            Err(BisectError::MissingHash { mcycle: 101 })
+        ));
+    }
+
+    fn uarch_hashes(mcycle: u64, steps: &[u8], reset: u8) -> UarchHashes {
+        UarchHashes {
+            mcycle,
+            uarch_cycle: 0,
+            initial: keccak(b"initial"),
+            steps: steps.iter().map(|step| keccak(&[*step])).collect(),
+            reset: keccak(&[reset]),
+        }
+    }
+
+    #[test]
+    fn finds_first_uarch_divergence() {
+        let left = uarch_hashes(7, &[1, 2, 3], 0);
+        let right = uarch_hashes(7, &[1, 4, 3], 0);
+        let divergence = left.first_divergence(&right).unwrap();
+
+        assert_eq!(
+            divergence.transition,
+            UarchTransition::Step { uarch_cycle: 1 }
+        );
+        assert_eq!(divergence.agreeing_hash, keccak(&[1]));
+        assert_eq!(divergence.left_hash, keccak(&[2]));
+        assert_eq!(divergence.right_hash, keccak(&[4]));
+
+        let left = uarch_hashes(7, &[1, 2, 3], 0);
+        let right = uarch_hashes(7, &[1, 2, 3], 5);
+        assert_eq!(
+            left.first_divergence(&right).unwrap().transition,
+            UarchTransition::Reset
+        );
+    }
+
+    #[test]
+    fn step_count_mismatch_is_a_divergence() {
+        // The shorter execution resets into the state the longer one steps into
+        let short = uarch_hashes(7, &[1, 2], 3);
+        let long = uarch_hashes(7, &[1, 2, 3], 3);
+
+        let divergence = short.first_divergence(&long).unwrap();
+        assert_eq!(divergence.transition, UarchTransition::Reset);
+        assert_eq!(divergence.agreeing_hash, keccak(&[2]));
+
+        let divergence = long.first_divergence(&short).unwrap();
+        assert_eq!(
+            divergence.transition,
+            UarchTransition::Step { uarch_cycle: 2 }
+        );
+    }
+
+    #[test]
+    fn uarch_hashes_of_different_mcycles_are_not_compared() {
+        let left = uarch_hashes(7, &[1], 0);
+        let right = uarch_hashes(8, &[1], 0);
+
+        assert!(matches!(
+            left.first_divergence(&right),
+            Err(BisectError::McycleMismatch { left: 7, right: 8 })
+        ));
+        assert!(matches!(
+            left.first_divergence(&left),
+            Err(BisectError::NoDivergence { mcycle: 7 })
+        ));
+    }
+}
@@ -0,0 +1,231 @@
+//! Binary encoding of access logs for the on-chain step verifier.
+//!
+//! The layout is the stream consumed by the `AccessLogs` library of the
+//! Solidity step verifier (`cartesi/machine-solidity-step`). For each access,
+//! in log order:
+//!
+//! | field          | size          | contents                                         |
+//! |----------------|---------------|--------------------------------------------------|
+//! | read value     | 8 or 32 bytes | word read for word accesses, node hash otherwise |
+//! | sibling hashes | 32 bytes each | from the accessed node up to the root            |
+//!
+//! There are `64 - log2_size` sibling hashes per access, in the reverse of the
+//! order used by `cm_access`. Access types, addresses and written values are
+//! not part of the stream: the verifier recomputes them by executing the step,
+//! so decoding replays the step as well.
+
+use crate::errors::EncodingError;
+use crate::hash::Hash;
+use crate::log::{AccessLogData, AccessLogType};
+use crate::merkle;
+use crate::replay::{self, UarchLayout};
+
+const HASH_SIZE: usize = 32;
+pub(crate) const WORD_SIZE: usize = 1 << merkle::LOG2_WORD_SIZE;
+
+impl AccessLogData {
+    /// Encodes the log in the binary layout consumed by the on-chain verifier
+    pub fn to_calldata(&self) -> Result<Vec<u8>, EncodingError> {
+        let mut calldata = Vec::new();
+
+        for (index, access) in self.accesses.iter().enumerate() {
+            let sibling_hashes = access
+                .sibling_hashes
+                .as_ref()
+                .ok_or(EncodingError::MissingProof { index })?;
+
+            if access.log2_size < merkle::LOG2_WORD_SIZE as i32
+                || access.log2_size as usize + sibling_hashes.len() != merkle::LOG2_ROOT_SIZE
+            {
+                return Err(EncodingError::MissingProof { index });
+            }
+
+            if access.log2_size as usize == merkle::LOG2_WORD_SIZE {
+                match access.read_data.as_deref() {
+                    Some(data) if data.len() == WORD_SIZE => calldata.extend_from_slice(data),
+                    _ => return Err(EncodingError::MissingData { index }),
+                }
+            } else {
+                calldata.extend_from_slice(access.read_hash.as_bytes());
+            }
+
+            for hash in sibling_hashes.iter().rev() {
+                calldata.extend_from_slice(hash.as_bytes());
+            }
+        }
+
+        Ok(calldata)
+    }
+
+    /// Decodes a uarch step log from the binary layout consumed by the on-chain verifier
+    ///
+    /// The step is replayed to recover the type, address and written value of
+    /// each access, so the stream is also checked for consistency. The decoded
+    /// log has proofs, but no annotations. Reset logs cannot be decoded, since
+    /// the values they write are not in the stream.
+    pub fn from_calldata(calldata: &[u8], layout: &UarchLayout) -> Result<Self, EncodingError> {
+        let mut reader = Reader {
+            calldata,
+            offset: 0,
+        };
+        let accesses = replay::decode_step(&mut reader, layout).map_err(EncodingError::Replay)?;
+
+        if !reader.is_empty() {
+            return Err(EncodingError::TrailingData {
+                offset: reader.offset,
+            });
+        }
+
+        Ok(Self {
+            log_type: AccessLogType {
+                proofs: true,
+                annotations: false,
+                large_data: false,
+            },
+            accesses,
+            brackets: Vec::new(),
+            notes: Vec::new(),
+        })
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    calldata: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.offset == self.calldata.len()
+    }
+
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.calldata.get(self.offset..self.offset + length)?;
+
+        self.offset += length;
+        Some(bytes)
+    }
+
+    /// Reads a word value
+    pub(crate) fn take_word(&mut self) -> Option<[u8; WORD_SIZE]> {
+        self.take(WORD_SIZE)?.try_into().ok()
+    }
+
+    /// Reads the sibling hashes of a node, returning them root first
+    pub(crate) fn take_sibling_hashes(&mut self, log2_size: usize) -> Option<Vec<Hash>> {
+        let mut sibling_hashes = (log2_size..merkle::LOG2_ROOT_SIZE)
+            .map(|_| Some(Hash::new(self.take(HASH_SIZE)?.try_into().ok()?)))
+            .collect::<Option<Vec<_>>>()?;
+
+        sibling_hashes.reverse();
+        Some(sibling_hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ReplayError;
+    use crate::replay::verify_uarch_step_state_transition;
+    use crate::testing::addi_step;
+
+    #[test]
+    fn calldata_has_a_word_and_siblings_per_access() {
+        let (_, log, _) = addi_step(&UarchLayout::default());
+        let calldata = log.to_calldata().unwrap();
+
+        assert_eq!(
+            calldata.len(),
+            log.accesses.len() * (WORD_SIZE + 61 * HASH_SIZE)
+        );
+        assert_eq!(calldata[..WORD_SIZE], 5u64.to_le_bytes());
+        assert_eq!(
+            &calldata[WORD_SIZE..WORD_SIZE + HASH_SIZE],
+            log.accesses[0].sibling_hashes.as_ref().unwrap()[60].as_bytes()
+        );
+    }
+
+    #[test]
+    fn decoded_log_matches_the_recorded_one() {
+        let layout = UarchLayout::default();
+        let (before, log, after) = addi_step(&layout);
+        let decoded = AccessLogData::from_calldata(&log.to_calldata().unwrap(), &layout).unwrap();
+
+        assert_eq!(decoded, log);
+        verify_uarch_step_state_transition(&before, &decoded, &after, &layout, false).unwrap();
+    }
+
+    #[test]
+    fn truncated_calldata_is_rejected() {
+        let layout = UarchLayout::default();
+        let (_, log, _) = addi_step(&layout);
+        let calldata = log.to_calldata().unwrap();
+
+        assert_eq!(
+            AccessLogData::from_calldata(&calldata[..calldata.len() - 1], &layout),
+            Err(EncodingError::Replay(ReplayError::TooFewAccesses))
+        );
+        assert_eq!(
+            AccessLogData::from_calldata(&[], &layout),
+            Err(EncodingError::Replay(ReplayError::TooFewAccesses))
+        );
+    }
+
+    #[test]
+    fn trailing_calldata_is_rejected() {
+        let layout = UarchLayout::default();
+        let (_, log, _) = addi_step(&layout);
+        let mut calldata = log.to_calldata().unwrap();
+        let offset = calldata.len();
+        calldata.push(0);
+
+        assert_eq!(
+            AccessLogData::from_calldata(&calldata, &layout),
+            Err(EncodingError::TrailingData { offset })
+        );
+    }
+
+    #[test]
+    fn inconsistent_calldata_is_rejected() {
+        let layout = UarchLayout::default();
+        let (_, log, _) = addi_step(&layout);
+        let mut calldata = log.to_calldata().unwrap();
+        // Flip a bit of the program counter, read by the third access
+        calldata[2 * (WORD_SIZE + 61 * HASH_SIZE)] ^= 0x80;
+
+        assert_eq!(
+            AccessLogData::from_calldata(&calldata, &layout),
+            Err(EncodingError::Replay(ReplayError::RootHashMismatch {
+                index: 2
+            }))
+        );
+    }
+
+    #[test]
+    fn logs_without_proofs_cannot_be_encoded() {
+        let (_, mut log, _) = addi_step(&UarchLayout::default());
+        log.accesses[3].sibling_hashes = None;
+
+        assert_eq!(
+            log.to_calldata(),
+            Err(EncodingError::MissingProof { index: 3 })
+        );
+
+        log.accesses[3].sibling_hashes = Some(Vec::new());
+        assert_eq!(
+            log.to_calldata(),
+            Err(EncodingError::MissingProof { index: 3 })
+        );
+    }
+
+    #[test]
+    fn word_accesses_without_data_cannot_be_encoded() {
+        let (_, mut log, _) = addi_step(&UarchLayout::default());
+        log.accesses[1].read_data = None;
+
+        assert_eq!(
+            log.to_calldata(),
+            Err(EncodingError::MissingData { index: 1 })
+        );
+    }
+}
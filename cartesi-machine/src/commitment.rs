@@ -0,0 +1,272 @@
+//! Commitments to the sequence of states of a computation.
+//!
+//! A commitment is a Merkle tree with one leaf per interval of 2^k cycles.
+//! Leaf `i` is the root hash of the machine after `i + 1` intervals. Once the
+//! machine halts its state no longer changes, so the remaining leaves repeat
+//! the last hash.
+
+use crate::errors::CommitmentError;
+use crate::hash::Hash;
+use crate::{merkle, BreakReason, Machine, UarchBreakReason};
+
+/// Merkle tree over the state hashes sampled at the end of each interval
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Commitment {
+    /// Log2 of the number of cycles in each interval
+    pub log2_stride: u32,
+    leaves: Vec<Hash>,
+    levels: Vec<Vec<Hash>>,
+}
+
+impl Commitment {
+    /// Builds the tree over `leaves`, whose number must be a power of two
+    pub fn new(log2_stride: u32, leaves: Vec<Hash>) -> Result<Self, CommitmentError> {
+        if !leaves.len().is_power_of_two() {
+            return Err(CommitmentError::LeafCount {
+                count: leaves.len(),
+            });
+        }
+
+        let mut levels = vec![leaves.clone()];
+
+        while levels.last().unwrap().len() > 1 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| merkle::hash_children(&pair[0], &pair[1]))
+                .collect();
+            levels.push(level);
+        }
+
+        Ok(Self {
+            log2_stride,
+            leaves,
+            levels,
+        })
+    }
+
+    /// State hashes, one per interval
+    pub fn leaves(&self) -> &[Hash] {
+        &self.leaves
+    }
+
+    /// Log2 of the number of leaves
+    pub fn log2_leaf_count(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// Root hash of the commitment
+    pub fn root_hash(&self) -> Hash {
+        self.levels.last().unwrap()[0].clone()
+    }
+
+    /// Proof that the leaf at `index` belongs to the commitment
+    pub fn proof(&self, index: usize) -> Option<CommitmentProof> {
+        let leaf = self.leaves.get(index)?.clone();
+        let sibling_hashes = self.levels[..self.log2_leaf_count()]
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(height, level)| level[(index >> height) ^ 1].clone())
+            .collect();
+
+        Some(CommitmentProof {
+            index,
+            leaf,
+            sibling_hashes,
+        })
+    }
+}
+
+/// Proof of a leaf of a [`Commitment`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitmentProof {
+    /// Index of the leaf
+    pub index: usize,
+    /// Hash of the leaf
+    pub leaf: Hash,
+    /// Sibling hashes ordered from the root towards the leaf
+    pub sibling_hashes: Vec<Hash>,
+}
+
+impl CommitmentProof {
+    /// Computes the root hash implied by the proof
+    pub fn compute_root_hash(&self) -> Hash {
+        merkle::root_from_siblings(self.index as u64, 0, &self.leaf, &self.sibling_hashes)
+    }
+
+    /// Checks the proof against a commitment root hash
+    pub fn verify(&self, root_hash: &Hash) -> bool {
+        &self.compute_root_hash() == root_hash
+    }
+}
+
+/// Builds commitments by running a machine from its current state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommitmentBuilder {
+    log2_stride: u32,
+    log2_leaf_count: u32,
+}
+
+impl CommitmentBuilder {
+    /// Commits to 2^`log2_leaf_count` intervals of 2^`log2_stride` cycles each
+    pub fn new(log2_stride: u32, log2_leaf_count: u32) -> Self {
+        Self {
+            log2_stride,
+            log2_leaf_count,
+        }
+    }
+
+    /// Runs the machine one interval of mcycles per leaf
+    ///
+    /// Manual yields are acknowledged by resetting `iflags.Y` and the run
+    /// goes on; only a halt ends it before the last interval.
+    pub fn build_mcycle(&self, machine: &mut Machine) -> Result<Commitment, CommitmentError> {
+        let start = machine.read_mcycle()?;
+
+        self.build(machine, |machine, index| {
+            let target = self.interval_end(start, index)?;
+
+            while machine.read_mcycle()? < target {
+                match machine.run(target)? {
+                    BreakReason::YieldedManually => machine.reset_iflags_y()?,
+                    BreakReason::YieldedAutomatically | BreakReason::ReachedTargetMcycle => {}
+                    BreakReason::Halted | BreakReason::Failed => return Ok(false),
+                }
+            }
+
+            Ok(true)
+        })
+    }
+
+    /// Runs the microarchitecture one interval of uarch cycles per leaf
+    ///
+    /// When the microarchitecture halts it is reset, completing the mcycle,
+    /// and the hash after the reset pads the remaining leaves.
+    pub fn build_uarch(&self, machine: &mut Machine) -> Result<Commitment, CommitmentError> {
+        let start = machine.read_uarch_cycle()?;
+
+        self.build(machine, |machine, index| {
+            let target = self.interval_end(start, index)?;
+
+            match machine.run_uarch(target)? {
+                UarchBreakReason::ReachedTargetCycle => Ok(true),
+                UarchBreakReason::UarchHalted => {
+                    machine.reset_uarch()?;
+                    Ok(false)
+                }
+            }
+        })
+    }
+
+    /// Cycle at which the interval at `index` ends, counting from `start`
+    fn interval_end(&self, start: u64, index: u64) -> Result<u64, CommitmentError> {
+        index
+            .checked_shl(self.log2_stride)
+            .filter(|offset| offset >> self.log2_stride == index)
+            .and_then(|offset| start.checked_add(offset))
+            .ok_or(CommitmentError::CycleOverflow {
+                index,
+                log2_stride: self.log2_stride,
+            })
+    }
+
+    /// Samples one leaf per interval, `advance` running the machine to the
+    /// end of an interval and reporting whether it can still make progress
+    fn build<F>(&self, machine: &mut Machine, mut advance: F) -> Result<Commitment, CommitmentError>
+    where
+        F: FnMut(&mut Machine, u64) -> Result<bool, CommitmentError>,
+    {
+        let leaf_count =
+            1usize
+                .checked_shl(self.log2_leaf_count)
+                .ok_or(CommitmentError::TooManyLeaves {
+                    log2_leaf_count: self.log2_leaf_count,
+                })?;
+        let mut leaves = Vec::new();
+
+        for index in 1..=leaf_count as u64 {
+            let running = advance(machine, index)?;
+            leaves.push(machine.get_root_hash()?);
+
+            if !running {
+                break;
+            }
+        }
+
+        let last = leaves.last().unwrap().clone();
+        leaves.resize(leaf_count, last);
+
+        Commitment::new(self.log2_stride, leaves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: u8) -> Vec<Hash> {
+        (0..count).map(|leaf| merkle::keccak(&[leaf])).collect()
+    }
+
+    #[test]
+    fn proves_every_leaf() {
+        let commitment = Commitment::new(10, leaves(8)).unwrap();
+
+        assert_eq!(commitment.leaves(), leaves(8));
+        assert_eq!(commitment.log2_leaf_count(), 3);
+
+        for index in 0..8 {
+            let proof = commitment.proof(index).unwrap();
+            assert_eq!(proof.leaf, leaves(8)[index]);
+            assert!(proof.verify(&commitment.root_hash()));
+        }
+
+        assert_eq!(commitment.proof(8), None);
+    }
+
+    #[test]
+    fn single_leaf_is_the_root() {
+        let commitment = Commitment::new(0, leaves(1)).unwrap();
+
+        assert_eq!(commitment.root_hash(), leaves(1)[0]);
+        assert!(commitment.proof(0).unwrap().sibling_hashes.is_empty());
+    }
+
+    #[test]
+    fn rejects_leaf_counts_other_than_powers_of_two() {
+        for count in [0, 3, 6] {
+            assert!(matches!(
+                Commitment::new(0, leaves(count)),
+                Err(CommitmentError::LeafCount { count: found }) if found == count as usize
+            ));
+        }
+    }
+
+    #[test]
+    fn interval_ends_do_not_overflow() {
+        let builder = CommitmentBuilder::new(4, 2);
+
+        assert_eq!(builder.interval_end(100, 3).unwrap(), 148);
+        assert!(matches!(
+            builder.interval_end(u64::MAX - 16, 1),
+            Ok(end) if end == u64::MAX
+        ));
+        assert!(matches!(
+            builder.interval_end(u64::MAX - 15, 1),
+            Err(CommitmentError::CycleOverflow {
+                index: 1,
+                log2_stride: 4
+            })
+        ));
+        assert!(matches!(
+            builder.interval_end(0, 1 << 60),
+            Err(CommitmentError::CycleOverflow { .. })
+        ));
+        assert!(matches!(
+            CommitmentBuilder::new(64, 0).interval_end(0, 1),
+            Err(CommitmentError::CycleOverflow { .. })
+        ));
+    }
+}
@@ -1,9 +1,21 @@
 //! Configuration structures for the Cartesi Machine.
 
-use crate::ffi::{free_cstr, from_cstr, to_cstr};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ConfigIssue;
+use crate::ffi::{free_cstr, from_cstr, CStringArena};
+
+pub mod boot;
+mod builder;
+pub mod cli;
+mod export;
+mod lua;
+mod validation;
+
+pub use builder::MachineConfigBuilder;
 
 #[repr(C)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// Processor state configuration
 pub struct ProcessorConfig {
     /// General purpose registers
@@ -85,20 +97,24 @@ impl From<cartesi_machine_sys::cm_processor_config> for ProcessorConfig {
 }
 
 /// RAM state configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RamConfig {
     /// RAM length
     pub length: u64,
     /// RAM image file name
+    #[serde(default, with = "optional_string")]
     pub image_filename: Option<String>,
 }
 
-impl From<RamConfig> for cartesi_machine_sys::cm_ram_config {
-    fn from(config: RamConfig) -> Self {
-        Self {
-            length: config.length,
-            image_filename: to_cstr(config.image_filename),
-        }
+impl RamConfig {
+    pub(crate) fn to_cm(
+        &self,
+        strings: &mut CStringArena,
+    ) -> Result<cartesi_machine_sys::cm_ram_config, ConfigIssue> {
+        Ok(cartesi_machine_sys::cm_ram_config {
+            length: self.length,
+            image_filename: strings.alloc("ram.image_filename", self.image_filename.as_deref())?,
+        })
     }
 }
 
@@ -112,26 +128,33 @@ impl From<cartesi_machine_sys::cm_ram_config> for RamConfig {
 }
 
 /// DTB state configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DtbConfig {
     /// Bootargs to pass to kernel
+    #[serde(default, with = "optional_string")]
     pub bootargs: Option<String>,
     /// Initialization commands to be executed as root on boot
+    #[serde(default, with = "optional_string")]
     pub init: Option<String>,
     /// Commands to execute the main application
+    #[serde(default, with = "optional_string")]
     pub entrypoint: Option<String>,
     /// ROM image file
+    #[serde(default, with = "optional_string")]
     pub image_filename: Option<String>,
 }
 
-impl From<DtbConfig> for cartesi_machine_sys::cm_dtb_config {
-    fn from(config: DtbConfig) -> Self {
-        Self {
-            bootargs: to_cstr(config.bootargs),
-            init: to_cstr(config.init),
-            entrypoint: to_cstr(config.entrypoint),
-            image_filename: to_cstr(config.image_filename),
-        }
+impl DtbConfig {
+    pub(crate) fn to_cm(
+        &self,
+        strings: &mut CStringArena,
+    ) -> Result<cartesi_machine_sys::cm_dtb_config, ConfigIssue> {
+        Ok(cartesi_machine_sys::cm_dtb_config {
+            bootargs: strings.alloc("dtb.bootargs", self.bootargs.as_deref())?,
+            init: strings.alloc("dtb.init", self.init.as_deref())?,
+            entrypoint: strings.alloc("dtb.entrypoint", self.entrypoint.as_deref())?,
+            image_filename: strings.alloc("dtb.image_filename", self.image_filename.as_deref())?,
+        })
     }
 }
 
@@ -147,7 +170,7 @@ impl From<cartesi_machine_sys::cm_dtb_config> for DtbConfig {
 }
 
 /// Memory range configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryRangeConfig {
     /// Memory range start position
     pub start: u64,
@@ -156,17 +179,26 @@ pub struct MemoryRangeConfig {
     /// Target changes to range affect image file?
     pub shared: bool,
     /// Memory range image file name
+    #[serde(default, with = "optional_string")]
     pub image_filename: Option<String>,
 }
 
-impl From<MemoryRangeConfig> for cartesi_machine_sys::cm_memory_range_config {
-    fn from(config: MemoryRangeConfig) -> Self {
-        Self {
-            start: config.start,
-            length: config.length,
-            shared: config.shared,
-            image_filename: to_cstr(config.image_filename),
-        }
+impl MemoryRangeConfig {
+    /// Lays out the range for the emulator, `path` naming it in errors
+    pub(crate) fn to_cm(
+        &self,
+        path: &str,
+        strings: &mut CStringArena,
+    ) -> Result<cartesi_machine_sys::cm_memory_range_config, ConfigIssue> {
+        Ok(cartesi_machine_sys::cm_memory_range_config {
+            start: self.start,
+            length: self.length,
+            shared: self.shared,
+            image_filename: strings.alloc(
+                &format!("{}.image_filename", path),
+                self.image_filename.as_deref(),
+            )?,
+        })
     }
 }
 
@@ -182,17 +214,21 @@ impl From<cartesi_machine_sys::cm_memory_range_config> for MemoryRangeConfig {
 }
 
 /// TLB configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TlbConfig {
     /// TLB image file name
+    #[serde(default, with = "optional_string")]
     pub image_filename: Option<String>,
 }
 
-impl From<TlbConfig> for cartesi_machine_sys::cm_tlb_config {
-    fn from(config: TlbConfig) -> Self {
-        Self {
-            image_filename: to_cstr(config.image_filename),
-        }
+impl TlbConfig {
+    pub(crate) fn to_cm(
+        &self,
+        strings: &mut CStringArena,
+    ) -> Result<cartesi_machine_sys::cm_tlb_config, ConfigIssue> {
+        Ok(cartesi_machine_sys::cm_tlb_config {
+            image_filename: strings.alloc("tlb.image_filename", self.image_filename.as_deref())?,
+        })
     }
 }
 
@@ -205,7 +241,7 @@ impl From<cartesi_machine_sys::cm_tlb_config> for TlbConfig {
 }
 
 /// CLint configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub struct ClintConfig {
     /// Value of mtimecmp CSR
@@ -225,7 +261,7 @@ impl From<cartesi_machine_sys::cm_clint_config> for ClintConfig {
 }
 
 /// Htif configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub struct HtifConfig {
     /// Value of fromhost CSR
@@ -253,9 +289,10 @@ impl From<cartesi_machine_sys::cm_htif_config> for HtifConfig {
 }
 
 /// Rollup configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RollupConfig {
     /// Represents whether the rest of the struct have been filled
+    #[serde(skip)]
     pub has_value: bool,
     /// RX buffer memory range
     pub rx_buffer: MemoryRangeConfig,
@@ -263,13 +300,16 @@ pub struct RollupConfig {
     pub tx_buffer: MemoryRangeConfig,
 }
 
-impl From<RollupConfig> for cartesi_machine_sys::cm_rollup_config {
-    fn from(config: RollupConfig) -> Self {
-        Self {
-            has_value: config.has_value,
-            rx_buffer: config.rx_buffer.into(),
-            tx_buffer: config.tx_buffer.into(),
-        }
+impl RollupConfig {
+    pub(crate) fn to_cm(
+        &self,
+        strings: &mut CStringArena,
+    ) -> Result<cartesi_machine_sys::cm_rollup_config, ConfigIssue> {
+        Ok(cartesi_machine_sys::cm_rollup_config {
+            has_value: self.has_value,
+            rx_buffer: self.rx_buffer.to_cm("rollup.rx_buffer", strings)?,
+            tx_buffer: self.tx_buffer.to_cm("rollup.tx_buffer", strings)?,
+        })
     }
 }
 
@@ -284,17 +324,22 @@ impl From<cartesi_machine_sys::cm_rollup_config> for RollupConfig {
 }
 
 /// Uarch RAM configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UarchRamConfig {
     /// RAM image file name
+    #[serde(default, with = "optional_string")]
     pub image_filename: Option<String>,
 }
 
-impl From<UarchRamConfig> for cartesi_machine_sys::cm_uarch_ram_config {
-    fn from(config: UarchRamConfig) -> Self {
-        Self {
-            image_filename: to_cstr(config.image_filename),
-        }
+impl UarchRamConfig {
+    pub(crate) fn to_cm(
+        &self,
+        strings: &mut CStringArena,
+    ) -> Result<cartesi_machine_sys::cm_uarch_ram_config, ConfigIssue> {
+        Ok(cartesi_machine_sys::cm_uarch_ram_config {
+            image_filename: strings
+                .alloc("uarch.ram.image_filename", self.image_filename.as_deref())?,
+        })
     }
 }
 
@@ -307,7 +352,7 @@ impl From<cartesi_machine_sys::cm_uarch_ram_config> for UarchRamConfig {
 }
 
 /// Uarch Processor configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub struct UarchProcessorConfig {
     /// General purpose registers
@@ -333,7 +378,7 @@ impl From<cartesi_machine_sys::cm_uarch_processor_config> for UarchProcessorConf
 }
 
 /// Uarch configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UarchConfig {
     /// Processor configuration
     pub processor: UarchProcessorConfig,
@@ -341,12 +386,15 @@ pub struct UarchConfig {
     pub ram: UarchRamConfig,
 }
 
-impl From<UarchConfig> for cartesi_machine_sys::cm_uarch_config {
-    fn from(config: UarchConfig) -> Self {
-        Self {
-            processor: config.processor.into(),
-            ram: config.ram.into(),
-        }
+impl UarchConfig {
+    pub(crate) fn to_cm(
+        &self,
+        strings: &mut CStringArena,
+    ) -> Result<cartesi_machine_sys::cm_uarch_config, ConfigIssue> {
+        Ok(cartesi_machine_sys::cm_uarch_config {
+            processor: self.processor.clone().into(),
+            ram: self.ram.to_cm(strings)?,
+        })
     }
 }
 
@@ -360,7 +408,7 @@ impl From<cartesi_machine_sys::cm_uarch_config> for UarchConfig {
 }
 
 /// Machine configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MachineConfig {
     /// Processor configuration
     pub processor: ProcessorConfig,
@@ -369,6 +417,7 @@ pub struct MachineConfig {
     /// DTB configuration
     pub dtb: DtbConfig,
     /// Flash drive configuration
+    #[serde(default)]
     pub flash_drive: Vec<MemoryRangeConfig>,
     /// TLB configuration
     pub tlb: TlbConfig,
@@ -376,39 +425,62 @@ pub struct MachineConfig {
     pub clint: ClintConfig,
     /// Htif configuration
     pub htif: HtifConfig,
-    /// Rollup configuration
+    /// Rollup configuration, serialized as absent when it has no value
+    #[serde(default = "optional_rollup::none", with = "optional_rollup")]
     pub rollup: RollupConfig,
     /// Uarch configuration
     pub uarch: UarchConfig,
 }
 
-impl From<MachineConfig> for OwnedMachineConfig {
-    fn from(config: MachineConfig) -> Self {
-        let count = config.flash_drive.len();
-        let leaked_entry = Box::leak(
-            config
-                .flash_drive
-                .into_iter()
-                .map(|x| x.into())
-                .collect::<Vec<_>>()
-                .into_boxed_slice(),
-        );
-        let entry: *mut cartesi_machine_sys::cm_memory_range_config = leaked_entry.as_mut_ptr();
-        let flash_drive = cartesi_machine_sys::cm_memory_range_config_array { entry, count };
+impl TryFrom<&MachineConfig> for OwnedMachineConfig {
+    type Error = ConfigIssue;
+
+    /// Lays out the configuration, failing if a string contains a NUL byte
+    fn try_from(config: &MachineConfig) -> Result<Self, ConfigIssue> {
+        let mut strings = CStringArena::default();
+
+        let mut flash_drive = config
+            .flash_drive
+            .iter()
+            .enumerate()
+            .map(|(index, drive)| drive.to_cm(&format!("flash_drive[{}]", index), &mut strings))
+            .collect::<Result<Vec<_>, _>>()?;
 
         let config = cartesi_machine_sys::cm_machine_config {
-            processor: config.processor.into(),
-            ram: config.ram.into(),
-            dtb: config.dtb.into(),
-            flash_drive,
-            tlb: config.tlb.into(),
-            clint: config.clint.into(),
-            htif: config.htif.into(),
-            rollup: config.rollup.into(),
-            uarch: config.uarch.into(),
+            processor: config.processor.clone().into(),
+            ram: config.ram.to_cm(&mut strings)?,
+            dtb: config.dtb.to_cm(&mut strings)?,
+            flash_drive: cartesi_machine_sys::cm_memory_range_config_array {
+                entry: flash_drive.as_mut_ptr(),
+                count: flash_drive.len(),
+            },
+            tlb: config.tlb.to_cm(&mut strings)?,
+            clint: config.clint.clone().into(),
+            htif: config.htif.clone().into(),
+            rollup: config.rollup.to_cm(&mut strings)?,
+            uarch: config.uarch.to_cm(&mut strings)?,
         };
 
-        Self(config)
+        Ok(Self {
+            config,
+            _flash_drive: flash_drive,
+            _strings: strings,
+        })
+    }
+}
+
+impl From<MachineConfig> for OwnedMachineConfig {
+    /// Lays out the configuration
+    ///
+    /// # Panics
+    ///
+    /// Panics if a string contains a NUL byte; use `TryFrom<&MachineConfig>`
+    /// to get an error instead.
+    fn from(config: MachineConfig) -> Self {
+        match Self::try_from(&config) {
+            Ok(config) => config,
+            Err(issue) => panic!("{}", issue),
+        }
     }
 }
 
@@ -438,49 +510,63 @@ impl From<cartesi_machine_sys::cm_machine_config> for MachineConfig {
 
 impl Default for MachineConfig {
     fn default() -> Self {
-        unsafe {
-            let raw_config = cartesi_machine_sys::cm_new_default_machine_config();
-            let config = MachineConfig::from(*raw_config);
-            cartesi_machine_sys::cm_delete_machine_config(raw_config);
-            config
-        }
+        let config = unsafe {
+            ForeignMachineConfig::from_raw(cartesi_machine_sys::cm_new_default_machine_config())
+        };
+        MachineConfig::from(&config)
     }
 }
 
-/// A machine configuration that is owned by Rust and should be dropped in another way.
-pub struct OwnedMachineConfig(cartesi_machine_sys::cm_machine_config);
+/// A machine configuration laid out in memory owned by Rust, viewable as a `cm_machine_config`
+///
+/// The flash drive array and every string live as long as this value, so the
+/// view needs no manual cleanup.
+pub struct OwnedMachineConfig {
+    config: cartesi_machine_sys::cm_machine_config,
+    _flash_drive: Vec<cartesi_machine_sys::cm_memory_range_config>,
+    _strings: CStringArena,
+}
 
 impl AsRef<cartesi_machine_sys::cm_machine_config> for OwnedMachineConfig {
     fn as_ref(&self) -> &cartesi_machine_sys::cm_machine_config {
-        &self.0
+        &self.config
+    }
+}
+
+/// A machine configuration allocated by the emulator, deleted when dropped
+pub(crate) struct ForeignMachineConfig(*const cartesi_machine_sys::cm_machine_config);
+
+impl ForeignMachineConfig {
+    /// Takes ownership of a configuration returned by the emulator
+    ///
+    /// # Safety
+    ///
+    /// `config` must be a valid configuration allocated by the emulator and
+    /// not deleted elsewhere.
+    pub(crate) unsafe fn from_raw(config: *const cartesi_machine_sys::cm_machine_config) -> Self {
+        Self(config)
     }
 }
 
-impl Drop for OwnedMachineConfig {
+impl From<&ForeignMachineConfig> for MachineConfig {
+    fn from(config: &ForeignMachineConfig) -> Self {
+        // Strings are copied, so the emulator keeps ownership of its own
+        unsafe { MachineConfig::from(*config.0) }
+    }
+}
+
+impl Drop for ForeignMachineConfig {
     fn drop(&mut self) {
-        free_cstr(self.0.ram.image_filename);
-        free_cstr(self.0.dtb.bootargs);
-        free_cstr(self.0.dtb.init);
-        free_cstr(self.0.dtb.entrypoint);
-        free_cstr(self.0.dtb.image_filename);
-        free_cstr(self.0.tlb.image_filename);
-        free_cstr(self.0.rollup.rx_buffer.image_filename);
-        free_cstr(self.0.rollup.tx_buffer.image_filename);
-        free_cstr(self.0.uarch.ram.image_filename);
-
-        unsafe {
-            drop(Box::from_raw(std::slice::from_raw_parts_mut(
-                self.0.flash_drive.entry,
-                self.0.flash_drive.count as usize,
-            )))
-        }
+        unsafe { cartesi_machine_sys::cm_delete_machine_config(self.0) }
     }
 }
 
-#[derive(Debug, Default, Clone)]
+/// Concurrency runtime configuration
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub struct ConcurrencyRuntimeConfig {
-    update_merkle_tree: u64,
+    /// Number of threads used to update the Merkle tree, or 0 to use all available cores
+    pub update_merkle_tree: u64,
 }
 
 impl From<ConcurrencyRuntimeConfig> for cartesi_machine_sys::cm_concurrency_runtime_config {
@@ -495,10 +581,12 @@ impl From<cartesi_machine_sys::cm_concurrency_runtime_config> for ConcurrencyRun
     }
 }
 
-#[derive(Debug, Default, Clone)]
+/// Htif runtime configuration
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub struct HtifRuntimeConfig {
-    no_console_putchar: bool,
+    /// Discard characters the guest writes to the console instead of printing them
+    pub no_console_putchar: bool,
 }
 
 impl From<HtifRuntimeConfig> for cartesi_machine_sys::cm_htif_runtime_config {
@@ -513,15 +601,46 @@ impl From<cartesi_machine_sys::cm_htif_runtime_config> for HtifRuntimeConfig {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+/// Runtime configuration, used when creating or loading a machine
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub struct RuntimeConfig {
+    /// Concurrency configuration
     pub concurrency: ConcurrencyRuntimeConfig,
+    /// Htif configuration
     pub htif: HtifRuntimeConfig,
+    /// Skip checking the root hash of a stored machine when loading it
     pub skip_root_hash_check: bool,
+    /// Skip checking the version of a stored machine when loading it
     pub skip_version_check: bool,
 }
 
+impl RuntimeConfig {
+    /// Sets the number of threads used to update the Merkle tree
+    pub fn update_merkle_tree_concurrency(mut self, threads: u64) -> Self {
+        self.concurrency.update_merkle_tree = threads;
+        self
+    }
+
+    /// Sets whether characters the guest writes to the console are discarded
+    pub fn no_console_putchar(mut self, no_console_putchar: bool) -> Self {
+        self.htif.no_console_putchar = no_console_putchar;
+        self
+    }
+
+    /// Sets whether loading skips the root hash check
+    pub fn skip_root_hash_check(mut self, skip: bool) -> Self {
+        self.skip_root_hash_check = skip;
+        self
+    }
+
+    /// Sets whether loading skips the version check
+    pub fn skip_version_check(mut self, skip: bool) -> Self {
+        self.skip_version_check = skip;
+        self
+    }
+}
+
 impl From<RuntimeConfig> for cartesi_machine_sys::cm_machine_runtime_config {
     fn from(config: RuntimeConfig) -> Self {
         unsafe { std::mem::transmute(config) }
@@ -534,6 +653,186 @@ impl From<cartesi_machine_sys::cm_machine_runtime_config> for RuntimeConfig {
     }
 }
 
+/// Frees an image filename allocated with `CString::into_raw`, leaving it null
+#[deprecated(note = "convert through `OwnedMachineConfig`, which frees its strings when dropped")]
 pub fn free_cm_memory_range_config_cstr(config: &mut cartesi_machine_sys::cm_memory_range_config) {
     free_cstr(config.image_filename);
-}
\ No newline at end of file
+    config.image_filename = std::ptr::null();
+}
+
+/// Serializes absent strings as empty strings, like the JSON-RPC server does
+mod optional_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(value.as_deref().unwrap_or(""))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<String>, D::Error> {
+        let value = Option::<String>::deserialize(deserializer)?;
+        Ok(value.filter(|value| !value.is_empty()))
+    }
+}
+
+/// Serializes the rollup configuration as absent when it has no value
+mod optional_rollup {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{MemoryRangeConfig, RollupConfig};
+
+    pub fn none() -> RollupConfig {
+        let empty = MemoryRangeConfig {
+            start: 0,
+            length: 0,
+            shared: false,
+            image_filename: None,
+        };
+
+        RollupConfig {
+            has_value: false,
+            rx_buffer: empty.clone(),
+            tx_buffer: empty,
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        value: &RollupConfig,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.has_value.then_some(value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<RollupConfig, D::Error> {
+        Ok(match Option::<RollupConfig>::deserialize(deserializer)? {
+            Some(rollup) => RollupConfig {
+                has_value: true,
+                ..rollup
+            },
+            None => none(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{leaked_bytes, machine_config};
+
+    fn range(start: u64, image_filename: Option<&str>) -> MemoryRangeConfig {
+        MemoryRangeConfig {
+            start,
+            length: 0x1000,
+            shared: false,
+            image_filename: image_filename.map(str::to_string),
+        }
+    }
+
+    /// Configuration using every string field
+    fn config() -> MachineConfig {
+        let mut config = machine_config();
+        config.ram.image_filename = Some("linux.bin".to_string());
+        config.dtb.bootargs = Some("quiet console=hvc0".to_string());
+        config.dtb.init = Some("echo init".to_string());
+        config.dtb.entrypoint = Some("/bin/app".to_string());
+        config.dtb.image_filename = Some("machine.dtb".to_string());
+        config.flash_drive = (0..20)
+            .map(|i| {
+                range(
+                    0x80000000000000 + (i << 52),
+                    Some(&format!("drive-{}.ext2", i)),
+                )
+            })
+            .collect();
+        config.tlb.image_filename = Some("tlb.bin".to_string());
+        config.rollup.has_value = true;
+        config.rollup.rx_buffer = range(0x60000000, Some("rx.bin"));
+        config.rollup.tx_buffer = range(0x60200000, None);
+        config.uarch.ram.image_filename = Some("uarch-ram.bin".to_string());
+        config
+    }
+
+    fn json(config: &MachineConfig) -> serde_json::Value {
+        serde_json::to_value(config).unwrap()
+    }
+
+    #[test]
+    fn owned_config_outlives_moves() {
+        let config = config();
+        let owned = OwnedMachineConfig::try_from(&config).unwrap();
+
+        // Moving the value must not move the memory the view points to
+        let owned = Box::new(owned);
+        let owned = vec![*owned].pop().unwrap();
+
+        assert_eq!(json(&MachineConfig::from(*owned.as_ref())), json(&config));
+    }
+
+    #[test]
+    fn owned_config_frees_everything() {
+        let config = config();
+
+        let leaked = leaked_bytes(|| {
+            let owned = OwnedMachineConfig::try_from(&config).unwrap();
+            drop(MachineConfig::from(*owned.as_ref()));
+        });
+        assert_eq!(leaked, 0);
+
+        let leaked = leaked_bytes(|| {
+            drop(OwnedMachineConfig::from(config.clone()));
+        });
+        assert_eq!(leaked, 0);
+    }
+
+    #[test]
+    fn owned_config_rejects_nul_bytes_without_leaking() {
+        let mut config = config();
+        config.flash_drive[7].image_filename = Some("data\0.ext2".to_string());
+
+        let leaked = leaked_bytes(|| {
+            assert_eq!(
+                OwnedMachineConfig::try_from(&config).err(),
+                Some(ConfigIssue::new(
+                    "flash_drive[7].image_filename",
+                    "contains a NUL byte at 4"
+                ))
+            );
+        });
+        assert_eq!(leaked, 0);
+
+        let mut config = machine_config();
+        config.dtb.bootargs = Some("\0".to_string());
+        assert_eq!(
+            OwnedMachineConfig::try_from(&config).err(),
+            Some(ConfigIssue::new("dtb.bootargs", "contains a NUL byte at 0"))
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn legacy_range_strings_are_freed_once() {
+        let leaked = leaked_bytes(|| {
+            let mut range = cartesi_machine_sys::cm_memory_range_config {
+                start: 0x1000,
+                length: 0x1000,
+                shared: false,
+                image_filename: std::ffi::CString::new("image.bin").unwrap().into_raw(),
+            };
+
+            free_cm_memory_range_config_cstr(&mut range);
+            assert!(range.image_filename.is_null());
+
+            // Freeing again is a no-op rather than a double free
+            free_cm_memory_range_config_cstr(&mut range);
+        });
+
+        assert_eq!(leaked, 0);
+    }
+}
+
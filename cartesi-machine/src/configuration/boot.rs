@@ -0,0 +1,493 @@
+//! Structured kernel command lines and DTB init and entrypoint scripts.
+
+use std::fmt::Write;
+
+use crate::errors::BootError;
+
+/// Linux kernel command line, as stored in [`super::DtbConfig::bootargs`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bootargs {
+    /// Suppresses most kernel log messages
+    pub quiet: bool,
+    /// Console device, as in `console=hvc0`
+    pub console: Option<String>,
+    /// Root device, as in `root=/dev/pmem0`
+    pub root: Option<String>,
+    /// Memory size in bytes, as in `mem=64M`
+    pub memory: Option<u64>,
+    /// Remaining parameters, in order, with their optional values
+    pub parameters: Vec<(String, Option<String>)>,
+}
+
+impl Bootargs {
+    /// Creates an empty command line
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the kernel runs quietly
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Sets the console device
+    pub fn console(mut self, console: &str) -> Self {
+        self.console = Some(console.to_string());
+        self
+    }
+
+    /// Sets the root device
+    pub fn root(mut self, root: &str) -> Self {
+        self.root = Some(root.to_string());
+        self
+    }
+
+    /// Sets the memory size in bytes
+    pub fn memory(mut self, memory: u64) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Adds a parameter, with a value for `name=value` or without one for a flag
+    pub fn parameter(mut self, name: &str, value: Option<&str>) -> Self {
+        self.parameters
+            .push((name.to_string(), value.map(str::to_string)));
+        self
+    }
+
+    /// Parses a kernel command line, following the kernel quoting rules
+    pub fn parse(bootargs: &str) -> Result<Self, BootError> {
+        let mut parsed = Self::default();
+
+        for (name, value) in split_bootargs(bootargs)? {
+            match (name.as_str(), value) {
+                ("quiet", None) if !parsed.quiet => parsed.quiet = true,
+                ("console", Some(value)) if parsed.console.is_none() => {
+                    parsed.console = Some(value)
+                }
+                ("root", Some(value)) if parsed.root.is_none() => parsed.root = Some(value),
+                ("mem", Some(value)) if parsed.memory.is_none() => {
+                    parsed.memory = Some(parse_memory(&value)?)
+                }
+                (_, value) => parsed.parameters.push((name, value)),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Composes the kernel command line, quoting values where needed
+    pub fn compose(&self) -> Result<String, BootError> {
+        let mut parameters = Vec::new();
+
+        if self.quiet {
+            parameters.push(("quiet", None));
+        }
+        if let Some(console) = &self.console {
+            parameters.push(("console", Some(console.clone())));
+        }
+        if let Some(root) = &self.root {
+            parameters.push(("root", Some(root.clone())));
+        }
+        if let Some(memory) = self.memory {
+            parameters.push(("mem", Some(format_memory(memory))));
+        }
+        for (name, value) in &self.parameters {
+            parameters.push((name.as_str(), value.clone()));
+        }
+
+        let mut bootargs = String::new();
+        for (name, value) in parameters {
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '=' || c == '"')
+            {
+                return Err(BootError::InvalidName {
+                    name: name.to_string(),
+                });
+            }
+
+            if !bootargs.is_empty() {
+                bootargs.push(' ');
+            }
+            bootargs.push_str(name);
+
+            let Some(value) = value else {
+                continue;
+            };
+
+            if value.contains('"') {
+                return Err(BootError::UnquotableValue {
+                    name: name.to_string(),
+                    value,
+                });
+            }
+
+            if value.is_empty() || value.contains(char::is_whitespace) {
+                write!(bootargs, "=\"{}\"", value).unwrap();
+            } else {
+                write!(bootargs, "={}", value).unwrap();
+            }
+        }
+
+        Ok(bootargs)
+    }
+}
+
+/// Splits a kernel command line into parameters, as the kernel `next_arg` does
+fn split_bootargs(bootargs: &str) -> Result<Vec<(String, Option<String>)>, BootError> {
+    let mut parameters = Vec::new();
+    let mut chars = bootargs.chars().enumerate().peekable();
+
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(parameters);
+        }
+
+        let mut name = String::new();
+        let mut value: Option<String> = None;
+        let mut open_quote = None;
+
+        while let Some((offset, c)) =
+            chars.next_if(|&(_, c)| open_quote.is_some() || !c.is_whitespace())
+        {
+            match (c, &mut value) {
+                ('"', _) if open_quote.is_some() => open_quote = None,
+                ('"', _) => open_quote = Some(offset),
+                ('=', None) => value = Some(String::new()),
+                (c, Some(value)) => value.push(c),
+                (c, None) => name.push(c),
+            }
+        }
+
+        if let Some(offset) = open_quote {
+            return Err(BootError::UnterminatedBootargsQuote { offset });
+        }
+
+        parameters.push((name, value));
+    }
+}
+
+/// Parses a size the way the kernel `memparse` does
+fn parse_memory(value: &str) -> Result<u64, BootError> {
+    let invalid = || BootError::InvalidMemory {
+        value: value.to_string(),
+    };
+
+    let hex = value.starts_with("0x");
+    let (digits, shift) = match value.char_indices().last() {
+        Some((i, suffix))
+            if suffix.is_ascii_alphabetic() && !(hex && suffix.is_ascii_hexdigit()) =>
+        {
+            let shift = match suffix.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                'P' => 50,
+                'E' => 60,
+                _ => return Err(invalid()),
+            };
+            (&value[..i], shift)
+        }
+        _ => (value, 0),
+    };
+
+    let number = if let Some(hex) = digits.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    }
+    .map_err(|_| invalid())?;
+
+    number.checked_mul(1 << shift).ok_or_else(invalid)
+}
+
+/// Formats a size with the largest exact kernel suffix
+fn format_memory(memory: u64) -> String {
+    for (suffix, shift) in [("G", 30), ("M", 20), ("K", 10)] {
+        if memory != 0 && memory.trailing_zeros() >= shift {
+            return format!("{}{}", memory >> shift, suffix);
+        }
+    }
+
+    memory.to_string()
+}
+
+/// Shell script made of plain commands, as stored in [`super::DtbConfig::init`]
+/// and [`super::DtbConfig::entrypoint`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    /// Commands, each a list of words
+    pub commands: Vec<Vec<String>>,
+}
+
+impl Script {
+    /// Creates an empty script
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a command made of the given words
+    pub fn command<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.commands
+            .push(words.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Parses a script of plain commands
+    ///
+    /// Quotes, backslash escapes, comments and `;` are understood. Expansions,
+    /// redirections, pipes and other shell syntax are rejected, since they
+    /// cannot be represented as lists of words.
+    pub fn parse(script: &str) -> Result<Self, BootError> {
+        let mut parsed = Self::default();
+        let mut command = Vec::new();
+        let mut word: Option<String> = None;
+        let mut line = 1;
+        let mut chars = script.chars().peekable();
+
+        let unsupported = |line, construct: &str| BootError::UnsupportedShell {
+            line,
+            construct: construct.to_string(),
+        };
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\n' | ';' | ' ' | '\t' => {
+                    command.extend(word.take());
+                    if c != ' ' && c != '\t' && !command.is_empty() {
+                        parsed.commands.push(std::mem::take(&mut command));
+                    }
+                    if c == '\n' {
+                        line += 1;
+                    }
+                }
+                '#' if word.is_none() => while chars.next_if(|&c| c != '\n').is_some() {},
+                '\\' => match chars.next() {
+                    Some('\n') => line += 1,
+                    Some(c) => word.get_or_insert_with(String::new).push(c),
+                    None => return Err(unsupported(line, "trailing backslash")),
+                },
+                '\'' => {
+                    let word = word.get_or_insert_with(String::new);
+                    loop {
+                        match chars.next() {
+                            Some('\'') => break,
+                            Some(c) => {
+                                if c == '\n' {
+                                    line += 1;
+                                }
+                                word.push(c)
+                            }
+                            None => return Err(BootError::UnterminatedQuote { line }),
+                        }
+                    }
+                }
+                '"' => {
+                    let word = word.get_or_insert_with(String::new);
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some('\n') => line += 1,
+                                Some(c @ ('$' | '`' | '"' | '\\')) => word.push(c),
+                                Some(c) => {
+                                    word.push('\\');
+                                    word.push(c);
+                                }
+                                None => return Err(BootError::UnterminatedQuote { line }),
+                            },
+                            Some('$' | '`') => return Err(unsupported(line, "expansion")),
+                            Some(c) => {
+                                if c == '\n' {
+                                    line += 1;
+                                }
+                                word.push(c)
+                            }
+                            None => return Err(BootError::UnterminatedQuote { line }),
+                        }
+                    }
+                }
+                '$' | '`' => return Err(unsupported(line, "expansion")),
+                '*' | '?' | '[' => return Err(unsupported(line, "pattern")),
+                '~' if word.is_none() => return Err(unsupported(line, "tilde expansion")),
+                '|' | '&' | '<' | '>' | '(' | ')' => {
+                    return Err(unsupported(line, &format!("operator '{}'", c)))
+                }
+                c => word.get_or_insert_with(String::new).push(c),
+            }
+        }
+
+        command.extend(word);
+        if !command.is_empty() {
+            parsed.commands.push(command);
+        }
+
+        Ok(parsed)
+    }
+
+    /// Composes the script, one command per line with words quoted as needed
+    pub fn compose(&self) -> String {
+        let mut script = String::new();
+
+        for command in &self.commands {
+            let words: Vec<_> = command.iter().map(|word| quote(word)).collect();
+            script.push_str(&words.join(" "));
+            script.push('\n');
+        }
+
+        script
+    }
+}
+
+/// Quotes a word for a POSIX shell
+fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+
+    if !word.is_empty() && word.chars().all(safe) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bootargs_round_trip() {
+        let bootargs = Bootargs::new()
+            .quiet(true)
+            .console("hvc0")
+            .root("/dev/pmem0")
+            .memory(64 << 20)
+            .parameter("rw", None)
+            .parameter("init", Some("/opt/cartesi/bin/init"))
+            .parameter("empty", Some(""))
+            .parameter("spaced", Some("a b\tc"))
+            .parameter("console", Some("ttyS0"));
+
+        let composed = bootargs.compose().unwrap();
+        assert_eq!(
+            composed,
+            "quiet console=hvc0 root=/dev/pmem0 mem=64M rw init=/opt/cartesi/bin/init \
+             empty=\"\" spaced=\"a b\tc\" console=ttyS0"
+        );
+        assert_eq!(Bootargs::parse(&composed).unwrap(), bootargs);
+    }
+
+    #[test]
+    fn parsed_bootargs_compose_back() {
+        let bootargs = "quiet  console=hvc0 mem=0x1000 root=/dev/pmem0 rw \"quoted\"=x";
+        let parsed = Bootargs::parse(bootargs).unwrap();
+
+        assert_eq!(parsed.memory, Some(0x1000));
+        assert_eq!(
+            parsed.parameters.last().unwrap(),
+            &("quoted".to_string(), Some("x".to_string()))
+        );
+        assert_eq!(Bootargs::parse(&parsed.compose().unwrap()).unwrap(), parsed);
+    }
+
+    #[test]
+    fn memory_sizes_round_trip() {
+        for memory in [0, 1, 1023, 1 << 10, 3 << 20, 5 << 30, (1 << 40) + (1 << 10)] {
+            assert_eq!(parse_memory(&format_memory(memory)), Ok(memory));
+        }
+        assert_eq!(parse_memory("010"), Ok(8));
+        assert_eq!(parse_memory("0x1aK"), Ok(0x1a << 10));
+        assert!(parse_memory("16Q").is_err());
+        assert!(parse_memory("20000000000000E").is_err());
+    }
+
+    #[test]
+    fn unterminated_bootargs_quotes_report_their_offset() {
+        assert_eq!(
+            Bootargs::parse("quiet init=\"/bin/sh -c"),
+            Err(BootError::UnterminatedBootargsQuote { offset: 11 })
+        );
+        assert_eq!(
+            Bootargs::parse("a=\"é\" b=\"x"),
+            Err(BootError::UnterminatedBootargsQuote { offset: 8 })
+        );
+    }
+
+    #[test]
+    fn rejects_bootargs_that_cannot_be_quoted() {
+        assert_eq!(
+            Bootargs::new().parameter("a b", None).compose(),
+            Err(BootError::InvalidName {
+                name: "a b".to_string()
+            })
+        );
+        assert_eq!(
+            Bootargs::new().parameter("a", Some("\"")).compose(),
+            Err(BootError::UnquotableValue {
+                name: "a".to_string(),
+                value: "\"".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn script_round_trip() {
+        let script = Script::new()
+            .command(["echo", "hello world"])
+            .command(["printf", "it's", "", "a\nb", "$HOME", "*", "~"])
+            .command(["/opt/cartesi/bin/app", "--flag=1,2:3", "a\\b", "\"q\""]);
+
+        let composed = script.compose();
+        assert_eq!(composed.lines().next(), Some("echo 'hello world'"));
+        assert_eq!(Script::parse(&composed).unwrap(), script);
+    }
+
+    #[test]
+    fn parsed_scripts_compose_back() {
+        let script = "# setup\nmkdir -p /mnt/data; mount /dev/pmem1 /mnt/data\n\
+                      echo \"a \\\"b\\\" \\$x\" 'c d' e\\ f \\\n  continued\n";
+        let parsed = Script::parse(script).unwrap();
+
+        assert_eq!(
+            parsed,
+            Script::new()
+                .command(["mkdir", "-p", "/mnt/data"])
+                .command(["mount", "/dev/pmem1", "/mnt/data"])
+                .command(["echo", "a \"b\" $x", "c d", "e f", "continued"])
+        );
+        assert_eq!(Script::parse(&parsed.compose()).unwrap(), parsed);
+    }
+
+    #[test]
+    fn rejects_unsupported_scripts() {
+        assert_eq!(
+            Script::parse("echo ok\necho 'open"),
+            Err(BootError::UnterminatedQuote { line: 2 })
+        );
+        assert_eq!(
+            Script::parse("echo \"a\nb"),
+            Err(BootError::UnterminatedQuote { line: 2 })
+        );
+        assert_eq!(
+            Script::parse("true\necho $HOME"),
+            Err(BootError::UnsupportedShell {
+                line: 2,
+                construct: "expansion".to_string()
+            })
+        );
+        assert_eq!(
+            Script::parse("ls | wc"),
+            Err(BootError::UnsupportedShell {
+                line: 1,
+                construct: "operator '|'".to_string()
+            })
+        );
+    }
+}
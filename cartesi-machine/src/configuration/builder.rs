@@ -0,0 +1,213 @@
+//! Fluent construction of machine configurations.
+
+use super::{DtbConfig, MachineConfig, MemoryRangeConfig, RamConfig, RollupConfig};
+use crate::errors::ConfigError;
+
+/// Builds a [`MachineConfig`] starting from the emulator defaults
+#[derive(Debug, Clone)]
+pub struct MachineConfigBuilder {
+    config: MachineConfig,
+}
+
+impl Default for MachineConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MachineConfigBuilder {
+    /// Starts from the default machine configuration
+    pub fn new() -> Self {
+        Self::from_config(MachineConfig::default())
+    }
+
+    /// Starts from an existing machine configuration
+    pub fn from_config(config: MachineConfig) -> Self {
+        Self { config }
+    }
+
+    /// Sets the RAM length and image
+    pub fn ram(mut self, length: u64, image_filename: Option<&str>) -> Self {
+        self.config.ram = RamConfig {
+            length,
+            image_filename: image_filename.map(str::to_string),
+        };
+        self
+    }
+
+    /// Sets the kernel command line and the init and entrypoint scripts
+    pub fn dtb(
+        mut self,
+        bootargs: Option<&str>,
+        init: Option<&str>,
+        entrypoint: Option<&str>,
+    ) -> Self {
+        self.config.dtb = DtbConfig {
+            bootargs: bootargs.map(str::to_string),
+            init: init.map(str::to_string),
+            entrypoint: entrypoint.map(str::to_string),
+            image_filename: self.config.dtb.image_filename,
+        };
+        self
+    }
+
+    /// Sets the DTB image
+    pub fn dtb_image(mut self, image_filename: Option<&str>) -> Self {
+        self.config.dtb.image_filename = image_filename.map(str::to_string);
+        self
+    }
+
+    /// Adds a flash drive
+    pub fn flash_drive(
+        mut self,
+        start: u64,
+        length: u64,
+        image_filename: Option<&str>,
+        shared: bool,
+    ) -> Self {
+        self.config.flash_drive.push(MemoryRangeConfig {
+            start,
+            length,
+            shared,
+            image_filename: image_filename.map(str::to_string),
+        });
+        self
+    }
+
+    /// Removes every flash drive
+    pub fn clear_flash_drives(mut self) -> Self {
+        self.config.flash_drive.clear();
+        self
+    }
+
+    /// Enables rollups with the given RX and TX buffers
+    pub fn rollup(mut self, rx_buffer: MemoryRangeConfig, tx_buffer: MemoryRangeConfig) -> Self {
+        self.config.rollup = RollupConfig {
+            has_value: true,
+            rx_buffer,
+            tx_buffer,
+        };
+        self
+    }
+
+    /// Sets the uarch RAM image
+    pub fn uarch_ram(mut self, image_filename: Option<&str>) -> Self {
+        self.config.uarch.ram.image_filename = image_filename.map(str::to_string);
+        self
+    }
+
+    /// Validates the configuration and returns it
+    pub fn build(self) -> Result<MachineConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ConfigIssue;
+    use crate::testing::machine_config;
+
+    fn json(config: &MachineConfig) -> serde_json::Value {
+        serde_json::to_value(config).unwrap()
+    }
+
+    fn range(start: u64, length: u64) -> MemoryRangeConfig {
+        MemoryRangeConfig {
+            start,
+            length,
+            shared: false,
+            image_filename: None,
+        }
+    }
+
+    #[test]
+    fn keeps_the_starting_config() {
+        let config = MachineConfigBuilder::from_config(machine_config())
+            .build()
+            .unwrap();
+
+        assert_eq!(json(&config), json(&machine_config()));
+    }
+
+    #[test]
+    fn setters_change_only_their_fields() {
+        let image = std::env::temp_dir().join(format!("builder-{}.img", std::process::id()));
+        std::fs::write(&image, [0; 0x1000]).unwrap();
+        let image = image.to_str().unwrap();
+
+        let config = MachineConfigBuilder::from_config(machine_config())
+            .dtb_image(Some(image))
+            .dtb(Some("quiet"), None, Some("echo hi"))
+            .ram(0x4000000, Some(image))
+            .flash_drive(0x80000000000000, 0x1000, None, true)
+            .uarch_ram(Some(image))
+            .build();
+
+        std::fs::remove_file(image).unwrap();
+        let config = config.unwrap();
+
+        let mut expected = machine_config();
+        expected.ram = RamConfig {
+            length: 0x4000000,
+            image_filename: Some(image.to_string()),
+        };
+        expected.dtb = DtbConfig {
+            bootargs: Some("quiet".to_string()),
+            init: None,
+            entrypoint: Some("echo hi".to_string()),
+            image_filename: Some(image.to_string()),
+        };
+        expected.flash_drive = vec![MemoryRangeConfig {
+            shared: true,
+            ..range(0x80000000000000, 0x1000)
+        }];
+        expected.uarch.ram.image_filename = Some(image.to_string());
+
+        assert_eq!(json(&config), json(&expected));
+    }
+
+    #[test]
+    fn build_rejects_zero_ram_length() {
+        let error = MachineConfigBuilder::from_config(machine_config())
+            .ram(0, None)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            error.issues,
+            [ConfigIssue::new("ram.length", "must not be zero")]
+        );
+    }
+
+    #[test]
+    fn build_rejects_overlapping_drives() {
+        let error = MachineConfigBuilder::from_config(machine_config())
+            .flash_drive(0x80000000000000, 0x2000, None, false)
+            .flash_drive(0x80000000001000, 0x1000, None, false)
+            .rollup(range(0x60000000, 0x1000), range(0x60000000, 0x1000))
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            error.issues,
+            [
+                ConfigIssue::new("flash_drive[0]", "overlaps flash_drive[1]"),
+                ConfigIssue::new("rollup.rx_buffer", "overlaps rollup.tx_buffer"),
+            ]
+        );
+    }
+
+    #[test]
+    fn clears_flash_drives() {
+        let config = MachineConfigBuilder::from_config(machine_config())
+            .flash_drive(0x80000000000000, 0x1000, None, false)
+            .flash_drive(0x80000000000000, 0x1000, None, false)
+            .clear_flash_drives()
+            .build()
+            .unwrap();
+
+        assert!(config.flash_drive.is_empty());
+    }
+}
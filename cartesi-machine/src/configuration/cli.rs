@@ -0,0 +1,830 @@
+//! Parsing and formatting of `cartesi-machine` command-line specs.
+//!
+//! Memory ranges are described as comma-separated `key:value` pairs, such as
+//! `label:root,filename:rootfs.ext2,start:0x80000000000000,shared`. Numbers may
+//! be decimal or hexadecimal, and may carry a binary unit suffix (`Ki`, `Mi`,
+//! `Gi`, `Ti`) or a left shift (`1<<30`). A backslash escapes a `,`, `:` or
+//! `\\` that is part of a value, such as a filename.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use super::{DtbConfig, MachineConfig, MemoryRangeConfig, RamConfig, RollupConfig, RuntimeConfig};
+use crate::errors::CliError;
+
+/// Parses a number in any of the notations accepted by the CLI
+pub fn parse_number(value: &str) -> Result<u64, CliError> {
+    let invalid = || CliError::InvalidNumber {
+        value: value.to_string(),
+    };
+    let trimmed = value.trim();
+
+    if let Some((base, shift)) = trimmed.split_once("<<") {
+        let base = parse_number(base)?;
+        let shift = parse_number(shift)?;
+        return u32::try_from(shift)
+            .ok()
+            .and_then(|shift| base.checked_shl(shift))
+            .filter(|result| result >> shift == base)
+            .ok_or_else(invalid);
+    }
+
+    let (digits, unit) = [("Ki", 10), ("Mi", 20), ("Gi", 30), ("Ti", 40)]
+        .into_iter()
+        .find_map(|(suffix, unit)| Some((trimmed.strip_suffix(suffix)?, unit)))
+        .unwrap_or((trimmed, 0));
+
+    let number = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => digits.parse(),
+    }
+    .map_err(|_| invalid())?;
+
+    number.checked_mul(1 << unit).ok_or_else(invalid)
+}
+
+/// Formats a number the way the CLI prints addresses and lengths
+pub fn format_number(value: u64) -> String {
+    format!("0x{:x}", value)
+}
+
+fn parse_bool(key: &str, value: Option<&str>) -> Result<bool, CliError> {
+    match value {
+        None | Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(value) => Err(CliError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+/// Splits a spec into its `key:value` pairs, values being optional
+fn parse_pairs(spec: &str) -> Vec<(String, Option<String>)> {
+    let mut pairs = Vec::new();
+    let mut key = String::new();
+    let mut value: Option<String> = None;
+    let mut chars = spec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ',' => {
+                if !key.is_empty() || value.is_some() {
+                    pairs.push((std::mem::take(&mut key), value.take()));
+                }
+            }
+            ':' if value.is_none() => value = Some(String::new()),
+            _ => {
+                let target = value.as_mut().unwrap_or(&mut key);
+                match c {
+                    '\\' => target.extend(chars.next()),
+                    _ => target.push(c),
+                }
+            }
+        }
+    }
+
+    if !key.is_empty() || value.is_some() {
+        pairs.push((key, value));
+    }
+
+    pairs
+}
+
+/// Escapes the characters [`parse_pairs`] would take as separators
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if matches!(c, ',' | ':' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn required<'a>(key: &str, value: Option<&'a str>) -> Result<&'a str, CliError> {
+    value.ok_or_else(|| CliError::MissingValue {
+        key: key.to_string(),
+    })
+}
+
+/// Length of the image file, used when a spec gives no explicit length
+fn image_length(filename: Option<&str>) -> Result<u64, CliError> {
+    let filename = filename.ok_or_else(|| CliError::MissingKey {
+        key: "length".to_string(),
+    })?;
+
+    std::fs::metadata(filename)
+        .map(|metadata| metadata.len())
+        .map_err(|error| CliError::Image {
+            filename: filename.to_string(),
+            message: error.to_string(),
+        })
+}
+
+/// Start the CLI gives the flash drive at `index` when its spec has no `start`
+pub fn flash_drive_start(index: usize) -> u64 {
+    (1 << 55) + ((index as u64) << 52)
+}
+
+/// Flash drive as given to `--flash-drive`
+#[derive(Debug, Clone)]
+pub struct FlashDriveSpec {
+    /// Label of the drive inside the guest
+    pub label: Option<String>,
+    /// Mount point of the drive inside the guest
+    pub mount: Option<String>,
+    /// User that owns the mount point
+    pub user: Option<String>,
+    /// Whether the spec gives `start`, otherwise taken from [`flash_drive_start`]
+    pub explicit_start: bool,
+    /// Memory range of the drive
+    pub config: MemoryRangeConfig,
+}
+
+impl FlashDriveSpec {
+    /// Memory range of the drive when it is the one at `index` in the drive list
+    pub fn config_at(&self, index: usize) -> MemoryRangeConfig {
+        let mut config = self.config.clone();
+
+        if !self.explicit_start {
+            config.start = flash_drive_start(index);
+        }
+
+        config
+    }
+}
+
+impl FromStr for FlashDriveSpec {
+    type Err = CliError;
+
+    /// Parses a spec with keys `label`, `filename`, `start`, `length`,
+    /// `shared`, `mount` and `user`
+    ///
+    /// The length defaults to the size of the image file, and the start to
+    /// the one the CLI gives the drive's position, see [`FlashDriveSpec::config_at`].
+    fn from_str(spec: &str) -> Result<Self, CliError> {
+        let mut label = None;
+        let mut mount = None;
+        let mut user = None;
+        let mut range = Vec::new();
+
+        for (key, value) in parse_pairs(spec) {
+            match key.as_str() {
+                "label" => label = Some(required(&key, value.as_deref())?.to_string()),
+                "mount" => mount = Some(required(&key, value.as_deref())?.to_string()),
+                "user" => user = Some(required(&key, value.as_deref())?.to_string()),
+                _ => range.push((key, value)),
+            }
+        }
+
+        let (start, mut config) = memory_range_from_pairs(range)?;
+        config.start = start.unwrap_or(flash_drive_start(0));
+
+        Ok(Self {
+            label,
+            mount,
+            user,
+            explicit_start: start.is_some(),
+            config,
+        })
+    }
+}
+
+impl Display for FlashDriveSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(label) = &self.label {
+            write!(f, "label:{},", escape_value(label))?;
+        }
+
+        write!(
+            f,
+            "{}",
+            format_range_pairs(&self.config, self.explicit_start)
+        )?;
+
+        if let Some(mount) = &self.mount {
+            write!(f, ",mount:{}", escape_value(mount))?;
+        }
+
+        if let Some(user) = &self.user {
+            write!(f, ",user:{}", escape_value(user))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a memory range spec with keys `filename`, `start`, `length` and `shared`
+pub fn parse_memory_range(spec: &str) -> Result<MemoryRangeConfig, CliError> {
+    let (start, mut config) = memory_range_from_pairs(parse_pairs(spec))?;
+
+    config.start = start.ok_or_else(|| CliError::MissingKey {
+        key: "start".to_string(),
+    })?;
+
+    Ok(config)
+}
+
+/// Parses the pairs of a memory range spec, returning its start separately
+/// since it is optional for flash drives
+fn memory_range_from_pairs(
+    pairs: Vec<(String, Option<String>)>,
+) -> Result<(Option<u64>, MemoryRangeConfig), CliError> {
+    let mut start = None;
+    let mut length = None;
+    let mut shared = false;
+    let mut image_filename = None;
+
+    for (key, value) in pairs {
+        let value = value.as_deref();
+
+        match key.as_str() {
+            "filename" => image_filename = Some(required(&key, value)?.to_string()),
+            "start" => start = Some(parse_number(required(&key, value)?)?),
+            "length" => length = Some(parse_number(required(&key, value)?)?),
+            "shared" => shared = parse_bool(&key, value)?,
+            _ => {
+                return Err(CliError::UnknownKey {
+                    key: key.to_string(),
+                })
+            }
+        }
+    }
+
+    let length = match length {
+        Some(length) => length,
+        None => image_length(image_filename.as_deref())?,
+    };
+
+    Ok((
+        start,
+        MemoryRangeConfig {
+            start: 0,
+            length,
+            shared,
+            image_filename,
+        },
+    ))
+}
+
+/// Formats a memory range as a spec accepted by [`parse_memory_range`]
+pub fn format_memory_range(config: &MemoryRangeConfig) -> String {
+    format_range_pairs(config, true)
+}
+
+fn format_range_pairs(config: &MemoryRangeConfig, with_start: bool) -> String {
+    let mut pairs = Vec::new();
+
+    if let Some(filename) = &config.image_filename {
+        pairs.push(format!("filename:{}", escape_value(filename)));
+    }
+
+    if with_start {
+        pairs.push(format!("start:{}", format_number(config.start)));
+    }
+
+    pairs.push(format!("length:{}", format_number(config.length)));
+
+    if config.shared {
+        pairs.push("shared".to_string());
+    }
+
+    pairs.join(",")
+}
+
+/// Parses the specs given to `--rollup-rx-buffer` and `--rollup-tx-buffer`
+pub fn parse_rollup(rx_buffer: &str, tx_buffer: &str) -> Result<RollupConfig, CliError> {
+    Ok(RollupConfig {
+        has_value: true,
+        rx_buffer: parse_memory_range(rx_buffer)?,
+        tx_buffer: parse_memory_range(tx_buffer)?,
+    })
+}
+
+/// Formats the RX and TX buffer specs of a rollup configuration, if it has a value
+pub fn format_rollup(config: &RollupConfig) -> Option<(String, String)> {
+    config.has_value.then(|| {
+        (
+            format_memory_range(&config.rx_buffer),
+            format_memory_range(&config.tx_buffer),
+        )
+    })
+}
+
+/// Splits `--option=value` into its option and value
+fn split_option(arg: &str) -> (&str, Option<&str>) {
+    match arg.split_once('=') {
+        Some((option, value)) => (option, Some(value)),
+        None => (arg, None),
+    }
+}
+
+/// Parses `--ram-length` and `--ram-image`, starting from `ram`
+pub fn parse_ram_args<S: AsRef<str>>(ram: RamConfig, args: &[S]) -> Result<RamConfig, CliError> {
+    let mut ram = ram;
+
+    for arg in args {
+        match split_option(arg.as_ref()) {
+            ("--ram-length", value) => ram.length = parse_number(required("--ram-length", value)?)?,
+            ("--ram-image", value) => {
+                ram.image_filename = Some(required("--ram-image", value)?.to_string())
+            }
+            (option, _) => {
+                return Err(CliError::UnknownOption {
+                    option: option.to_string(),
+                })
+            }
+        }
+    }
+
+    Ok(ram)
+}
+
+/// Formats a RAM configuration as `--ram-length` and `--ram-image` options
+pub fn format_ram_args(config: &RamConfig) -> Vec<String> {
+    let mut args = vec![format!("--ram-length={}", format_number(config.length))];
+
+    if let Some(filename) = &config.image_filename {
+        args.push(format!("--ram-image={}", filename));
+    }
+
+    args
+}
+
+fn append(target: &mut Option<String>, value: &str) {
+    *target = Some(match target.take() {
+        Some(current) if !current.is_empty() => format!("{} {}", current, value),
+        _ => value.to_string(),
+    });
+}
+
+/// Parses `--dtb-image`, `--no-bootargs`, `--append-bootargs`, `--append-init`
+/// and `--append-entrypoint`, starting from `dtb`
+pub fn parse_dtb_args<S: AsRef<str>>(dtb: DtbConfig, args: &[S]) -> Result<DtbConfig, CliError> {
+    let mut dtb = dtb;
+
+    for arg in args {
+        match split_option(arg.as_ref()) {
+            ("--dtb-image", value) => {
+                dtb.image_filename = Some(required("--dtb-image", value)?.to_string())
+            }
+            ("--no-bootargs", None) => dtb.bootargs = None,
+            ("--append-bootargs", value) => {
+                append(&mut dtb.bootargs, required("--append-bootargs", value)?)
+            }
+            ("--append-init", value) => {
+                let command = required("--append-init", value)?;
+                let init = dtb.init.get_or_insert_with(String::new);
+                init.push_str(command);
+                init.push('\n');
+            }
+            ("--append-entrypoint", value) => {
+                let command = required("--append-entrypoint", value)?;
+                let entrypoint = dtb.entrypoint.get_or_insert_with(String::new);
+                entrypoint.push_str(command);
+                entrypoint.push('\n');
+            }
+            (option, _) => {
+                return Err(CliError::UnknownOption {
+                    option: option.to_string(),
+                })
+            }
+        }
+    }
+
+    Ok(dtb)
+}
+
+/// Formats a DTB configuration as options that rebuild it from scratch
+pub fn format_dtb_args(config: &DtbConfig) -> Vec<String> {
+    let mut args = vec!["--no-bootargs".to_string()];
+
+    if let Some(bootargs) = config.bootargs.as_deref().filter(|b| !b.is_empty()) {
+        args.push(format!("--append-bootargs={}", bootargs));
+    }
+
+    for command in config.init.iter().flat_map(|init| init.lines()) {
+        args.push(format!("--append-init={}", command));
+    }
+
+    for command in config
+        .entrypoint
+        .iter()
+        .flat_map(|entrypoint| entrypoint.lines())
+    {
+        args.push(format!("--append-entrypoint={}", command));
+    }
+
+    if let Some(filename) = &config.image_filename {
+        args.push(format!("--dtb-image={}", filename));
+    }
+
+    args
+}
+
+/// Label the CLI gives its default flash drive
+pub(crate) const ROOT_LABEL: &str = "root";
+
+fn find_label(labels: &[Option<String>], label: &str) -> Option<usize> {
+    labels
+        .iter()
+        .position(|other| other.as_deref() == Some(label))
+}
+
+/// Applies a `cartesi-machine` argument list to a machine and runtime configuration
+///
+/// Besides the RAM and DTB options, this understands `--no-root-flash-drive`,
+/// `--flash-drive`, `--rollup-rx-buffer`, `--rollup-tx-buffer`,
+/// `--htif-console-getchar`, `--htif-yield-manual`, `--htif-yield-automatic`,
+/// `--uarch-ram-image`, `--skip-root-hash-check`, `--skip-version-check` and
+/// `--concurrency=update_merkle_tree:<n>`.
+///
+/// As in the CLI, the first drive of `config` is the one labeled `root`, and a
+/// `--flash-drive` whose label is already taken replaces that drive in place.
+pub fn parse_args<S: AsRef<str>>(
+    config: MachineConfig,
+    runtime: RuntimeConfig,
+    args: &[S],
+) -> Result<(MachineConfig, RuntimeConfig), CliError> {
+    let mut config = config;
+    let mut runtime = runtime;
+    let mut labels: Vec<Option<String>> = (0..config.flash_drive.len())
+        .map(|index| (index == 0).then(|| ROOT_LABEL.to_string()))
+        .collect();
+
+    for arg in args {
+        let arg = arg.as_ref();
+
+        match split_option(arg) {
+            ("--ram-length" | "--ram-image", _) => {
+                config.ram = parse_ram_args(config.ram, &[arg])?;
+            }
+            (
+                "--dtb-image"
+                | "--no-bootargs"
+                | "--append-bootargs"
+                | "--append-init"
+                | "--append-entrypoint",
+                _,
+            ) => {
+                config.dtb = parse_dtb_args(config.dtb, &[arg])?;
+            }
+            ("--no-root-flash-drive", None) => {
+                if let Some(index) = find_label(&labels, ROOT_LABEL) {
+                    labels.remove(index);
+                    config.flash_drive.remove(index);
+                }
+            }
+            ("--flash-drive", value) => {
+                let spec: FlashDriveSpec = required("--flash-drive", value)?.parse()?;
+
+                match spec
+                    .label
+                    .as_deref()
+                    .and_then(|label| find_label(&labels, label))
+                {
+                    Some(index) => config.flash_drive[index] = spec.config_at(index),
+                    None => {
+                        config.flash_drive.push(spec.config_at(labels.len()));
+                        labels.push(spec.label);
+                    }
+                }
+            }
+            ("--rollup-rx-buffer", value) => {
+                config.rollup.rx_buffer =
+                    parse_memory_range(required("--rollup-rx-buffer", value)?)?;
+                config.rollup.has_value = true;
+            }
+            ("--rollup-tx-buffer", value) => {
+                config.rollup.tx_buffer =
+                    parse_memory_range(required("--rollup-tx-buffer", value)?)?;
+                config.rollup.has_value = true;
+            }
+            ("--htif-console-getchar", None) => config.htif.console_getchar = true,
+            ("--htif-yield-manual", None) => config.htif.yield_manual = true,
+            ("--htif-yield-automatic", None) => config.htif.yield_automatic = true,
+            ("--uarch-ram-image", value) => {
+                config.uarch.ram.image_filename =
+                    Some(required("--uarch-ram-image", value)?.to_string());
+            }
+            ("--skip-root-hash-check", None) => runtime.skip_root_hash_check = true,
+            ("--skip-version-check", None) => runtime.skip_version_check = true,
+            ("--concurrency", value) => {
+                for (key, value) in parse_pairs(required("--concurrency", value)?) {
+                    match key.as_str() {
+                        "update_merkle_tree" => {
+                            runtime.concurrency.update_merkle_tree =
+                                parse_number(required(&key, value.as_deref())?)?
+                        }
+                        _ => return Err(CliError::UnknownKey { key }),
+                    }
+                }
+            }
+            (option, _) => {
+                return Err(CliError::UnknownOption {
+                    option: option.to_string(),
+                })
+            }
+        }
+    }
+
+    Ok((config, runtime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::machine_config;
+
+    #[test]
+    fn parses_numbers_in_every_notation() {
+        for (value, expected) in [
+            ("4096", 4096),
+            ("0x1000", 4096),
+            ("0X1000", 4096),
+            ("4Ki", 4096),
+            ("2Mi", 2 << 20),
+            ("0x10Gi", 16 << 30),
+            ("1Ti", 1 << 40),
+            ("1<<12", 4096),
+            ("0x8<<52", 0x80000000000000),
+            (" 12 ", 12),
+        ] {
+            assert_eq!(parse_number(value), Ok(expected), "{}", value);
+        }
+
+        for value in [
+            "",
+            "0x",
+            "ten",
+            "-1",
+            "1<<64",
+            "2<<63",
+            "16777216Ti",
+            "1Ki<<60",
+        ] {
+            assert_eq!(
+                parse_number(value),
+                Err(CliError::InvalidNumber {
+                    value: value.to_string()
+                }),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn formatted_numbers_parse_back() {
+        for value in [0, 1, 4096, 0x80000000000000, u64::MAX] {
+            assert_eq!(parse_number(&format_number(value)), Ok(value));
+        }
+    }
+
+    #[test]
+    fn flash_drive_start_is_optional() {
+        let spec: FlashDriveSpec = "label:data,length:0x1000,shared".parse().unwrap();
+
+        assert!(!spec.explicit_start);
+        assert_eq!(spec.label.as_deref(), Some("data"));
+        assert_eq!(spec.config_at(0).start, 0x80000000000000);
+        assert_eq!(spec.config_at(1).start, 0x90000000000000);
+        assert_eq!(spec.to_string(), "label:data,length:0x1000,shared");
+
+        let spec: FlashDriveSpec = "start:0x1000,length:4Ki".parse().unwrap();
+        assert!(spec.explicit_start);
+        assert_eq!(spec.config_at(3).start, 0x1000);
+    }
+
+    #[test]
+    fn flash_drive_specs_round_trip() {
+        for spec in [
+            "label:root,filename:rootfs.ext2,start:0x80000000000000,length:0x4000000",
+            "label:data,start:0x90000000000000,length:0x1000,shared,mount:/mnt/data,user:dapp",
+            "length:0x1000",
+        ] {
+            let parsed: FlashDriveSpec = spec.parse().unwrap();
+            assert_eq!(parsed.to_string(), spec);
+
+            let reparsed: FlashDriveSpec = parsed.to_string().parse().unwrap();
+            assert_eq!(
+                format_memory_range(&reparsed.config_at(2)),
+                format_memory_range(&parsed.config_at(2))
+            );
+        }
+    }
+
+    #[test]
+    fn memory_range_specs_round_trip() {
+        let config = parse_memory_range("start:0x60000000,length:2Mi,shared:false").unwrap();
+
+        assert_eq!(config.start, 0x60000000);
+        assert_eq!(config.length, 2 << 20);
+        assert!(!config.shared);
+        assert_eq!(
+            format_memory_range(&config),
+            "start:0x60000000,length:0x200000"
+        );
+        assert_eq!(
+            format_memory_range(&parse_memory_range(&format_memory_range(&config)).unwrap()),
+            format_memory_range(&config)
+        );
+    }
+
+    #[test]
+    fn memory_ranges_need_a_start() {
+        assert_eq!(
+            parse_memory_range("length:0x1000").unwrap_err(),
+            CliError::MissingKey {
+                key: "start".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_range_specs() {
+        assert_eq!(
+            parse_memory_range("start:0,length:1,size:2").unwrap_err(),
+            CliError::UnknownKey {
+                key: "size".to_string()
+            }
+        );
+        assert_eq!(
+            parse_memory_range("start:0,length").unwrap_err(),
+            CliError::MissingValue {
+                key: "length".to_string()
+            }
+        );
+        assert_eq!(
+            parse_memory_range("start:0,length:1,shared:yes").unwrap_err(),
+            CliError::InvalidValue {
+                key: "shared".to_string(),
+                value: "yes".to_string()
+            }
+        );
+        assert_eq!(
+            parse_memory_range("start:0").unwrap_err(),
+            CliError::MissingKey {
+                key: "length".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn length_defaults_to_the_image_size() {
+        let path = std::env::temp_dir().join(format!("cli-{}.img", std::process::id()));
+        std::fs::write(&path, [0; 0x3000]).unwrap();
+        let filename = path.to_str().unwrap();
+
+        let spec: FlashDriveSpec = format!("filename:{}", filename).parse().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(spec.config.length, 0x3000);
+        assert!(matches!(
+            format!("filename:{}", filename).parse::<FlashDriveSpec>(),
+            Err(CliError::Image { .. })
+        ));
+    }
+
+    #[test]
+    fn ram_and_dtb_args_round_trip() {
+        let ram = parse_ram_args(
+            machine_config().ram,
+            &["--ram-length=64Mi", "--ram-image=linux.bin"],
+        )
+        .unwrap();
+        assert_eq!(ram.length, 64 << 20);
+        assert_eq!(
+            format_ram_args(&ram),
+            ["--ram-length=0x4000000", "--ram-image=linux.bin"]
+        );
+
+        let dtb = parse_dtb_args(
+            machine_config().dtb,
+            &[
+                "--append-bootargs=quiet",
+                "--append-bootargs=ro",
+                "--append-init=echo hi",
+                "--append-entrypoint=/bin/app",
+            ],
+        )
+        .unwrap();
+        assert_eq!(dtb.bootargs.as_deref(), Some("quiet ro"));
+        assert_eq!(dtb.init.as_deref(), Some("echo hi\n"));
+
+        let args = format_dtb_args(&dtb);
+        assert_eq!(
+            serde_json::to_value(parse_dtb_args(machine_config().dtb, &args).unwrap()).unwrap(),
+            serde_json::to_value(dtb).unwrap()
+        );
+    }
+
+    #[test]
+    fn drives_without_start_take_their_slot() {
+        let (config, _) = parse_args(
+            machine_config(),
+            RuntimeConfig::default(),
+            &[
+                "--flash-drive=label:root,length:0x1000",
+                "--flash-drive=label:data,start:0x1000000000000000,length:0x1000",
+                "--flash-drive=label:extra,length:0x1000",
+            ],
+        )
+        .unwrap();
+
+        let starts: Vec<u64> = config.flash_drive.iter().map(|drive| drive.start).collect();
+        assert_eq!(
+            starts,
+            [0x80000000000000, 0x1000000000000000, 0xa0000000000000]
+        );
+    }
+
+    #[test]
+    fn drives_with_a_taken_label_replace_it() {
+        let mut base = machine_config();
+        base.flash_drive = vec![MemoryRangeConfig {
+            start: 0x80000000000000,
+            length: 0x1000,
+            shared: false,
+            image_filename: Some("rootfs.ext2".to_string()),
+        }];
+
+        let (config, _) = parse_args(
+            base.clone(),
+            RuntimeConfig::default(),
+            &[
+                "--flash-drive=label:data,length:0x2000",
+                "--flash-drive=label:root,filename:other.ext2,length:0x4000",
+                "--flash-drive=label:data,length:0x3000,shared",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.flash_drive.len(), 2);
+        assert_eq!(
+            format_memory_range(&config.flash_drive[0]),
+            "filename:other.ext2,start:0x80000000000000,length:0x4000"
+        );
+        assert_eq!(
+            format_memory_range(&config.flash_drive[1]),
+            "start:0x90000000000000,length:0x3000,shared"
+        );
+
+        let (config, _) = parse_args(
+            base,
+            RuntimeConfig::default(),
+            &[
+                "--flash-drive=label:data,length:0x2000",
+                "--no-root-flash-drive",
+                "--flash-drive=label:root,length:0x1000",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            config
+                .flash_drive
+                .iter()
+                .map(format_memory_range)
+                .collect::<Vec<_>>(),
+            [
+                "start:0x90000000000000,length:0x2000",
+                "start:0x90000000000000,length:0x1000",
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_values_parse_back() {
+        let spec = FlashDriveSpec {
+            label: Some("a,b".to_string()),
+            mount: Some("/mnt/x:y".to_string()),
+            user: None,
+            explicit_start: false,
+            config: MemoryRangeConfig {
+                start: 0,
+                length: 0x1000,
+                shared: true,
+                image_filename: Some("dir\\file,1:2".to_string()),
+            },
+        };
+        let formatted = spec.to_string();
+
+        assert_eq!(
+            formatted,
+            "label:a\\,b,filename:dir\\\\file\\,1\\:2,length:0x1000,shared,mount:/mnt/x\\:y"
+        );
+
+        let parsed: FlashDriveSpec = formatted.parse().unwrap();
+        assert_eq!(parsed.label, spec.label);
+        assert_eq!(parsed.mount, spec.mount);
+        assert_eq!(parsed.config.image_filename, spec.config.image_filename);
+    }
+}
@@ -0,0 +1,377 @@
+//! Export of configurations for the stock `cartesi-machine` tool.
+
+use std::fmt::Write;
+
+use super::cli::{
+    format_dtb_args, format_memory_range, format_number, format_ram_args, FlashDriveSpec,
+    ROOT_LABEL,
+};
+use super::{MachineConfig, MemoryRangeConfig, RuntimeConfig};
+
+impl MachineConfig {
+    /// Converts the configuration into equivalent `cartesi-machine` arguments
+    ///
+    /// The command line cannot set processor, CLINT, TLB or uarch processor
+    /// state; use [`MachineConfig::to_lua`] when those matter. The arguments
+    /// assume the stock defaults, whose only drive is the root drive: the
+    /// first drive takes its place and the others follow it.
+    pub fn to_cli_args(&self, runtime: &RuntimeConfig) -> Vec<String> {
+        let mut args = format_ram_args(&self.ram);
+        args.extend(format_dtb_args(&self.dtb));
+
+        if self.flash_drive.is_empty() {
+            args.push("--no-root-flash-drive".to_string());
+        }
+
+        for (index, drive) in self.flash_drive.iter().enumerate() {
+            let spec = FlashDriveSpec {
+                label: (index == 0).then(|| ROOT_LABEL.to_string()),
+                mount: None,
+                user: None,
+                explicit_start: true,
+                config: drive.clone(),
+            };
+            args.push(format!("--flash-drive={}", spec));
+        }
+
+        if self.rollup.has_value {
+            args.push(format!(
+                "--rollup-rx-buffer={}",
+                format_memory_range(&self.rollup.rx_buffer)
+            ));
+            args.push(format!(
+                "--rollup-tx-buffer={}",
+                format_memory_range(&self.rollup.tx_buffer)
+            ));
+        }
+
+        for (enabled, option) in [
+            (self.htif.console_getchar, "--htif-console-getchar"),
+            (self.htif.yield_manual, "--htif-yield-manual"),
+            (self.htif.yield_automatic, "--htif-yield-automatic"),
+            (runtime.skip_root_hash_check, "--skip-root-hash-check"),
+            (runtime.skip_version_check, "--skip-version-check"),
+        ] {
+            if enabled {
+                args.push(option.to_string());
+            }
+        }
+
+        if let Some(filename) = &self.uarch.ram.image_filename {
+            args.push(format!("--uarch-ram-image={}", filename));
+        }
+
+        if runtime.concurrency.update_merkle_tree != 0 {
+            args.push(format!(
+                "--concurrency=update_merkle_tree:{}",
+                runtime.concurrency.update_merkle_tree
+            ));
+        }
+
+        args
+    }
+
+    /// Converts the configuration into a Lua table, in the layout written by
+    /// `cartesi-machine --store-config`
+    pub fn to_lua(&self) -> String {
+        let mut lua = LuaWriter::default();
+
+        lua.open("return {");
+
+        lua.open("processor = {");
+        lua.array("x", &self.processor.x[1..], 1);
+        lua.array("f", &self.processor.f, 0);
+        let p = &self.processor;
+        for (name, value) in [
+            ("pc", p.pc),
+            ("fcsr", p.fcsr),
+            ("mvendorid", p.mvendorid),
+            ("marchid", p.marchid),
+            ("mimpid", p.mimpid),
+            ("mcycle", p.mcycle),
+            ("icycleinstret", p.icycleinstret),
+            ("mstatus", p.mstatus),
+            ("mtvec", p.mtvec),
+            ("mscratch", p.mscratch),
+            ("mepc", p.mepc),
+            ("mcause", p.mcause),
+            ("mtval", p.mtval),
+            ("misa", p.misa),
+            ("mie", p.mie),
+            ("mip", p.mip),
+            ("medeleg", p.medeleg),
+            ("mideleg", p.mideleg),
+            ("mcounteren", p.mcounteren),
+            ("menvcfg", p.menvcfg),
+            ("stvec", p.stvec),
+            ("sscratch", p.sscratch),
+            ("sepc", p.sepc),
+            ("scause", p.scause),
+            ("stval", p.stval),
+            ("satp", p.satp),
+            ("scounteren", p.scounteren),
+            ("senvcfg", p.senvcfg),
+            ("ilrsc", p.ilrsc),
+            ("iflags", p.iflags),
+        ] {
+            lua.number(name, value);
+        }
+        lua.close();
+
+        lua.open("ram = {");
+        lua.number("length", self.ram.length);
+        lua.string("image_filename", self.ram.image_filename.as_deref());
+        lua.close();
+
+        lua.open("dtb = {");
+        lua.string("bootargs", self.dtb.bootargs.as_deref());
+        lua.string("init", self.dtb.init.as_deref());
+        lua.string("entrypoint", self.dtb.entrypoint.as_deref());
+        lua.string("image_filename", self.dtb.image_filename.as_deref());
+        lua.close();
+
+        lua.open("flash_drive = {");
+        for drive in &self.flash_drive {
+            lua.open("{");
+            lua.memory_range(drive);
+            lua.close();
+        }
+        lua.close();
+
+        lua.open("tlb = {");
+        lua.string("image_filename", self.tlb.image_filename.as_deref());
+        lua.close();
+
+        lua.open("clint = {");
+        lua.number("mtimecmp", self.clint.mtimecmp);
+        lua.close();
+
+        lua.open("htif = {");
+        lua.number("fromhost", self.htif.fromhost);
+        lua.number("tohost", self.htif.tohost);
+        lua.boolean("console_getchar", self.htif.console_getchar);
+        lua.boolean("yield_manual", self.htif.yield_manual);
+        lua.boolean("yield_automatic", self.htif.yield_automatic);
+        lua.close();
+
+        if self.rollup.has_value {
+            lua.open("rollup = {");
+            lua.open("rx_buffer = {");
+            lua.memory_range(&self.rollup.rx_buffer);
+            lua.close();
+            lua.open("tx_buffer = {");
+            lua.memory_range(&self.rollup.tx_buffer);
+            lua.close();
+            lua.close();
+        }
+
+        lua.open("uarch = {");
+        lua.open("processor = {");
+        lua.array("x", &self.uarch.processor.x[1..], 1);
+        lua.number("pc", self.uarch.processor.pc);
+        lua.number("cycle", self.uarch.processor.cycle);
+        lua.boolean("halt_flag", self.uarch.processor.halt_flag);
+        lua.close();
+        lua.open("ram = {");
+        lua.string("image_filename", self.uarch.ram.image_filename.as_deref());
+        lua.close();
+        lua.close();
+
+        lua.depth -= 1;
+        lua.output.push_str("}\n");
+        lua.output
+    }
+}
+
+/// Writes an indented Lua table constructor
+#[derive(Default)]
+struct LuaWriter {
+    output: String,
+    depth: usize,
+}
+
+impl LuaWriter {
+    fn line(&mut self, line: &str) {
+        self.output.push_str(&"  ".repeat(self.depth));
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn open(&mut self, line: &str) {
+        self.line(line);
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.depth -= 1;
+        self.line("},");
+    }
+
+    fn number(&mut self, name: &str, value: u64) {
+        self.line(&format!("{} = {},", name, format_number(value)));
+    }
+
+    fn boolean(&mut self, name: &str, value: bool) {
+        self.line(&format!("{} = {},", name, value));
+    }
+
+    fn string(&mut self, name: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.line(&format!("{} = {},", name, lua_string(value)));
+        }
+    }
+
+    /// Writes `values` with explicit indices starting at `first`
+    fn array(&mut self, name: &str, values: &[u64], first: usize) {
+        self.open(&format!("{} = {{", name));
+        for (i, value) in values.iter().enumerate() {
+            self.line(&format!("[{}] = {},", first + i, format_number(*value)));
+        }
+        self.close();
+    }
+
+    fn memory_range(&mut self, range: &MemoryRangeConfig) {
+        self.number("start", range.start);
+        self.number("length", range.length);
+        self.boolean("shared", range.shared);
+        self.string("image_filename", range.image_filename.as_deref());
+    }
+}
+
+/// Quotes a string as a Lua string literal
+fn lua_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+
+    for byte in value.bytes() {
+        match byte {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            0x20..=0x7e => quoted.push(byte as char),
+            _ => write!(quoted, "\\{:03}", byte).unwrap(),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cli::parse_args;
+    use super::*;
+    use crate::testing::machine_config;
+
+    fn drive(start: u64, image_filename: Option<&str>, shared: bool) -> MemoryRangeConfig {
+        MemoryRangeConfig {
+            start,
+            length: 0x1000,
+            shared,
+            image_filename: image_filename.map(str::to_string),
+        }
+    }
+
+    /// Stock defaults as far as the CLI is concerned: a single root drive
+    fn defaults() -> MachineConfig {
+        let mut config = machine_config();
+        config.flash_drive = vec![drive(0x80000000000000, Some("rootfs.ext2"), false)];
+        config.dtb.bootargs = Some("quiet console=hvc0".to_string());
+        config
+    }
+
+    fn exported() -> (MachineConfig, RuntimeConfig) {
+        let mut config = machine_config();
+        config.ram.length = 64 << 20;
+        config.ram.image_filename = Some("linux.bin".to_string());
+        config.dtb.bootargs = Some("quiet".to_string());
+        config.dtb.init = Some("echo a\necho b\n".to_string());
+        config.dtb.entrypoint = Some("/bin/app\n".to_string());
+        config.flash_drive = vec![
+            drive(0x80000000000000, Some("/images/root,v2.ext2"), false),
+            drive(0x90000000000000, Some("C:\\data\\a:b.img"), true),
+            drive(0xa0000000000000, None, false),
+        ];
+        config.rollup.has_value = true;
+        config.rollup.rx_buffer = drive(0x60000000, None, true);
+        config.rollup.tx_buffer = drive(0x60200000, Some("tx,buffer"), true);
+        config.htif.yield_manual = true;
+        config.htif.console_getchar = true;
+        config.uarch.ram.image_filename = Some("uarch-ram.bin".to_string());
+
+        let runtime = RuntimeConfig::default()
+            .skip_version_check(true)
+            .update_merkle_tree_concurrency(4);
+
+        (config, runtime)
+    }
+
+    fn json<T: serde::Serialize>(value: &T) -> serde_json::Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn cli_args_round_trip() {
+        let (config, runtime) = exported();
+        let args = config.to_cli_args(&runtime);
+        let (parsed, parsed_runtime) =
+            parse_args(defaults(), RuntimeConfig::default(), &args).unwrap();
+
+        assert_eq!(json(&parsed), json(&config));
+        assert_eq!(json(&parsed_runtime), json(&runtime));
+    }
+
+    #[test]
+    fn cli_args_escape_separators_in_filenames() {
+        let (config, runtime) = exported();
+        let args = config.to_cli_args(&runtime);
+
+        assert!(args.contains(
+            &"--flash-drive=label:root,filename:/images/root\\,v2.ext2,start:0x80000000000000,length:0x1000"
+                .to_string()
+        ));
+        assert!(args.contains(
+            &"--flash-drive=filename:C\\:\\\\data\\\\a\\:b.img,start:0x90000000000000,length:0x1000,shared"
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn cli_args_without_drives_remove_the_root_drive() {
+        let mut config = machine_config();
+        config.flash_drive.clear();
+        let args = config.to_cli_args(&RuntimeConfig::default());
+
+        assert!(args.contains(&"--no-root-flash-drive".to_string()));
+
+        let (parsed, _) = parse_args(defaults(), RuntimeConfig::default(), &args).unwrap();
+        assert!(parsed.flash_drive.is_empty());
+    }
+
+    #[test]
+    fn lua_round_trip() {
+        let (mut config, _) = exported();
+        config.processor.x[5] = 0x1234;
+        config.processor.pc = 0x1000;
+        config.processor.mcycle = 42;
+        config.clint.mtimecmp = 7;
+        config.uarch.processor.cycle = 3;
+        config.uarch.processor.halt_flag = true;
+        config.dtb.init = Some("echo \"quoted\" \\ back\tslash\n".to_string());
+
+        let mut imported = machine_config();
+        imported.update_from_lua(&config.to_lua()).unwrap();
+
+        assert_eq!(json(&imported), json(&config));
+    }
+
+    #[test]
+    fn lua_strings_are_escaped() {
+        assert_eq!(
+            lua_string("a\"b\\c\nd\u{7f}é"),
+            "\"a\\\"b\\\\c\\nd\\127\\195\\169\""
+        );
+    }
+}
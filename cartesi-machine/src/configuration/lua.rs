@@ -0,0 +1,1256 @@
+//! Import of Lua machine configs written by `cartesi-machine --store-config`.
+//!
+//! Only the table-constructor subset of Lua used by those files is accepted:
+//! an optional `return` followed by a single table made of nested tables,
+//! integers, strings, booleans and `nil`.
+
+use std::collections::BTreeMap;
+
+use super::{
+    MachineConfig, MemoryRangeConfig, ProcessorConfig, RollupConfig, UarchConfig,
+    UarchProcessorConfig,
+};
+use crate::errors::LuaError;
+
+impl MachineConfig {
+    /// Parses a Lua machine config, keeping defaults for absent fields
+    pub fn from_lua(source: &str) -> Result<MachineConfig, LuaError> {
+        let mut config = MachineConfig::default();
+        config.update_from_lua(source)?;
+        Ok(config)
+    }
+
+    /// Overrides the fields present in a Lua machine config
+    pub fn update_from_lua(&mut self, source: &str) -> Result<(), LuaError> {
+        let value = Parser::new(source).chunk()?;
+        machine(self, &value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Integer(u64),
+    String(String),
+    Symbol(u8),
+    End,
+}
+
+#[derive(Debug)]
+enum Key {
+    Name(String),
+    Index(u64),
+}
+
+#[derive(Debug)]
+enum Value {
+    Integer(u64),
+    String(String),
+    Boolean(bool),
+    Table(Vec<(Key, Value)>),
+}
+
+#[derive(Clone)]
+struct Lexer<'a> {
+    source: &'a [u8],
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek_byte(&self, offset: usize) -> Option<u8> {
+        self.source.get(self.position + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let byte = self.peek_byte(0)?;
+        self.position += 1;
+        if byte == b'\n' {
+            self.line += 1;
+        }
+        Some(byte)
+    }
+
+    fn syntax(&self, message: impl Into<String>) -> LuaError {
+        LuaError::Syntax {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn unsupported(&self, construct: impl Into<String>) -> LuaError {
+        LuaError::Unsupported {
+            line: self.line,
+            construct: construct.into(),
+        }
+    }
+
+    /// Skips whitespace and comments
+    fn skip_trivia(&mut self) -> Result<(), LuaError> {
+        loop {
+            match (self.peek_byte(0), self.peek_byte(1)) {
+                (Some(byte), _) if byte.is_ascii_whitespace() => {
+                    self.bump();
+                }
+                (Some(b'-'), Some(b'-')) => {
+                    self.position += 2;
+                    if self.peek_byte(0) == Some(b'[') && self.long_bracket_level().is_some() {
+                        self.long_bracket("comment")?;
+                    } else {
+                        while !matches!(self.peek_byte(0), None | Some(b'\n')) {
+                            self.bump();
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Returns the level of a long bracket opening at the current position
+    fn long_bracket_level(&self) -> Option<usize> {
+        let mut level = 0;
+        while self.peek_byte(1 + level) == Some(b'=') {
+            level += 1;
+        }
+        (self.peek_byte(1 + level) == Some(b'[')).then_some(level)
+    }
+
+    /// Skips a long bracket, as used by comments
+    fn long_bracket(&mut self, what: &str) -> Result<(), LuaError> {
+        let level = self.long_bracket_level().unwrap_or(0);
+        let start = self.line;
+        self.position += level + 2;
+
+        loop {
+            match self.bump() {
+                Some(b']') => {
+                    let closes = (0..level).all(|i| self.peek_byte(i) == Some(b'='))
+                        && self.peek_byte(level) == Some(b']');
+                    if closes {
+                        self.position += level + 1;
+                        return Ok(());
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    return Err(LuaError::Syntax {
+                        line: start,
+                        message: format!("unfinished long {}", what),
+                    })
+                }
+            }
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, LuaError> {
+        self.skip_trivia()?;
+
+        let Some(byte) = self.peek_byte(0) else {
+            return Ok(Token::End);
+        };
+
+        match byte {
+            b'{' | b'}' | b']' | b'=' | b',' | b';' => {
+                self.bump();
+                Ok(Token::Symbol(byte))
+            }
+            b'[' if self.long_bracket_level().is_some() => Err(self.unsupported("long string")),
+            b'[' => {
+                self.bump();
+                Ok(Token::Symbol(byte))
+            }
+            b'"' | b'\'' => self.string(),
+            b'0'..=b'9' => self.number(),
+            b'.' if matches!(self.peek_byte(1), Some(b'0'..=b'9')) => {
+                Err(self.unsupported("floating-point number"))
+            }
+            b'-' => Err(self.unsupported("unary minus")),
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
+                let start = self.position;
+                while matches!(
+                    self.peek_byte(0),
+                    Some(b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_')
+                ) {
+                    self.bump();
+                }
+                let name = std::str::from_utf8(&self.source[start..self.position])
+                    .expect("names are ASCII");
+                Ok(Token::Name(name.to_string()))
+            }
+            _ if byte.is_ascii_punctuation() => {
+                Err(self.unsupported(format!("operator '{}'", byte as char)))
+            }
+            _ => Err(self.syntax(format!("unexpected byte 0x{:02x}", byte))),
+        }
+    }
+
+    fn number(&mut self) -> Result<Token, LuaError> {
+        let hex = self.peek_byte(0) == Some(b'0') && matches!(self.peek_byte(1), Some(b'x' | b'X'));
+        let start = self.position;
+        if hex {
+            self.position += 2;
+        }
+
+        let digits_start = self.position;
+        while matches!(self.peek_byte(0), Some(byte) if byte.is_ascii_alphanumeric() || byte == b'.')
+        {
+            self.bump();
+        }
+
+        let digits = std::str::from_utf8(&self.source[digits_start..self.position])
+            .expect("digits are ASCII");
+        let text =
+            std::str::from_utf8(&self.source[start..self.position]).expect("digits are ASCII");
+
+        let is_float = if hex {
+            digits.contains(['.', 'p', 'P'])
+        } else {
+            digits.contains(['.', 'e', 'E'])
+        };
+        if is_float {
+            return Err(self.unsupported("floating-point number"));
+        }
+
+        let value = if hex {
+            // Lua hexadecimal integers wrap around on overflow
+            if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                None
+            } else {
+                Some(digits.bytes().fold(0u64, |value, digit| {
+                    let digit = (digit as char).to_digit(16).expect("checked above");
+                    value.wrapping_shl(4) | digit as u64
+                }))
+            }
+        } else {
+            digits.parse().ok()
+        };
+
+        value
+            .map(Token::Integer)
+            .ok_or_else(|| self.syntax(format!("malformed number \"{}\"", text)))
+    }
+
+    fn string(&mut self) -> Result<Token, LuaError> {
+        let quote = self.bump().expect("caller checked the quote");
+        let mut bytes = Vec::new();
+
+        loop {
+            match self.bump() {
+                Some(byte) if byte == quote => break,
+                None | Some(b'\n') => return Err(self.syntax("unfinished string")),
+                Some(b'\\') => self.escape(&mut bytes)?,
+                Some(byte) => bytes.push(byte),
+            }
+        }
+
+        String::from_utf8(bytes)
+            .map(Token::String)
+            .map_err(|_| self.syntax("string is not valid UTF-8"))
+    }
+
+    fn escape(&mut self, bytes: &mut Vec<u8>) -> Result<(), LuaError> {
+        let Some(byte) = self.bump() else {
+            return Err(self.syntax("unfinished string"));
+        };
+
+        match byte {
+            b'a' => bytes.push(0x07),
+            b'b' => bytes.push(0x08),
+            b'f' => bytes.push(0x0c),
+            b'n' | b'\n' => bytes.push(b'\n'),
+            b'r' => bytes.push(b'\r'),
+            b't' => bytes.push(b'\t'),
+            b'v' => bytes.push(0x0b),
+            b'\\' | b'"' | b'\'' => bytes.push(byte),
+            b'z' => {
+                while matches!(self.peek_byte(0), Some(byte) if byte.is_ascii_whitespace()) {
+                    self.bump();
+                }
+            }
+            b'x' => {
+                let mut value = 0;
+                for _ in 0..2 {
+                    let digit = self
+                        .bump()
+                        .and_then(|byte| (byte as char).to_digit(16))
+                        .ok_or_else(|| self.syntax("hexadecimal digit expected"))?;
+                    value = value * 16 + digit;
+                }
+                bytes.push(value as u8);
+            }
+            b'0'..=b'9' => {
+                let mut value = (byte - b'0') as u32;
+                for _ in 0..2 {
+                    match self.peek_byte(0) {
+                        Some(digit @ b'0'..=b'9') => {
+                            self.bump();
+                            value = value * 10 + (digit - b'0') as u32;
+                        }
+                        _ => break,
+                    }
+                }
+                let value = u8::try_from(value).map_err(|_| self.syntax("escape too large"))?;
+                bytes.push(value);
+            }
+            b'u' => return Err(self.unsupported("UTF-8 escape")),
+            _ => return Err(self.syntax("invalid escape sequence")),
+        }
+
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    token: Token,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            lexer: Lexer {
+                source: source.as_bytes(),
+                position: 0,
+                line: 1,
+            },
+            token: Token::End,
+            line: 1,
+        }
+    }
+
+    fn advance(&mut self) -> Result<Token, LuaError> {
+        let next = self.lexer.next()?;
+        self.line = self.lexer.line;
+        Ok(std::mem::replace(&mut self.token, next))
+    }
+
+    fn expect(&mut self, symbol: u8) -> Result<(), LuaError> {
+        if self.token == Token::Symbol(symbol) {
+            self.advance()?;
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", symbol as char)))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> LuaError {
+        let found = match &self.token {
+            Token::Name(name) => format!("'{}'", name),
+            Token::Integer(value) => value.to_string(),
+            Token::String(_) => "string".to_string(),
+            Token::Symbol(symbol) => format!("'{}'", *symbol as char),
+            Token::End => "end of input".to_string(),
+        };
+        LuaError::Syntax {
+            line: self.line,
+            message: format!("{} expected near {}", expected, found),
+        }
+    }
+
+    fn unsupported(&self, construct: impl Into<String>) -> LuaError {
+        LuaError::Unsupported {
+            line: self.line,
+            construct: construct.into(),
+        }
+    }
+
+    /// Parses `[return] <table> [;]`
+    fn chunk(mut self) -> Result<Value, LuaError> {
+        self.advance()?;
+
+        match &self.token {
+            Token::Name(name) if name == "return" => {
+                self.advance()?;
+            }
+            Token::Symbol(b'{') => {}
+            Token::Name(name) => return Err(self.unsupported(format!("statement '{}'", name))),
+            _ => return Err(self.unexpected("'return'")),
+        }
+
+        if self.token != Token::Symbol(b'{') {
+            return Err(self.unexpected("table"));
+        }
+        let value = self.value()?;
+
+        if self.token == Token::Symbol(b';') {
+            self.advance()?;
+        }
+        if self.token != Token::End {
+            return Err(self.unexpected("end of input"));
+        }
+
+        Ok(value.expect("tables are never nil"))
+    }
+
+    /// Parses a value, returning `None` for `nil`
+    fn value(&mut self) -> Result<Option<Value>, LuaError> {
+        let value = match &self.token {
+            Token::Integer(value) => Value::Integer(*value),
+            Token::String(value) => Value::String(value.clone()),
+            Token::Name(name) => match name.as_str() {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                "nil" => {
+                    self.advance()?;
+                    return Ok(None);
+                }
+                "function" => return Err(self.unsupported("function")),
+                _ => return Err(self.unsupported(format!("variable '{}'", name))),
+            },
+            Token::Symbol(b'{') => {
+                self.advance()?;
+                return Ok(Some(Value::Table(self.table_fields()?)));
+            }
+            _ => return Err(self.unexpected("value")),
+        };
+
+        self.advance()?;
+        Ok(Some(value))
+    }
+
+    /// Parses the fields of a table, after its opening brace
+    fn table_fields(&mut self) -> Result<Vec<(Key, Value)>, LuaError> {
+        let mut fields = Vec::new();
+        let mut next_index = 1;
+
+        while self.token != Token::Symbol(b'}') {
+            let key = match &self.token {
+                Token::Symbol(b'[') => {
+                    self.advance()?;
+                    let key = match self.advance()? {
+                        Token::Integer(index) => Key::Index(index),
+                        Token::String(name) => Key::Name(name),
+                        _ => return Err(self.unsupported("non-integer, non-string key")),
+                    };
+                    self.expect(b']')?;
+                    self.expect(b'=')?;
+                    Some(key)
+                }
+                Token::Name(name) if self.at_assignment() => {
+                    let key = Key::Name(name.clone());
+                    self.advance()?;
+                    self.expect(b'=')?;
+                    Some(key)
+                }
+                _ => None,
+            };
+
+            let key = key.unwrap_or_else(|| {
+                next_index += 1;
+                Key::Index(next_index - 1)
+            });
+
+            if let Some(value) = self.value()? {
+                fields.push((key, value));
+            }
+
+            match self.token {
+                Token::Symbol(b',' | b';') => {
+                    self.advance()?;
+                }
+                Token::Symbol(b'}') => {}
+                _ => return Err(self.unexpected("'}'")),
+            }
+        }
+
+        self.advance()?;
+        Ok(fields)
+    }
+
+    /// Tells whether the current name is followed by `=`, making it a key
+    fn at_assignment(&self) -> bool {
+        matches!(self.lexer.clone().next(), Ok(Token::Symbol(b'=')))
+    }
+}
+
+fn field_path(path: &str, key: &Key) -> String {
+    match (path, key) {
+        ("", Key::Name(name)) => name.clone(),
+        (_, Key::Name(name)) => format!("{}.{}", path, name),
+        (_, Key::Index(index)) => format!("{}[{}]", path, index),
+    }
+}
+
+fn unknown(path: String) -> LuaError {
+    LuaError::UnknownField { path }
+}
+
+fn table<'v>(value: &'v Value, path: &str) -> Result<&'v [(Key, Value)], LuaError> {
+    match value {
+        Value::Table(fields) => Ok(fields),
+        _ => Err(LuaError::InvalidValue {
+            path: path.to_string(),
+            expected: "a table",
+        }),
+    }
+}
+
+/// Iterates over the named fields of a table, with their paths
+fn named_fields<'v>(
+    value: &'v Value,
+    path: &str,
+) -> Result<impl Iterator<Item = Result<(&'v str, &'v Value, String), LuaError>>, LuaError> {
+    let path = path.to_string();
+    Ok(table(value, &path)?.iter().map(move |(key, value)| {
+        let field = field_path(&path, key);
+        match key {
+            Key::Name(name) => Ok((name.as_str(), value, field)),
+            Key::Index(_) => Err(unknown(field)),
+        }
+    }))
+}
+
+fn integer(value: &Value, path: &str) -> Result<u64, LuaError> {
+    match value {
+        Value::Integer(value) => Ok(*value),
+        _ => Err(LuaError::InvalidValue {
+            path: path.to_string(),
+            expected: "an integer",
+        }),
+    }
+}
+
+fn boolean(value: &Value, path: &str) -> Result<bool, LuaError> {
+    match value {
+        Value::Boolean(value) => Ok(*value),
+        _ => Err(LuaError::InvalidValue {
+            path: path.to_string(),
+            expected: "a boolean",
+        }),
+    }
+}
+
+/// Reads an optional string, where the empty string means absent
+fn string(value: &Value, path: &str) -> Result<Option<String>, LuaError> {
+    match value {
+        Value::String(value) if value.is_empty() => Ok(None),
+        Value::String(value) => Ok(Some(value.clone())),
+        _ => Err(LuaError::InvalidValue {
+            path: path.to_string(),
+            expected: "a string",
+        }),
+    }
+}
+
+fn machine(config: &mut MachineConfig, value: &Value) -> Result<(), LuaError> {
+    for field in named_fields(value, "")? {
+        let (name, value, path) = field?;
+        match name {
+            "processor" => processor(&mut config.processor, value, &path)?,
+            "ram" => {
+                for field in named_fields(value, &path)? {
+                    let (name, value, path) = field?;
+                    match name {
+                        "length" => config.ram.length = integer(value, &path)?,
+                        "image_filename" => config.ram.image_filename = string(value, &path)?,
+                        _ => return Err(unknown(path)),
+                    }
+                }
+            }
+            "dtb" => {
+                for field in named_fields(value, &path)? {
+                    let (name, value, path) = field?;
+                    let target = match name {
+                        "bootargs" => &mut config.dtb.bootargs,
+                        "init" => &mut config.dtb.init,
+                        "entrypoint" => &mut config.dtb.entrypoint,
+                        "image_filename" => &mut config.dtb.image_filename,
+                        _ => return Err(unknown(path)),
+                    };
+                    *target = string(value, &path)?;
+                }
+            }
+            "flash_drive" => config.flash_drive = memory_ranges(value, &path)?,
+            "tlb" => {
+                for field in named_fields(value, &path)? {
+                    let (name, value, path) = field?;
+                    match name {
+                        "image_filename" => config.tlb.image_filename = string(value, &path)?,
+                        _ => return Err(unknown(path)),
+                    }
+                }
+            }
+            "clint" => {
+                for field in named_fields(value, &path)? {
+                    let (name, value, path) = field?;
+                    match name {
+                        "mtimecmp" => config.clint.mtimecmp = integer(value, &path)?,
+                        _ => return Err(unknown(path)),
+                    }
+                }
+            }
+            "htif" => {
+                for field in named_fields(value, &path)? {
+                    let (name, value, path) = field?;
+                    match name {
+                        "fromhost" => config.htif.fromhost = integer(value, &path)?,
+                        "tohost" => config.htif.tohost = integer(value, &path)?,
+                        "console_getchar" => config.htif.console_getchar = boolean(value, &path)?,
+                        "yield_manual" => config.htif.yield_manual = boolean(value, &path)?,
+                        "yield_automatic" => config.htif.yield_automatic = boolean(value, &path)?,
+                        _ => return Err(unknown(path)),
+                    }
+                }
+            }
+            "rollup" => rollup(&mut config.rollup, value, &path)?,
+            "uarch" => uarch(&mut config.uarch, value, &path)?,
+            _ => return Err(unknown(path)),
+        }
+    }
+
+    Ok(())
+}
+
+fn processor(config: &mut ProcessorConfig, value: &Value, path: &str) -> Result<(), LuaError> {
+    for field in named_fields(value, path)? {
+        let (name, value, path) = field?;
+        let target = match name {
+            "x" => {
+                registers(&mut config.x, 1, value, &path)?;
+                continue;
+            }
+            "f" => {
+                registers(&mut config.f, 0, value, &path)?;
+                continue;
+            }
+            "pc" => &mut config.pc,
+            "fcsr" => &mut config.fcsr,
+            "mvendorid" => &mut config.mvendorid,
+            "marchid" => &mut config.marchid,
+            "mimpid" => &mut config.mimpid,
+            "mcycle" => &mut config.mcycle,
+            "icycleinstret" => &mut config.icycleinstret,
+            "mstatus" => &mut config.mstatus,
+            "mtvec" => &mut config.mtvec,
+            "mscratch" => &mut config.mscratch,
+            "mepc" => &mut config.mepc,
+            "mcause" => &mut config.mcause,
+            "mtval" => &mut config.mtval,
+            "misa" => &mut config.misa,
+            "mie" => &mut config.mie,
+            "mip" => &mut config.mip,
+            "medeleg" => &mut config.medeleg,
+            "mideleg" => &mut config.mideleg,
+            "mcounteren" => &mut config.mcounteren,
+            "menvcfg" => &mut config.menvcfg,
+            "stvec" => &mut config.stvec,
+            "sscratch" => &mut config.sscratch,
+            "sepc" => &mut config.sepc,
+            "scause" => &mut config.scause,
+            "stval" => &mut config.stval,
+            "satp" => &mut config.satp,
+            "scounteren" => &mut config.scounteren,
+            "senvcfg" => &mut config.senvcfg,
+            "ilrsc" => &mut config.ilrsc,
+            "iflags" => &mut config.iflags,
+            _ => return Err(unknown(path)),
+        };
+        *target = integer(value, &path)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a register file indexed from `first`
+fn registers(
+    registers: &mut [u64; 32],
+    first: u64,
+    value: &Value,
+    path: &str,
+) -> Result<(), LuaError> {
+    for (key, value) in table(value, path)? {
+        let path = field_path(path, key);
+        match key {
+            Key::Index(index) if (first..32).contains(index) => {
+                registers[*index as usize] = integer(value, &path)?;
+            }
+            _ => return Err(unknown(path)),
+        }
+    }
+
+    Ok(())
+}
+
+fn memory_range(range: &mut MemoryRangeConfig, value: &Value, path: &str) -> Result<(), LuaError> {
+    for field in named_fields(value, path)? {
+        let (name, value, path) = field?;
+        match name {
+            "start" => range.start = integer(value, &path)?,
+            "length" => range.length = integer(value, &path)?,
+            "shared" => range.shared = boolean(value, &path)?,
+            "image_filename" => range.image_filename = string(value, &path)?,
+            _ => return Err(unknown(path)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a sequence of memory ranges, which must be indexed from one
+fn memory_ranges(value: &Value, path: &str) -> Result<Vec<MemoryRangeConfig>, LuaError> {
+    let mut entries = BTreeMap::new();
+    for (key, value) in table(value, path)? {
+        match key {
+            Key::Index(index) => entries.insert(*index, value),
+            Key::Name(_) => return Err(unknown(field_path(path, key))),
+        };
+    }
+
+    let mut ranges = Vec::with_capacity(entries.len());
+    for (position, (index, value)) in entries.into_iter().enumerate() {
+        if index != position as u64 + 1 {
+            return Err(LuaError::InvalidValue {
+                path: path.to_string(),
+                expected: "a sequence",
+            });
+        }
+
+        let mut range = MemoryRangeConfig {
+            start: 0,
+            length: 0,
+            shared: false,
+            image_filename: None,
+        };
+        memory_range(&mut range, value, &format!("{}[{}]", path, index))?;
+        ranges.push(range);
+    }
+
+    Ok(ranges)
+}
+
+fn rollup(config: &mut RollupConfig, value: &Value, path: &str) -> Result<(), LuaError> {
+    for field in named_fields(value, path)? {
+        let (name, value, path) = field?;
+        match name {
+            "rx_buffer" => memory_range(&mut config.rx_buffer, value, &path)?,
+            "tx_buffer" => memory_range(&mut config.tx_buffer, value, &path)?,
+            _ => return Err(unknown(path)),
+        }
+    }
+
+    config.has_value = true;
+    Ok(())
+}
+
+fn uarch(config: &mut UarchConfig, value: &Value, path: &str) -> Result<(), LuaError> {
+    for field in named_fields(value, path)? {
+        let (name, value, path) = field?;
+        match name {
+            "processor" => uarch_processor(&mut config.processor, value, &path)?,
+            "ram" => {
+                for field in named_fields(value, &path)? {
+                    let (name, value, path) = field?;
+                    match name {
+                        "image_filename" => config.ram.image_filename = string(value, &path)?,
+                        _ => return Err(unknown(path)),
+                    }
+                }
+            }
+            _ => return Err(unknown(path)),
+        }
+    }
+
+    Ok(())
+}
+
+fn uarch_processor(
+    config: &mut UarchProcessorConfig,
+    value: &Value,
+    path: &str,
+) -> Result<(), LuaError> {
+    for field in named_fields(value, path)? {
+        let (name, value, path) = field?;
+        match name {
+            "x" => registers(&mut config.x, 1, value, &path)?,
+            "pc" => config.pc = integer(value, &path)?,
+            "cycle" => config.cycle = integer(value, &path)?,
+            "halt_flag" => config.halt_flag = boolean(value, &path)?,
+            _ => return Err(unknown(path)),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::machine_config;
+
+    /// Layout written by `cartesi-machine --store-config` 0.15: `%q` strings,
+    /// hexadecimal integers, `-- default` comments and 1-based `x` registers
+    /// next to 0-based `f` registers
+    const STORED_CONFIG: &str = r#"return {
+  processor = {
+    x = {
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x7ff00000,
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+    },
+    f = {
+      [0] = 0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x0, -- default
+      0x3ff0000000000000,
+    },
+    pc = 0x70000000, -- default
+    fcsr = 0x0, -- default
+    mvendorid = 0x6361727465736920, -- default
+    marchid = 0xf, -- default
+    mimpid = 0x1, -- default
+    mcycle = 0x2a,
+    icycleinstret = 0x0, -- default
+    mstatus = 0xa00000000, -- default
+    mtvec = 0x0, -- default
+    mscratch = 0x0, -- default
+    mepc = 0x0, -- default
+    mcause = 0x0, -- default
+    mtval = 0x0, -- default
+    misa = 0x800000000014112d, -- default
+    mie = 0x0, -- default
+    mip = 0x0, -- default
+    medeleg = 0x0, -- default
+    mideleg = 0x0, -- default
+    mcounteren = 0x0, -- default
+    menvcfg = 0x0, -- default
+    stvec = 0x0, -- default
+    sscratch = 0x0, -- default
+    sepc = 0x0, -- default
+    scause = 0x0, -- default
+    stval = 0x0, -- default
+    satp = 0x0, -- default
+    scounteren = 0x0, -- default
+    senvcfg = 0x0, -- default
+    ilrsc = 0xffffffffffffffff, -- default
+    iflags = 0x18, -- default
+  },
+  ram = {
+    length = 0x4000000,
+    image_filename = "/opt/cartesi/share/images/linux.bin",
+  },
+  dtb = {
+    image_filename = "", -- default
+    bootargs = "console=hvc0 rootfstype=ext2 root=/dev/pmem0 rw quiet swiotlb=noforce init=/opt/cartesi/bin/init",
+    init = "echo \"Hello\9world\"\
+ls /mnt",
+    entrypoint = "",
+  },
+  tlb = {
+    image_filename = "", -- default
+  },
+  htif = {
+    tohost = 0x0, -- default
+    fromhost = 0x0, -- default
+    console_getchar = false, -- default
+    yield_automatic = true,
+    yield_manual = true,
+  },
+  clint = {
+    mtimecmp = 0x0, -- default
+  },
+  flash_drive = {
+    {
+      start = 0x80000000000000,
+      length = 0x5000000,
+      image_filename = "/opt/cartesi/share/images/rootfs.ext2",
+      shared = false, -- default
+    },
+    {
+      start = 0x90000000000000,
+      length = 0x1000,
+      shared = true,
+    },
+  },
+  rollup = {
+    rx_buffer = {
+      start = 0x60000000,
+      length = 0x200000,
+      shared = false, -- default
+    },
+    tx_buffer = {
+      start = 0x60200000,
+      length = 0x200000,
+      shared = false, -- default
+    },
+  },
+  uarch = {
+    ram = {
+      image_filename = "", -- default
+    },
+    processor = {
+      x = {
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+        0x0, -- default
+      },
+      pc = 0x600000, -- default
+      cycle = 0x0, -- default
+      halt_flag = false, -- default
+    },
+  },
+}
+"#;
+
+    fn parse(source: &str) -> Result<MachineConfig, LuaError> {
+        let mut config = machine_config();
+        config.update_from_lua(source)?;
+        Ok(config)
+    }
+
+    fn syntax(line: usize, message: &str) -> LuaError {
+        LuaError::Syntax {
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    fn unsupported(line: usize, construct: &str) -> LuaError {
+        LuaError::Unsupported {
+            line,
+            construct: construct.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_stored_config() {
+        let config = parse(STORED_CONFIG).unwrap();
+
+        assert_eq!(config.processor.x[0], 0);
+        assert_eq!(config.processor.x[10], 0x7ff00000);
+        assert_eq!(config.processor.f[0], 0);
+        assert_eq!(config.processor.f[31], 0x3ff0000000000000);
+        assert_eq!(config.processor.pc, 0x70000000);
+        assert_eq!(config.processor.mcycle, 0x2a);
+        assert_eq!(config.processor.mvendorid, 0x6361727465736920);
+        assert_eq!(config.processor.ilrsc, u64::MAX);
+        assert_eq!(config.processor.iflags, 0x18);
+        assert_eq!(config.ram.length, 0x4000000);
+        assert_eq!(
+            config.ram.image_filename.as_deref(),
+            Some("/opt/cartesi/share/images/linux.bin")
+        );
+        assert_eq!(config.dtb.image_filename, None);
+        assert_eq!(
+            config.dtb.init.as_deref(),
+            Some("echo \"Hello\tworld\"\nls /mnt")
+        );
+        assert_eq!(config.dtb.entrypoint, None);
+        assert!(config.htif.yield_manual && config.htif.yield_automatic);
+        assert!(!config.htif.console_getchar);
+        assert_eq!(config.flash_drive.len(), 2);
+        assert_eq!(config.flash_drive[0].length, 0x5000000);
+        assert!(config.flash_drive[1].shared);
+        assert_eq!(config.flash_drive[1].image_filename, None);
+        assert!(config.rollup.has_value);
+        assert_eq!(config.rollup.tx_buffer.start, 0x60200000);
+        assert_eq!(config.uarch.processor.pc, 0x600000);
+        assert!(!config.uarch.processor.halt_flag);
+    }
+
+    #[test]
+    fn hex_literals_wrap_like_lua() {
+        let config = parse(
+            "return { processor = { \
+                pc = 0xffffffffffffffff, \
+                mcycle = 0x10000000000000002, \
+                mtval = 0X8000000000000000, \
+                mepc = 18446744073709551615 } }",
+        )
+        .unwrap();
+
+        assert_eq!(config.processor.pc, u64::MAX);
+        assert_eq!(config.processor.mcycle, 2);
+        assert_eq!(config.processor.mtval, 1 << 63);
+        assert_eq!(config.processor.mepc, u64::MAX);
+
+        // Decimal integers past 64 bits become floats in Lua
+        assert_eq!(
+            parse("return { ram = { length = 18446744073709551616 } }").unwrap_err(),
+            syntax(1, "malformed number \"18446744073709551616\"")
+        );
+        assert_eq!(
+            parse("return { ram = { length = 0x } }").unwrap_err(),
+            syntax(1, "malformed number \"0x\"")
+        );
+    }
+
+    #[test]
+    fn skips_comments_and_long_brackets() {
+        let config = parse(
+            "-- header\n\
+             return { --[[ a long\n comment ]] ram = {\n\
+               length = 0x1000, --[==[ holds ]] and ]=] \n]==] image_filename = 'a' -- trailing\n\
+             } } -- end",
+        )
+        .unwrap();
+
+        assert_eq!(config.ram.length, 0x1000);
+        assert_eq!(config.ram.image_filename.as_deref(), Some("a"));
+
+        assert_eq!(
+            parse("return {\n--[=[ never\n closed ]]\n}").unwrap_err(),
+            syntax(2, "unfinished long comment")
+        );
+        assert_eq!(
+            parse("return {\n dtb = { init = [[echo]] } }").unwrap_err(),
+            unsupported(2, "long string")
+        );
+        assert_eq!(
+            parse("return {\n\n dtb = { init = [==[echo]==] } }").unwrap_err(),
+            unsupported(3, "long string")
+        );
+    }
+
+    #[test]
+    fn decodes_string_escapes() {
+        let config = parse(
+            r#"return { dtb = {
+                bootargs = "a\tb\\c\"d\'e\x41\65\0661\z
+                            f\
+g",
+                init = 'it\'s\r\a\b\f\v',
+            } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.dtb.bootargs.as_deref(), Some("a\tb\\c\"d'eAAB1f\ng"));
+        assert_eq!(config.dtb.init.as_deref(), Some("it's\r\x07\x08\x0c\x0b"));
+
+        assert_eq!(
+            parse(r#"return { dtb = { init = "\q" } }"#).unwrap_err(),
+            syntax(1, "invalid escape sequence")
+        );
+        assert_eq!(
+            parse(r#"return { dtb = { init = "\256" } }"#).unwrap_err(),
+            syntax(1, "escape too large")
+        );
+        assert_eq!(
+            parse(r#"return { dtb = { init = "\xg0" } }"#).unwrap_err(),
+            syntax(1, "hexadecimal digit expected")
+        );
+        assert_eq!(
+            parse("return { dtb = {\n init = \"open\n\" } }").unwrap_err(),
+            syntax(3, "unfinished string")
+        );
+        assert_eq!(
+            parse(r#"return { dtb = { init = "\u{48}" } }"#).unwrap_err(),
+            unsupported(1, "UTF-8 escape")
+        );
+    }
+
+    #[test]
+    fn indexes_f_from_zero_and_x_from_one() {
+        let config = parse(
+            "return { processor = { \
+                x = { 1, 2, [31] = 31 }, \
+                f = { [0] = 100, 101, 102, [31] = 131 } } }",
+        )
+        .unwrap();
+
+        assert_eq!(config.processor.x[0], 0);
+        assert_eq!(config.processor.x[1..3], [1, 2]);
+        assert_eq!(config.processor.x[31], 31);
+        assert_eq!(config.processor.f[..3], [100, 101, 102]);
+        assert_eq!(config.processor.f[31], 131);
+
+        assert_eq!(
+            parse("return { processor = { x = { [0] = 1 } } }").unwrap_err(),
+            LuaError::UnknownField {
+                path: "processor.x[0]".to_string()
+            }
+        );
+        assert_eq!(
+            parse("return { processor = { f = { [32] = 1 } } }").unwrap_err(),
+            LuaError::UnknownField {
+                path: "processor.f[32]".to_string()
+            }
+        );
+        assert_eq!(
+            parse("return { uarch = { processor = { x = { [0] = 1 } } } }").unwrap_err(),
+            LuaError::UnknownField {
+                path: "uarch.processor.x[0]".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_wrong_types() {
+        for (source, path) in [
+            ("return { cpu = {} }", "cpu"),
+            ("return { ram = { size = 1 } }", "ram.size"),
+            (
+                "return { flash_drive = { { start = 0, label = 'root' } } }",
+                "flash_drive[1].label",
+            ),
+            ("return { flash_drive = { root = {} } }", "flash_drive.root"),
+            ("return { 1 }", "[1]"),
+            (
+                "return { rollup = { rx_buffer = { mount = true } } }",
+                "rollup.rx_buffer.mount",
+            ),
+        ] {
+            assert_eq!(
+                parse(source).unwrap_err(),
+                LuaError::UnknownField {
+                    path: path.to_string()
+                },
+                "{}",
+                source
+            );
+        }
+
+        assert_eq!(
+            parse("return { ram = { length = '1' } }").unwrap_err(),
+            LuaError::InvalidValue {
+                path: "ram.length".to_string(),
+                expected: "an integer"
+            }
+        );
+        assert_eq!(
+            parse("return { htif = { yield_manual = 1 } }").unwrap_err(),
+            LuaError::InvalidValue {
+                path: "htif.yield_manual".to_string(),
+                expected: "a boolean"
+            }
+        );
+        assert_eq!(
+            parse("return { flash_drive = { [2] = {} } }").unwrap_err(),
+            LuaError::InvalidValue {
+                path: "flash_drive".to_string(),
+                expected: "a sequence"
+            }
+        );
+    }
+
+    #[test]
+    fn reports_unsupported_constructs_with_their_line() {
+        for (source, error) in [
+            ("local config = {}", unsupported(1, "statement 'local'")),
+            (
+                "return {\n ram = {\n length = -1 } }",
+                unsupported(3, "unary minus"),
+            ),
+            (
+                "return {\n ram = { length = 1.5 } }",
+                unsupported(2, "floating-point number"),
+            ),
+            (
+                "return {\n\n ram = { length = 0x1p4 } }",
+                unsupported(3, "floating-point number"),
+            ),
+            (
+                "return {\n ram = { length = 1 << 20 } }",
+                unsupported(2, "operator '<'"),
+            ),
+            (
+                "return {\n ram = { length = size } }",
+                unsupported(2, "variable 'size'"),
+            ),
+            (
+                "return {\n\n\n f = function() end }",
+                unsupported(4, "function"),
+            ),
+            (
+                "return { [true] = 1 }",
+                unsupported(1, "non-integer, non-string key"),
+            ),
+        ] {
+            assert_eq!(parse(source).unwrap_err(), error, "{}", source);
+        }
+
+        assert_eq!(
+            parse("return {\n ram = { length = 1 }\n clint = {} }").unwrap_err(),
+            syntax(3, "'}' expected near 'clint'")
+        );
+        assert_eq!(
+            parse("return { ram = {} } extra").unwrap_err(),
+            syntax(1, "end of input expected near 'extra'")
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Validation of machine configurations before they reach the emulator.
+
+use super::{MachineConfig, MemoryRangeConfig};
+use crate::errors::{ConfigError, ConfigIssue};
+use crate::merkle;
+
+/// Start of the RAM in the machine address space
+const RAM_START: u64 = 0x80000000;
+
+/// Ranges the emulator 0.15 always maps, which configured ranges must avoid
+const FIXED_RANGES: &[(&str, u64, u64)] = &[
+    ("shadow state", 0x0, 0x1000),
+    ("shadow PMAs", 0x10000, 0x1000),
+    ("shadow TLB", 0x20000, 0x6000),
+    ("shadow uarch state", 0x400000, 0x1000),
+    ("uarch RAM", 0x600000, 0x200000),
+    ("CLINT", 0x2000000, 0xc0000),
+    ("HTIF", 0x40008000, 0x1000),
+    ("DTB", 0x7ff00000, 0x100000),
+];
+
+/// Maximum number of flash drives the emulator supports
+const FLASH_DRIVE_MAX_COUNT: usize = cartesi_machine_sys::CM_FLASH_DRIVE_CONFIGS_MAX_SIZE as usize;
+
+impl MachineConfig {
+    /// Checks the configuration, reporting every problem found
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut issues = Vec::new();
+        let mut ranges = Vec::new();
+
+        if self.ram.length == 0 {
+            issues.push(ConfigIssue::new("ram.length", "must not be zero"));
+        } else if !is_page_aligned(self.ram.length) {
+            issues.push(ConfigIssue::new(
+                "ram.length",
+                "must be a multiple of the page size",
+            ));
+        }
+
+        check_image(
+            &mut issues,
+            "ram.image_filename",
+            self.ram.image_filename.as_deref(),
+            Some(self.ram.length),
+        );
+        ranges.push(("ram".to_string(), RAM_START, self.ram.length));
+
+        if self.flash_drive.len() > FLASH_DRIVE_MAX_COUNT {
+            issues.push(ConfigIssue::new(
+                "flash_drive",
+                format!(
+                    "has {} drives, at most {} are supported",
+                    self.flash_drive.len(),
+                    FLASH_DRIVE_MAX_COUNT
+                ),
+            ));
+        }
+
+        for (i, drive) in self.flash_drive.iter().enumerate() {
+            let path = format!("flash_drive[{}]", i);
+            let length = check_range(&mut issues, &path, drive, true);
+            ranges.push((path, drive.start, length));
+        }
+
+        if self.rollup.has_value {
+            for (name, buffer) in [
+                ("rx_buffer", &self.rollup.rx_buffer),
+                ("tx_buffer", &self.rollup.tx_buffer),
+            ] {
+                let path = format!("rollup.{}", name);
+                let length = check_range(&mut issues, &path, buffer, false);
+                ranges.push((path, buffer.start, length));
+            }
+        }
+
+        let configured = ranges.len();
+        ranges.extend(
+            FIXED_RANGES
+                .iter()
+                .map(|(name, start, length)| (name.to_string(), *start, *length)),
+        );
+
+        for (i, (path, start, length)) in ranges[..configured].iter().enumerate() {
+            for (other_path, other_start, other_length) in &ranges[i + 1..] {
+                if overlaps(*start, *length, *other_start, *other_length) {
+                    issues.push(ConfigIssue::new(
+                        path.clone(),
+                        format!("overlaps {}", other_path),
+                    ));
+                }
+            }
+        }
+
+        check_image(
+            &mut issues,
+            "dtb.image_filename",
+            self.dtb.image_filename.as_deref(),
+            None,
+        );
+        check_image(
+            &mut issues,
+            "tlb.image_filename",
+            self.tlb.image_filename.as_deref(),
+            None,
+        );
+        check_image(
+            &mut issues,
+            "uarch.ram.image_filename",
+            self.uarch.ram.image_filename.as_deref(),
+            None,
+        );
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { issues })
+        }
+    }
+}
+
+fn is_page_aligned(value: u64) -> bool {
+    value & ((1 << merkle::LOG2_PAGE_SIZE) - 1) == 0
+}
+
+fn overlaps(start: u64, length: u64, other_start: u64, other_length: u64) -> bool {
+    length != 0
+        && other_length != 0
+        && start < other_start.saturating_add(other_length)
+        && other_start < start.saturating_add(length)
+}
+
+/// Checks a memory range, returning its length
+///
+/// With `length_from_image`, a zero length stands for the length of the image,
+/// as the emulator does for flash drives.
+fn check_range(
+    issues: &mut Vec<ConfigIssue>,
+    path: &str,
+    range: &MemoryRangeConfig,
+    length_from_image: bool,
+) -> u64 {
+    let image_length = range
+        .image_filename
+        .as_deref()
+        .and_then(|image_filename| std::fs::metadata(image_filename).ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len());
+    let (length, max_image_length) = match image_length {
+        Some(image_length) if length_from_image && range.length == 0 => (image_length, None),
+        _ => (range.length, Some(range.length)),
+    };
+
+    if length == 0 {
+        let message = if length_from_image && range.image_filename.is_none() {
+            "must not be zero without an image"
+        } else {
+            "must not be zero"
+        };
+        issues.push(ConfigIssue::new(format!("{}.length", path), message));
+    } else if !is_page_aligned(length) {
+        let message = match max_image_length {
+            Some(_) => "must be a multiple of the page size".to_string(),
+            None => format!(
+                "taken from the image, {} is not a multiple of the page size",
+                length
+            ),
+        };
+        issues.push(ConfigIssue::new(format!("{}.length", path), message));
+    }
+
+    if !is_page_aligned(range.start) {
+        issues.push(ConfigIssue::new(
+            format!("{}.start", path),
+            "must be a multiple of the page size",
+        ));
+    }
+
+    if range.start.checked_add(length).is_none() {
+        issues.push(ConfigIssue::new(
+            path.to_string(),
+            "extends past the end of the address space",
+        ));
+    }
+
+    check_image(
+        issues,
+        &format!("{}.image_filename", path),
+        range.image_filename.as_deref(),
+        max_image_length,
+    );
+
+    length
+}
+
+fn check_image(
+    issues: &mut Vec<ConfigIssue>,
+    path: &str,
+    image_filename: Option<&str>,
+    max_length: Option<u64>,
+) {
+    let Some(image_filename) = image_filename else {
+        return;
+    };
+
+    match std::fs::metadata(image_filename) {
+        Ok(metadata) if !metadata.is_file() => {
+            issues.push(ConfigIssue::new(
+                path.to_string(),
+                format!("{} is not a file", image_filename),
+            ));
+        }
+        Ok(metadata) => {
+            if let Some(max_length) = max_length.filter(|&max| metadata.len() > max) {
+                issues.push(ConfigIssue::new(
+                    path.to_string(),
+                    format!(
+                        "{} has {} bytes, larger than the range length {}",
+                        image_filename,
+                        metadata.len(),
+                        max_length
+                    ),
+                ));
+            }
+        }
+        Err(error) => {
+            issues.push(ConfigIssue::new(
+                path.to_string(),
+                format!("cannot access {}: {}", image_filename, error),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::machine_config;
+
+    fn drive(start: u64, length: u64, image_filename: Option<&str>) -> MemoryRangeConfig {
+        MemoryRangeConfig {
+            start,
+            length,
+            shared: false,
+            image_filename: image_filename.map(str::to_string),
+        }
+    }
+
+    fn issues(config: &MachineConfig) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(error) => error.issues.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Writes an image of `length` bytes to a file unique to `name`
+    fn image(name: &str, length: usize) -> String {
+        let path =
+            std::env::temp_dir().join(format!("validation-{}-{}.img", name, std::process::id()));
+        std::fs::write(&path, vec![0; length]).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn accepts_drives_clear_of_other_ranges() {
+        let mut config = machine_config();
+        config.flash_drive = vec![
+            drive(0x80000000000000, 0x1000, None),
+            drive(0x90000000000000, 0x4000, None),
+        ];
+
+        assert_eq!(issues(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn rejects_drives_over_fixed_ranges() {
+        let mut config = machine_config();
+        config.flash_drive = vec![
+            drive(0x7ff00000, 0x1000, None),
+            drive(0x2000000, 0x1000, None),
+            drive(0x0, 0x1000, None),
+            drive(0x5ff000, 0x2000, None),
+        ];
+
+        assert_eq!(
+            issues(&config),
+            [
+                "flash_drive[0]: overlaps DTB",
+                "flash_drive[1]: overlaps CLINT",
+                "flash_drive[2]: overlaps shadow state",
+                "flash_drive[3]: overlaps uarch RAM",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_overlapping_configured_ranges() {
+        let mut config = machine_config();
+        config.flash_drive = vec![
+            drive(0x80000000000000, 0x2000, None),
+            drive(0x80000000001000, 0x1000, None),
+            drive(0x80000000, 0x1000, None),
+        ];
+
+        assert_eq!(
+            issues(&config),
+            [
+                "ram: overlaps flash_drive[2]",
+                "flash_drive[0]: overlaps flash_drive[1]",
+            ]
+        );
+    }
+
+    #[test]
+    fn sizes_zero_length_drives_from_their_image() {
+        let fitting = image("fitting", 0x2000);
+        let unaligned = image("unaligned", 0x1800);
+
+        let mut config = machine_config();
+        config.flash_drive = vec![
+            drive(0x80000000000000, 0, Some(&fitting)),
+            drive(0x80000000001000, 0x1000, None),
+            drive(0x90000000000000, 0, Some(&unaligned)),
+            drive(0xa0000000000000, 0, None),
+        ];
+        let found = issues(&config);
+
+        std::fs::remove_file(&fitting).unwrap();
+        std::fs::remove_file(&unaligned).unwrap();
+
+        assert_eq!(
+            found,
+            [
+                "flash_drive[2].length: taken from the image, 6144 is not a multiple of the page size",
+                "flash_drive[3].length: must not be zero without an image",
+                "flash_drive[0]: overlaps flash_drive[1]",
+            ]
+        );
+    }
+
+    #[test]
+    fn rollup_buffers_need_explicit_lengths() {
+        let buffer = image("buffer", 0x1000);
+
+        let mut config = machine_config();
+        config.rollup.has_value = true;
+        config.rollup.rx_buffer = drive(0x60000000, 0, Some(&buffer));
+        config.rollup.tx_buffer = drive(0x60200000, 0x1000, Some(&buffer));
+        let found = issues(&config);
+
+        std::fs::remove_file(&buffer).unwrap();
+
+        assert_eq!(
+            found[..2],
+            [
+                "rollup.rx_buffer.length: must not be zero",
+                format!(
+                    "rollup.rx_buffer.image_filename: {} has 4096 bytes, larger than the range length 0",
+                    buffer
+                )
+                .as_str(),
+            ]
+        );
+        assert_eq!(found.len(), 2);
+    }
+}
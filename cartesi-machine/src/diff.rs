@@ -0,0 +1,305 @@
+//! Structural comparison of access logs.
+//!
+//! Logs of the same step or reset perform the same accesses until the states
+//! they were taken from diverge. Accesses are aligned by access type and
+//! address: when the next accesses of the two logs touch different state, the
+//! one that reappears soonest in the other log is taken as the resumption
+//! point, and the accesses before it are reported as missing from the other
+//! log. The first mismatch is reported together with the brackets open at
+//! that point.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::log::{AccessData, AccessLogData, AccessType, BracketType};
+use crate::pretty::value;
+
+/// Aspect in which two aligned accesses differ
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffKind {
+    /// Only one of the logs has the access
+    Missing,
+    /// One access reads and the other writes
+    AccessType,
+    /// The accesses touch different addresses
+    Address,
+    /// The accesses have different sizes
+    Log2Size,
+    /// The accesses read different values
+    ReadValue,
+    /// The accesses write different values
+    WrittenValue,
+    /// The accesses have different sibling hashes
+    SiblingHashes,
+}
+
+/// First divergence between two access logs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessLogDiff {
+    /// Index of the first differing access in the left log
+    pub left_index: usize,
+    /// Index of the first differing access in the right log
+    pub right_index: usize,
+    /// How the accesses differ
+    pub kind: DiffKind,
+    /// Access in the left log, if any
+    pub left: Option<AccessData>,
+    /// Access in the right log, if any
+    pub right: Option<AccessData>,
+    /// Texts of the brackets open before the access in the left log, outermost first
+    pub left_context: Vec<String>,
+    /// Texts of the brackets open before the access in the right log, outermost first
+    pub right_context: Vec<String>,
+}
+
+impl AccessLogData {
+    /// Finds the first access in which `self` and `other` differ
+    pub fn diff(&self, other: &AccessLogData) -> Option<AccessLogDiff> {
+        let (mut i, mut j) = (0, 0);
+
+        let (left, right, kind) = loop {
+            match (self.accesses.get(i), other.accesses.get(j)) {
+                (None, None) => return None,
+                (Some(left), None) => break (Some(left), None, DiffKind::Missing),
+                (None, Some(right)) => break (None, Some(right), DiffKind::Missing),
+                (Some(left), Some(right)) if key(left) == key(right) => {
+                    if let Some(kind) = compare(left, right) {
+                        break (Some(left), Some(right), kind);
+                    }
+                    i += 1;
+                    j += 1;
+                }
+                (Some(left), Some(right)) => {
+                    let in_right = find(&other.accesses[j + 1..], left);
+                    let in_left = find(&self.accesses[i + 1..], right);
+
+                    break match (in_left, in_right) {
+                        (Some(l), Some(r)) if l < r => (Some(left), None, DiffKind::Missing),
+                        (_, Some(_)) => (None, Some(right), DiffKind::Missing),
+                        (Some(_), None) => (Some(left), None, DiffKind::Missing),
+                        (None, None) => (Some(left), Some(right), compare(left, right)?),
+                    };
+                }
+            }
+        };
+
+        Some(AccessLogDiff {
+            left_index: i,
+            right_index: j,
+            kind,
+            left: left.cloned(),
+            right: right.cloned(),
+            left_context: open_brackets(self, i),
+            right_context: open_brackets(other, j),
+        })
+    }
+}
+
+/// State an access touches, used to align the logs
+fn key(access: &AccessData) -> (AccessType, u64) {
+    (access.access_type, access.address)
+}
+
+/// Distance to the first access in `accesses` touching the same state as `access`
+fn find(accesses: &[AccessData], access: &AccessData) -> Option<usize> {
+    accesses.iter().position(|other| key(other) == key(access))
+}
+
+fn compare(left: &AccessData, right: &AccessData) -> Option<DiffKind> {
+    if left.access_type != right.access_type {
+        Some(DiffKind::AccessType)
+    } else if left.address != right.address {
+        Some(DiffKind::Address)
+    } else if left.log2_size != right.log2_size {
+        Some(DiffKind::Log2Size)
+    } else if left.read_hash != right.read_hash {
+        Some(DiffKind::ReadValue)
+    } else if left.written_hash != right.written_hash {
+        Some(DiffKind::WrittenValue)
+    } else if left.sibling_hashes.is_some()
+        && right.sibling_hashes.is_some()
+        && left.sibling_hashes != right.sibling_hashes
+    {
+        Some(DiffKind::SiblingHashes)
+    } else {
+        None
+    }
+}
+
+/// Texts of the brackets still open before the access at `index`
+fn open_brackets(log: &AccessLogData, index: usize) -> Vec<String> {
+    let mut stack = Vec::new();
+
+    for bracket in log
+        .brackets
+        .iter()
+        .filter(|bracket| bracket.r#where <= index as u64)
+    {
+        match bracket.kind {
+            BracketType::Begin => stack.push(bracket.text.clone()),
+            BracketType::End => {
+                stack.pop();
+            }
+        }
+    }
+
+    stack
+}
+
+impl Display for AccessLogDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.left_index == self.right_index {
+            writeln!(
+                f,
+                "first difference at access {} ({:?})",
+                self.left_index, self.kind
+            )?;
+        } else {
+            writeln!(
+                f,
+                "first difference at access {} of left, {} of right ({:?})",
+                self.left_index, self.right_index, self.kind
+            )?;
+        }
+        write_side(f, "left", self.left.as_ref(), &self.left_context)?;
+        write_side(f, "right", self.right.as_ref(), &self.right_context)
+    }
+}
+
+fn write_side(
+    f: &mut Formatter<'_>,
+    side: &str,
+    access: Option<&AccessData>,
+    context: &[String],
+) -> fmt::Result {
+    write!(f, "  {}: ", side)?;
+
+    match access {
+        Some(access) => {
+            let kind = match access.access_type {
+                AccessType::Read => "read",
+                AccessType::Write => "write",
+            };
+
+            write!(
+                f,
+                "{} 0x{:x} (2^{} bytes): {}",
+                kind,
+                access.address,
+                access.log2_size,
+                value(access.read_data.as_deref(), Some(&access.read_hash))
+            )?;
+
+            if access.access_type == AccessType::Write {
+                write!(
+                    f,
+                    " -> {}",
+                    value(access.written_data.as_deref(), access.written_hash.as_ref())
+                )?;
+            }
+        }
+        None => write!(f, "no access")?,
+    }
+
+    if context.is_empty() {
+        writeln!(f)
+    } else {
+        writeln!(f, " in {}", context.join(" > "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::BracketNoteData;
+    use crate::replay::UarchLayout;
+    use crate::testing::addi_step;
+
+    fn log() -> AccessLogData {
+        addi_step(&UarchLayout::default()).1
+    }
+
+    #[test]
+    fn equal_logs_have_no_diff() {
+        assert_eq!(log().diff(&log()), None);
+    }
+
+    #[test]
+    fn reports_different_values() {
+        let mut other = log();
+        let access = &mut other.accesses[5];
+        access.written_data = Some(vec![0; 8]);
+        access.written_hash = Some(crate::merkle::keccak(&[0; 8]));
+
+        let diff = log().diff(&other).unwrap();
+        assert_eq!((diff.left_index, diff.right_index), (5, 5));
+        assert_eq!(diff.kind, DiffKind::WrittenValue);
+    }
+
+    #[test]
+    fn aligns_past_extra_access() {
+        let mut other = log();
+        let extra = other.accesses[4].clone();
+        other.accesses.insert(2, extra.clone());
+
+        let diff = log().diff(&other).unwrap();
+        assert_eq!((diff.left_index, diff.right_index), (2, 2));
+        assert_eq!(diff.kind, DiffKind::Missing);
+        assert_eq!((diff.left, diff.right), (None, Some(extra)));
+
+        let diff = other.diff(&log()).unwrap();
+        assert_eq!(diff.kind, DiffKind::Missing);
+        assert!(diff.left.is_some() && diff.right.is_none());
+    }
+
+    #[test]
+    fn reports_truncated_log() {
+        let mut other = log();
+        other.accesses.truncate(6);
+
+        let diff = log().diff(&other).unwrap();
+        assert_eq!((diff.left_index, diff.right_index), (6, 6));
+        assert_eq!(diff.kind, DiffKind::Missing);
+        assert_eq!(diff.right, None);
+    }
+
+    #[test]
+    fn reports_unmatched_address() {
+        let mut other = log();
+        other.accesses[3].address = 0x1000;
+
+        let diff = log().diff(&other).unwrap();
+        assert_eq!((diff.left_index, diff.right_index), (3, 3));
+        assert_eq!(diff.kind, DiffKind::Address);
+    }
+
+    #[test]
+    fn reports_open_brackets() {
+        let mut left = log();
+        left.brackets = vec![
+            BracketNoteData {
+                kind: BracketType::Begin,
+                r#where: 0,
+                text: "step".to_string(),
+            },
+            BracketNoteData {
+                kind: BracketType::Begin,
+                r#where: 4,
+                text: "addi".to_string(),
+            },
+            BracketNoteData {
+                kind: BracketType::End,
+                r#where: 6,
+                text: "addi".to_string(),
+            },
+        ];
+        let mut right = left.clone();
+        right.accesses[5].read_hash = crate::merkle::keccak(b"other");
+
+        let diff = left.diff(&right).unwrap();
+        assert_eq!(diff.left_context, ["step", "addi"]);
+        assert_eq!(
+            diff.to_string().lines().next(),
+            Some("first difference at access 5 (ReadValue)")
+        );
+    }
+}
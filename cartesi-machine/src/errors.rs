@@ -2,6 +2,9 @@
 
 use std::{ffi::c_char, fmt::Display};
 
+use crate::hash::Hash;
+use crate::log::AccessType;
+
 fn c_char_to_string(c_char: *const c_char) -> &'static str {
     if c_char.is_null() {
         ""
@@ -55,25 +58,59 @@ pub enum ErrorCode {
 /// Error returned from machine emulator C API
 pub struct MachineError {
     code: ErrorCode,
-    message: *const c_char
+    message: Message,
+}
+
+/// Message of a [`MachineError`], allocated by the emulator or by Rust
+enum Message {
+    Foreign(*const c_char),
+    Owned(String),
 }
 
-impl Drop for MachineError {
+impl Drop for Message {
     fn drop(&mut self) {
-        unsafe { cartesi_machine_sys::cm_delete_cstring(self.message) };
+        if let Message::Foreign(message) = self {
+            unsafe { cartesi_machine_sys::cm_delete_cstring(*message) };
+        }
+    }
+}
+
+impl MachineError {
+    /// Error code reported by the emulator
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    /// Error message reported by the emulator
+    pub fn message(&self) -> &str {
+        match &self.message {
+            Message::Foreign(message) => c_char_to_string(*message),
+            Message::Owned(message) => message,
+        }
+    }
+}
+
+/// Rejects a configuration before it reaches the emulator, as the emulator
+/// rejects invalid arguments
+impl From<ConfigIssue> for MachineError {
+    fn from(issue: ConfigIssue) -> Self {
+        MachineError {
+            code: ErrorCode::InvalidArgument,
+            message: Message::Owned(issue.to_string()),
+        }
     }
 }
 
 impl Display for MachineError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let message = c_char_to_string(self.message);
+        let message = self.message();
         write!(f, "Error {:?}: {}", self.code as u8, message)
     }
 }
 
 impl std::fmt::Debug for MachineError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let message = c_char_to_string(self.message);
+        let message = self.message();
         f.debug_struct("MachineError")
             .field("code", &self.code)
             .field("message", &message)
@@ -98,6 +135,11 @@ impl ErrorCollector {
         self.ptr
     }
 
+    /// Gets the location the C API writes the error message to
+    pub fn as_out_ptr(&mut self) -> &mut *mut c_char {
+        &mut self.ptr
+    }
+
     /// Collect error from C API
     pub fn collect(self, code: i32) -> Result<(), MachineError> {
         if code == 0 {
@@ -105,8 +147,786 @@ impl ErrorCollector {
         } else {
             Err(MachineError {
                 code: unsafe { std::mem::transmute(code as u8) },
-                message: self.ptr
+                message: Message::Foreign(self.ptr),
             })
         }
     }
-}
\ No newline at end of file
+}
+
+/// Error returned when data fails to authenticate against the Merkle tree
+#[derive(Debug)]
+pub enum ProofError {
+    /// The emulator failed while producing the data or its proofs
+    Machine(MachineError),
+    /// A proof leads to a different root hash than the expected one
+    RootHashMismatch {
+        address: u64,
+        expected: Hash,
+        found: Hash,
+    },
+    /// The data does not hash to the target hash of its proof
+    TargetHashMismatch { address: u64, log2_size: usize },
+    /// The sibling hashes of a proof do not lead to its root hash
+    InvalidProof { address: u64, log2_size: usize },
+    /// Part of the requested range is not covered by any proof
+    MissingData { address: u64 },
+    /// The range does not start or end on a word boundary
+    UnalignedRange { start: u64, length: u64 },
+    /// The range extends past the end of the address space
+    RangeOverflow { start: u64, length: u64 },
+    /// Two targets of a multiproof are the same node or one contains the other
+    OverlappingTargets { address: u64, log2_size: usize },
+    /// A multiproof has no targets or the wrong number of sibling hashes
+    MalformedMultiProof,
+}
+
+impl From<MachineError> for ProofError {
+    fn from(error: MachineError) -> Self {
+        ProofError::Machine(error)
+    }
+}
+
+impl Display for ProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofError::Machine(error) => write!(f, "{}", error),
+            ProofError::RootHashMismatch {
+                address,
+                expected,
+                found,
+            } => write!(
+                f,
+                "proof for 0x{:x} leads to root hash {} instead of {}",
+                address, found, expected
+            ),
+            ProofError::TargetHashMismatch { address, log2_size } => write!(
+                f,
+                "data at 0x{:x} (log2_size {}) does not match the proven hash",
+                address, log2_size
+            ),
+            ProofError::InvalidProof { address, log2_size } => write!(
+                f,
+                "sibling hashes of proof for 0x{:x} (log2_size {}) are inconsistent",
+                address, log2_size
+            ),
+            ProofError::MissingData { address } => {
+                write!(f, "no proven data covers address 0x{:x}", address)
+            }
+            ProofError::UnalignedRange { start, length } => write!(
+                f,
+                "range 0x{:x}+0x{:x} is not aligned to word boundaries",
+                start, length
+            ),
+            ProofError::RangeOverflow { start, length } => write!(
+                f,
+                "range 0x{:x}+0x{:x} extends past the end of the address space",
+                start, length
+            ),
+            ProofError::OverlappingTargets { address, log2_size } => write!(
+                f,
+                "target 0x{:x} (log2_size {}) overlaps another target",
+                address, log2_size
+            ),
+            ProofError::MalformedMultiProof => {
+                write!(
+                    f,
+                    "multiproof has no targets or the wrong number of sibling hashes"
+                )
+            }
+        }
+    }
+}
+
+/// Error returned when an access log cannot be laid out for the emulator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessLogError {
+    /// The text at `path` contains a NUL byte at `position`, which a C string
+    /// cannot hold
+    NulByte { path: String, position: usize },
+}
+
+impl Display for AccessLogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessLogError::NulByte { path, position } => {
+                write!(f, "{} contains a NUL byte at {}", path, position)
+            }
+        }
+    }
+}
+
+/// Error returned when an access log cannot be converted to or from calldata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodingError {
+    /// The access at `index` has no sibling hashes
+    MissingProof { index: usize },
+    /// The access at `index` lacks the data or hash the encoding needs
+    MissingData { index: usize },
+    /// The replay of the step rejected the decoded accesses
+    Replay(ReplayError),
+    /// The calldata goes on after the step ended
+    TrailingData { offset: usize },
+}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodingError::MissingProof { index } => {
+                write!(f, "access {} has no complete proof", index)
+            }
+            EncodingError::MissingData { index } => {
+                write!(f, "access {} has no data to encode", index)
+            }
+            EncodingError::Replay(error) => write!(f, "cannot decode step: {}", error),
+            EncodingError::TrailingData { offset } => {
+                write!(f, "unexpected data after the step at offset {}", offset)
+            }
+        }
+    }
+}
+
+/// Error returned when the replay of a uarch step log rejects it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The log has no proofs
+    MissingProofs,
+    /// The log ended before the step did
+    TooFewAccesses,
+    /// The step ended before using every access in the log
+    TooManyAccesses { count: usize, used: usize },
+    /// The access at `index` is not the one the step performs
+    UnexpectedAccess {
+        index: usize,
+        expected_type: AccessType,
+        expected_address: u64,
+        found_type: AccessType,
+        found_address: u64,
+        found_log2_size: i32,
+    },
+    /// The access at `index` lacks word data
+    MissingData { index: usize },
+    /// The data of the access at `index` does not match its hash
+    DataHashMismatch { index: usize },
+    /// The proof of the access at `index` does not match the current root hash
+    RootHashMismatch { index: usize },
+    /// The access at `index` writes a value other than the one computed
+    WrittenValueMismatch {
+        index: usize,
+        expected: u64,
+        found: u64,
+    },
+    /// The root hash after the step is not the expected one
+    FinalRootHashMismatch { expected: Hash, found: Hash },
+    /// The instruction at `pc` is not supported by the microarchitecture
+    IllegalInstruction { pc: u64, insn: u32 },
+    /// A memory access is not aligned to its size
+    MisalignedAccess { address: u64, size: u32 },
+    /// The program requested an unknown ecall function
+    UnsupportedEcall { function: u64 },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::MissingProofs => write!(f, "log has no proofs"),
+            ReplayError::TooFewAccesses => write!(f, "too few accesses in log"),
+            ReplayError::TooManyAccesses { count, used } => {
+                write!(f, "too many accesses in log ({} of {} used)", used, count)
+            }
+            ReplayError::UnexpectedAccess {
+                index,
+                expected_type,
+                expected_address,
+                found_type,
+                found_address,
+                found_log2_size,
+            } => write!(
+                f,
+                "expected access {} to {:?} word at 0x{:x}, found {:?} of 2^{} bytes at 0x{:x}",
+                index, expected_type, expected_address, found_type, found_log2_size, found_address
+            ),
+            ReplayError::MissingData { index } => {
+                write!(f, "access {} has no word data", index)
+            }
+            ReplayError::DataHashMismatch { index } => {
+                write!(f, "data does not match hash in access {}", index)
+            }
+            ReplayError::RootHashMismatch { index } => {
+                write!(f, "mismatch in root hash of access {}", index)
+            }
+            ReplayError::WrittenValueMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "access {} writes 0x{:x}, expected 0x{:x}",
+                index, found, expected
+            ),
+            ReplayError::FinalRootHashMismatch { expected, found } => {
+                write!(
+                    f,
+                    "mismatch in root hash after replay (expected {}, found {})",
+                    expected, found
+                )
+            }
+            ReplayError::IllegalInstruction { pc, insn } => {
+                write!(f, "illegal instruction 0x{:08x} at 0x{:x}", insn, pc)
+            }
+            ReplayError::MisalignedAccess { address, size } => {
+                write!(f, "misaligned {}-byte access at 0x{:x}", size, address)
+            }
+            ReplayError::UnsupportedEcall { function } => {
+                write!(f, "unsupported ecall function {}", function)
+            }
+        }
+    }
+}
+
+/// Error returned when streaming access logs to a sink
+#[derive(Debug)]
+pub enum StreamError {
+    /// Error raised by the emulator while logging
+    Machine(MachineError),
+    /// Error raised while writing to the sink
+    Io(std::io::Error),
+}
+
+impl From<MachineError> for StreamError {
+    fn from(error: MachineError) -> Self {
+        StreamError::Machine(error)
+    }
+}
+
+impl From<std::io::Error> for StreamError {
+    fn from(error: std::io::Error) -> Self {
+        StreamError::Io(error)
+    }
+}
+
+impl Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Machine(error) => write!(f, "{}", error),
+            StreamError::Io(error) => write!(f, "failed to write log: {}", error),
+        }
+    }
+}
+
+/// Error returned when the brackets of an access log are not well formed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BracketError {
+    /// The bracket at `index` points past the last access
+    OutOfRange { index: usize },
+    /// The bracket at `index` points before the previous bracket
+    OutOfOrder { index: usize },
+    /// The end bracket at `index` has no matching begin
+    UnmatchedEnd { index: usize },
+    /// The end bracket at `index` closes a scope with a different name
+    MismatchedEnd {
+        index: usize,
+        expected: String,
+        found: String,
+    },
+    /// The begin bracket at `index` is never closed
+    UnclosedBegin { index: usize },
+}
+
+impl Display for BracketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BracketError::OutOfRange { index } => {
+                write!(f, "bracket {} points past the last access", index)
+            }
+            BracketError::OutOfOrder { index } => {
+                write!(f, "bracket {} points before the previous bracket", index)
+            }
+            BracketError::UnmatchedEnd { index } => {
+                write!(f, "end bracket {} has no matching begin", index)
+            }
+            BracketError::MismatchedEnd {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "end bracket {} closes \"{}\" but \"{}\" is open",
+                index, found, expected
+            ),
+            BracketError::UnclosedBegin { index } => {
+                write!(f, "begin bracket {} is never closed", index)
+            }
+        }
+    }
+}
+
+/// Error returned when bisecting two executions
+#[derive(Debug)]
+pub enum BisectError {
+    /// Error raised by the emulator while computing a hash
+    Machine(MachineError),
+    /// A recorded trace has no hash for `mcycle`
+    MissingHash { mcycle: u64 },
+    /// The executions already disagree at the start of the search
+    InitialDivergence { mcycle: u64 },
+    /// The executions still agree at the end of the search
+    NoDivergence { mcycle: u64 },
+    /// The search range does not end after it starts
+    EmptyRange { start: u64, end: u64 },
+    /// The compared micro-steps belong to different mcycles
+    McycleMismatch { left: u64, right: u64 },
+}
+
+impl From<MachineError> for BisectError {
+    fn from(error: MachineError) -> Self {
+        BisectError::Machine(error)
+    }
+}
+
+impl Display for BisectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BisectError::Machine(error) => write!(f, "{}", error),
+            BisectError::MissingHash { mcycle } => {
+                write!(f, "no recorded hash for mcycle {}", mcycle)
+            }
+            BisectError::InitialDivergence { mcycle } => {
+                write!(f, "executions already disagree at mcycle {}", mcycle)
+            }
+            BisectError::NoDivergence { mcycle } => {
+                write!(f, "executions still agree at mcycle {}", mcycle)
+            }
+            BisectError::EmptyRange { start, end } => {
+                write!(f, "search range [{}, {}] is empty", start, end)
+            }
+            BisectError::McycleMismatch { left, right } => {
+                write!(
+                    f,
+                    "cannot compare micro-steps of mcycles {} and {}",
+                    left, right
+                )
+            }
+        }
+    }
+}
+
+/// Error returned when building a commitment
+#[derive(Debug)]
+pub enum CommitmentError {
+    /// Error raised by the emulator while running the machine
+    Machine(MachineError),
+    /// The number of leaves is not a power of two
+    LeafCount { count: usize },
+    /// The number of leaves overflows `usize`
+    TooManyLeaves { log2_leaf_count: u32 },
+    /// The end of the interval at `index` does not fit in a cycle counter
+    CycleOverflow { index: u64, log2_stride: u32 },
+}
+
+impl From<MachineError> for CommitmentError {
+    fn from(error: MachineError) -> Self {
+        CommitmentError::Machine(error)
+    }
+}
+
+impl Display for CommitmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommitmentError::Machine(error) => write!(f, "{}", error),
+            CommitmentError::LeafCount { count } => {
+                write!(f, "number of leaves {} is not a power of two", count)
+            }
+            CommitmentError::TooManyLeaves { log2_leaf_count } => {
+                write!(f, "2^{} leaves do not fit in memory", log2_leaf_count)
+            }
+            CommitmentError::CycleOverflow { index, log2_stride } => write!(
+                f,
+                "interval {} of 2^{} cycles ends past the last cycle",
+                index, log2_stride
+            ),
+        }
+    }
+}
+
+/// Error returned when proving or checking a step proof
+#[derive(Debug)]
+pub enum ProveError {
+    /// Error raised by the emulator
+    Machine(MachineError),
+    /// The emulator verifier rejected the proof
+    Verification(VerificationError),
+    /// The machine is already past the requested cycle
+    CycleInPast { mcycle: u64, uarch_cycle: u64 },
+    /// The machine halted or the microarchitecture halted before the requested cycle
+    CycleUnreachable { mcycle: u64, uarch_cycle: u64 },
+    /// The proof uses an unknown format version
+    UnsupportedVersion { version: u32 },
+    /// The proof was produced by a different emulator version
+    EmulatorVersionMismatch { expected: String, found: String },
+    /// The proof log cannot be handed to the emulator verifier
+    Log(AccessLogError),
+}
+
+impl From<MachineError> for ProveError {
+    fn from(error: MachineError) -> Self {
+        ProveError::Machine(error)
+    }
+}
+
+impl From<VerificationError> for ProveError {
+    fn from(error: VerificationError) -> Self {
+        ProveError::Verification(error)
+    }
+}
+
+impl From<AccessLogError> for ProveError {
+    fn from(error: AccessLogError) -> Self {
+        ProveError::Log(error)
+    }
+}
+
+impl Display for ProveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProveError::Machine(error) => write!(f, "{}", error),
+            ProveError::Verification(error) => write!(f, "{}", error),
+            ProveError::CycleInPast {
+                mcycle,
+                uarch_cycle,
+            } => write!(
+                f,
+                "machine is already past the requested cycle (at mcycle {}, uarch cycle {})",
+                mcycle, uarch_cycle
+            ),
+            ProveError::CycleUnreachable {
+                mcycle,
+                uarch_cycle,
+            } => write!(
+                f,
+                "machine cannot reach mcycle {}, uarch cycle {}",
+                mcycle, uarch_cycle
+            ),
+            ProveError::UnsupportedVersion { version } => {
+                write!(f, "unsupported step proof version {}", version)
+            }
+            ProveError::EmulatorVersionMismatch { expected, found } => write!(
+                f,
+                "step proof produced by emulator {}, expected {}",
+                found, expected
+            ),
+            ProveError::Log(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Reason a log was rejected by the emulator verifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationErrorKind {
+    /// The log has no proofs
+    MissingProofs,
+    /// The log ended before the replay did
+    TooFewAccesses,
+    /// The replay ended before using every access in the log
+    TooManyAccesses,
+    /// An access is not the one the replay performs
+    UnexpectedAccess,
+    /// The data of an access does not match its hash
+    DataHashMismatch,
+    /// The proof of an access does not match the current root hash
+    RootHashMismatch,
+    /// The root hash after the replay is not the expected one
+    FinalRootHashMismatch,
+    /// Any other failure
+    Other,
+}
+
+/// Error returned when the emulator verifier rejects a log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationError {
+    /// Reason for the rejection
+    pub kind: VerificationErrorKind,
+    /// Index of the offending access, when the emulator reports one
+    pub access: Option<usize>,
+    /// Error code reported by the emulator
+    pub code: ErrorCode,
+    /// Error message reported by the emulator
+    pub message: String,
+}
+
+/// Fragments of the emulator 0.15 verifier messages, checked in order
+const VERIFICATION_PATTERNS: &[(&str, VerificationErrorKind)] = &[
+    (
+        "root hash after replay",
+        VerificationErrorKind::FinalRootHashMismatch,
+    ),
+    ("root hash", VerificationErrorKind::RootHashMismatch),
+    ("no proof", VerificationErrorKind::MissingProofs),
+    ("too few accesses", VerificationErrorKind::TooFewAccesses),
+    ("too many accesses", VerificationErrorKind::TooManyAccesses),
+    ("expected access", VerificationErrorKind::UnexpectedAccess),
+    ("does not match", VerificationErrorKind::DataHashMismatch),
+];
+
+/// Classifies a verifier message, falling back to [`VerificationErrorKind::Other`]
+fn classify_verification(message: &str) -> (VerificationErrorKind, Option<usize>) {
+    let kind = VERIFICATION_PATTERNS
+        .iter()
+        .find(|(pattern, _)| message.contains(pattern))
+        .map_or(VerificationErrorKind::Other, |(_, kind)| *kind);
+
+    let access = message.split("access ").skip(1).find_map(|rest| {
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    });
+
+    (kind, access)
+}
+
+impl From<MachineError> for VerificationError {
+    fn from(error: MachineError) -> Self {
+        let message = error.message().to_string();
+        let (kind, access) = classify_verification(&message);
+
+        VerificationError {
+            kind,
+            access,
+            code: error.code(),
+            message,
+        }
+    }
+}
+
+impl Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "verification failed ({:?}): {}", self.kind, self.message)
+    }
+}
+
+/// Problem found in a configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Path of the offending field, such as `flash_drive[1].length`
+    pub path: String,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl ConfigIssue {
+    /// Creates an issue for the field at `path`
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Error returned when a configuration is invalid, listing every problem found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// Problems found
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid configuration")?;
+
+        for issue in &self.issues {
+            write!(f, "\n  {}", issue)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Error returned when a `cartesi-machine` command-line spec cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliError {
+    /// The value is not a valid number
+    InvalidNumber { value: String },
+    /// The value is not valid for the key
+    InvalidValue { key: String, value: String },
+    /// The key is not part of the spec
+    UnknownKey { key: String },
+    /// A required key is absent
+    MissingKey { key: String },
+    /// The key or option requires a value
+    MissingValue { key: String },
+    /// The option is not supported
+    UnknownOption { option: String },
+    /// The image file, needed to infer a length, cannot be accessed
+    Image { filename: String, message: String },
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::InvalidNumber { value } => write!(f, "invalid number \"{}\"", value),
+            CliError::InvalidValue { key, value } => {
+                write!(f, "invalid value \"{}\" for {}", value, key)
+            }
+            CliError::UnknownKey { key } => write!(f, "unknown key \"{}\"", key),
+            CliError::MissingKey { key } => write!(f, "missing key \"{}\"", key),
+            CliError::MissingValue { key } => write!(f, "missing value for {}", key),
+            CliError::UnknownOption { option } => write!(f, "unknown option \"{}\"", option),
+            CliError::Image { filename, message } => {
+                write!(f, "cannot access {}: {}", filename, message)
+            }
+        }
+    }
+}
+
+/// Error returned when a Lua machine config cannot be imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LuaError {
+    /// The source is not valid Lua
+    Syntax { line: usize, message: String },
+    /// The construct is valid Lua but outside the supported table subset
+    Unsupported { line: usize, construct: String },
+    /// The field is not part of the machine configuration
+    UnknownField { path: String },
+    /// The field has a value of the wrong type
+    InvalidValue {
+        path: String,
+        expected: &'static str,
+    },
+}
+
+impl Display for LuaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LuaError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            LuaError::Unsupported { line, construct } => {
+                write!(f, "line {}: unsupported construct {}", line, construct)
+            }
+            LuaError::UnknownField { path } => write!(f, "unknown field {}", path),
+            LuaError::InvalidValue { path, expected } => {
+                write!(f, "invalid value for {}, expected {}", path, expected)
+            }
+        }
+    }
+}
+
+/// Error returned when a kernel command line or boot script cannot be composed or parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BootError {
+    /// The kernel parameter name cannot appear on the command line
+    InvalidName { name: String },
+    /// The kernel parameter value contains a double quote, which cannot be escaped
+    UnquotableValue { name: String, value: String },
+    /// The memory size is not valid
+    InvalidMemory { value: String },
+    /// A quote in the script is never closed
+    UnterminatedQuote { line: usize },
+    /// A quote in the command line is never closed, `offset` counting
+    /// characters up to the opening quote
+    UnterminatedBootargsQuote { offset: usize },
+    /// The script uses shell syntax beyond plain commands
+    UnsupportedShell { line: usize, construct: String },
+}
+
+impl Display for BootError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BootError::InvalidName { name } => write!(f, "invalid parameter name \"{}\"", name),
+            BootError::UnquotableValue { name, value } => {
+                write!(f, "value of {} cannot be quoted: {}", name, value)
+            }
+            BootError::InvalidMemory { value } => write!(f, "invalid memory size \"{}\"", value),
+            BootError::UnterminatedQuote { line } => write!(f, "line {}: unterminated quote", line),
+            BootError::UnterminatedBootargsQuote { offset } => {
+                write!(f, "unterminated quote at character {}", offset)
+            }
+            BootError::UnsupportedShell { line, construct } => {
+                write!(
+                    f,
+                    "line {}: unsupported shell construct {}",
+                    line, construct
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_issues_become_invalid_arguments() {
+        let error = MachineError::from(ConfigIssue::new("dtb.init", "contains a NUL byte at 4"));
+
+        assert_eq!(error.code(), ErrorCode::InvalidArgument);
+        assert_eq!(error.message(), "dtb.init: contains a NUL byte at 4");
+        assert_eq!(
+            error.to_string(),
+            "Error 1: dtb.init: contains a NUL byte at 4"
+        );
+    }
+
+    #[test]
+    fn owned_messages_are_freed() {
+        let leaked = crate::testing::leaked_bytes(|| {
+            let error = MachineError::from(ConfigIssue::new(
+                "ram.image_filename",
+                "contains a NUL byte at 0",
+            ));
+            assert!(!error.message().is_empty());
+        });
+
+        assert_eq!(leaked, 0);
+    }
+
+    #[test]
+    fn classifies_verifier_messages() {
+        use VerificationErrorKind::*;
+
+        for (message, kind, access) in [
+            (
+                "mismatch in root hash after replay",
+                FinalRootHashMismatch,
+                None,
+            ),
+            (
+                "Mismatch in root hash of access 4",
+                RootHashMismatch,
+                Some(4),
+            ),
+            ("initial root hash mismatch", RootHashMismatch, None),
+            ("log has no proofs", MissingProofs, None),
+            ("too few accesses in log", TooFewAccesses, None),
+            ("too many accesses in log", TooManyAccesses, None),
+            (
+                "expected access 2 to read uarch.pc",
+                UnexpectedAccess,
+                Some(2),
+            ),
+            (
+                "expected access 12 to write 2^3 bytes to x5",
+                UnexpectedAccess,
+                Some(12),
+            ),
+            (
+                "hash of read data and read hash at access 7 does not match read hash",
+                DataHashMismatch,
+                Some(7),
+            ),
+            ("invalid instruction", Other, None),
+            ("", Other, None),
+        ] {
+            assert_eq!(
+                classify_verification(message),
+                (kind, access),
+                "misclassified {:?}",
+                message
+            );
+        }
+    }
+}
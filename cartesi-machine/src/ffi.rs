@@ -1,6 +1,8 @@
 //! This module contains functions for converting between Rust and C strings.
 
-use std::ffi::{c_char, CStr};
+use std::ffi::{c_char, CStr, CString};
+
+use crate::errors::ConfigIssue;
 
 pub(crate) fn from_cstr(cstr: *const c_char) -> Option<String> {
     if cstr.is_null() {
@@ -10,20 +12,101 @@ pub(crate) fn from_cstr(cstr: *const c_char) -> Option<String> {
     }
 }
 
-pub(crate) fn to_cstr(string: Option<String>) -> *const c_char {
-    match string {
-        Some(string) => {
-            let cstring = std::ffi::CString::new(string).unwrap();
-            let ptr = cstring.as_ptr();
-            std::mem::forget(cstring);
-            ptr
+/// Owns the C strings handed to the emulator, keeping them valid until dropped
+#[derive(Debug, Default)]
+pub(crate) struct CStringArena {
+    strings: Vec<CString>,
+}
+
+impl CStringArena {
+    /// Copies the string of the field at `path` into the arena, returning
+    /// null for `None`
+    pub(crate) fn alloc(
+        &mut self,
+        path: &str,
+        string: Option<&str>,
+    ) -> Result<*const c_char, ConfigIssue> {
+        match string {
+            Some(string) => {
+                let cstring = CString::new(string).map_err(|error| {
+                    ConfigIssue::new(
+                        path,
+                        format!("contains a NUL byte at {}", error.nul_position()),
+                    )
+                })?;
+                // The buffer of a `CString` never moves, so the pointer
+                // survives the vector growing
+                let ptr = cstring.as_ptr();
+                self.strings.push(cstring);
+                Ok(ptr)
+            }
+            None => Ok(std::ptr::null()),
         }
-        None => std::ptr::null(),
     }
 }
 
+/// Releases a C string allocated with `CString::into_raw`
 pub(crate) fn free_cstr(string: *const c_char) {
     if !string.is_null() {
-        unsafe { drop(std::ffi::CString::from_raw(string as *mut c_char)) }
+        unsafe { drop(CString::from_raw(string as *mut c_char)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::leaked_bytes;
+
+    #[test]
+    fn arena_pointers_survive_growth() {
+        let mut strings = CStringArena::default();
+        let values: Vec<String> = (0..1000).map(|i| format!("image-{}.ext2", i)).collect();
+
+        // Every pointer is read after the vector has reallocated many times
+        let ptrs: Vec<_> = values
+            .iter()
+            .map(|value| strings.alloc("value", Some(value)).unwrap())
+            .collect();
+
+        for (ptr, value) in ptrs.iter().zip(&values) {
+            assert_eq!(from_cstr(*ptr).as_deref(), Some(value.as_str()));
+        }
+        assert!(strings.alloc("value", None).unwrap().is_null());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn arena_rejects_nul_bytes() {
+        let mut strings = CStringArena::default();
+
+        assert_eq!(
+            strings.alloc("dtb.init", Some("echo\0rm")),
+            Err(ConfigIssue::new("dtb.init", "contains a NUL byte at 4"))
+        );
+        assert!(strings.strings.is_empty());
+    }
+
+    #[test]
+    fn arena_frees_its_strings() {
+        let leaked = leaked_bytes(|| {
+            let mut strings = CStringArena::default();
+            for i in 0..100 {
+                strings.alloc("value", Some(&"x".repeat(i))).unwrap();
+            }
+            let _ = strings.alloc("value", Some("a\0b"));
+        });
+
+        assert_eq!(leaked, 0);
+    }
+
+    #[test]
+    fn raw_strings_are_freed() {
+        let leaked = leaked_bytes(|| {
+            let ptr = CString::new("rootfs.ext2").unwrap().into_raw();
+            assert_eq!(from_cstr(ptr).as_deref(), Some("rootfs.ext2"));
+            free_cstr(ptr);
+            free_cstr(std::ptr::null());
+        });
+
+        assert_eq!(leaked, 0);
+    }
+}
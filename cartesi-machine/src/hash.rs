@@ -3,6 +3,7 @@
 use std::fmt::Display;
 
 use cartesi_machine_sys::cm_hash;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 
 /// Digest generated by a hash function.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -28,4 +29,22 @@ impl Hash {
     pub fn as_bytes(&self) -> &[u8] {
         &self.0
     }
-}
\ No newline at end of file
+}
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        let bytes = hex::decode(string.trim_start_matches("0x")).map_err(D::Error::custom)?;
+        let hash = bytes
+            .try_into()
+            .map_err(|_| D::Error::custom("hash must be 32 bytes long"))?;
+
+        Ok(Hash::new(hash))
+    }
+}
@@ -2,17 +2,31 @@
 
 use std::path::Path;
 
+pub mod bisect;
+pub mod calldata;
+pub mod commitment;
 pub mod configuration;
+pub mod diff;
 pub mod errors;
 pub mod hash;
 pub mod log;
+pub mod merkle;
+pub mod pretty;
 pub mod proof;
+pub mod replay;
+pub mod scope;
+pub mod step_proof;
+pub mod stream;
+pub mod verifier;
 mod ffi;
+#[cfg(test)]
+mod testing;
 
-use cartesi_machine_sys::{cm_machine_runtime_config, cm_memory_range_config};
-use configuration::{free_cm_memory_range_config_cstr, OwnedMachineConfig};
+use cartesi_machine_sys::{cm_access_log, cm_machine_runtime_config};
+use configuration::{ForeignMachineConfig, OwnedMachineConfig};
 use configuration::{MachineConfig, RuntimeConfig};
-use errors::{ErrorCollector, MachineError};
+use errors::{ErrorCollector, MachineError, ProofError, ProveError};
+use ffi::CStringArena;
 
 macro_rules! read_csr {
     ($typ: ty, $name: ident, $flag: ident) => {
@@ -24,7 +38,7 @@ macro_rules! read_csr {
                 let result = cartesi_machine_sys::$flag(
                     self.machine,
                     &mut value,
-                    &mut error_collector.as_mut_ptr(),
+                    error_collector.as_out_ptr(),
                 );
 
                 error_collector.collect(result)?;
@@ -44,7 +58,7 @@ macro_rules! write_csr {
                 let result = cartesi_machine_sys::$flag(
                     self.machine,
                     value,
-                    &mut error_collector.as_mut_ptr(),
+                    error_collector.as_out_ptr(),
                 );
 
                 error_collector.collect(result)?;
@@ -63,7 +77,7 @@ macro_rules! iflags {
             unsafe {
                 let result = cartesi_machine_sys::$flag(
                     self.machine,
-                    &mut error_collector.as_mut_ptr(),
+                    error_collector.as_out_ptr(),
                 );
 
                 error_collector.collect(result)?;
@@ -105,6 +119,7 @@ impl BreakReason {
 
 /// Control and Status Registers (CSRs) to use with read_csr and write_csr
 #[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CSR {
     Pc = 0,
     Fcsr,
@@ -147,6 +162,96 @@ pub enum CSR {
     UarchHaltFlag,
 }
 
+impl CSR {
+    /// Every CSR, in declaration order
+    pub const ALL: [CSR; 39] = [
+        CSR::Pc,
+        CSR::Fcsr,
+        CSR::Mvendorid,
+        CSR::Marchid,
+        CSR::Mimpid,
+        CSR::Mcycle,
+        CSR::Icycleinstret,
+        CSR::Mstatus,
+        CSR::Mtvec,
+        CSR::Mscratch,
+        CSR::Mepc,
+        CSR::Mcause,
+        CSR::Mtval,
+        CSR::Misa,
+        CSR::Mie,
+        CSR::Mip,
+        CSR::Medeleg,
+        CSR::Mideleg,
+        CSR::Mcounteren,
+        CSR::Menvcfg,
+        CSR::Stvec,
+        CSR::Sscratch,
+        CSR::Sepc,
+        CSR::Scause,
+        CSR::Stval,
+        CSR::Satp,
+        CSR::Scounteren,
+        CSR::Senvcfg,
+        CSR::Ilrsc,
+        CSR::Iflags,
+        CSR::ClintMtimecmp,
+        CSR::HtifTohost,
+        CSR::HtifFromhost,
+        CSR::HtifIhalt,
+        CSR::HtifIconsole,
+        CSR::HtifIyield,
+        CSR::UarchPc,
+        CSR::UarchCycle,
+        CSR::UarchHaltFlag,
+    ];
+
+    /// Name of the CSR as shown in logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            CSR::Pc => "pc",
+            CSR::Fcsr => "fcsr",
+            CSR::Mvendorid => "mvendorid",
+            CSR::Marchid => "marchid",
+            CSR::Mimpid => "mimpid",
+            CSR::Mcycle => "mcycle",
+            CSR::Icycleinstret => "icycleinstret",
+            CSR::Mstatus => "mstatus",
+            CSR::Mtvec => "mtvec",
+            CSR::Mscratch => "mscratch",
+            CSR::Mepc => "mepc",
+            CSR::Mcause => "mcause",
+            CSR::Mtval => "mtval",
+            CSR::Misa => "misa",
+            CSR::Mie => "mie",
+            CSR::Mip => "mip",
+            CSR::Medeleg => "medeleg",
+            CSR::Mideleg => "mideleg",
+            CSR::Mcounteren => "mcounteren",
+            CSR::Menvcfg => "menvcfg",
+            CSR::Stvec => "stvec",
+            CSR::Sscratch => "sscratch",
+            CSR::Sepc => "sepc",
+            CSR::Scause => "scause",
+            CSR::Stval => "stval",
+            CSR::Satp => "satp",
+            CSR::Scounteren => "scounteren",
+            CSR::Senvcfg => "senvcfg",
+            CSR::Ilrsc => "ilrsc",
+            CSR::Iflags => "iflags",
+            CSR::ClintMtimecmp => "clint.mtimecmp",
+            CSR::HtifTohost => "htif.tohost",
+            CSR::HtifFromhost => "htif.fromhost",
+            CSR::HtifIhalt => "htif.ihalt",
+            CSR::HtifIconsole => "htif.iconsole",
+            CSR::HtifIyield => "htif.iyield",
+            CSR::UarchPc => "uarch.pc",
+            CSR::UarchCycle => "uarch.cycle",
+            CSR::UarchHaltFlag => "uarch.halt_flag",
+        }
+    }
+}
+
 /// Return values of uarch_interpret. Reason for the uarch_interpret to break.
 #[repr(u8)]
 pub enum UarchBreakReason {
@@ -154,6 +259,17 @@ pub enum UarchBreakReason {
     UarchHalted,
 }
 
+/// Description of a memory range in the machine address space
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryRangeDescription {
+    /// Address of the first byte in the range
+    pub start: u64,
+    /// Number of bytes in the range
+    pub length: u64,
+    /// Description of the range contents
+    pub description: String,
+}
+
 /// Machine instance handle
 pub struct Machine {
     machine: *mut cartesi_machine_sys::cm_machine,
@@ -178,15 +294,16 @@ impl Machine {
             machine: std::ptr::null_mut(),
         };
 
+        let config = OwnedMachineConfig::try_from(&machine_config)?;
+
         unsafe {
-            let config = OwnedMachineConfig::from(machine_config);
             let runtime = cm_machine_runtime_config::from(runtime);
 
             let result = cartesi_machine_sys::cm_create_machine(
                 config.as_ref(),
                 &runtime,
                 &mut machine.machine,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -211,7 +328,7 @@ impl Machine {
                 path.as_ptr(),
                 &runtime,
                 &mut machine.machine,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -231,7 +348,7 @@ impl Machine {
             let result = cartesi_machine_sys::cm_store(
                 self.machine,
                 path.as_ptr(),
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -250,7 +367,7 @@ impl Machine {
                 self.machine,
                 mcycle_end,
                 &mut break_reason,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -259,6 +376,55 @@ impl Machine {
         Ok(unsafe { BreakReason::from_u8_unchecked(break_reason as u8) })
     }
 
+    /// Logs consecutive uarch steps until the microarchitecture halts, then logs its reset
+    ///
+    /// Logging stops without a reset after a step that leaves the uarch cycle unchanged.
+    pub fn log_uarch_steps(
+        &mut self,
+        log_type: log::AccessLogType,
+        one_based: bool,
+    ) -> stream::UarchStepLogs<'_> {
+        stream::UarchStepLogs::new(self, log_type, one_based)
+    }
+
+    /// Runs the machine forward to `mcycle` and `uarch_cycle` and proves the next transition
+    ///
+    /// The transition is the uarch reset when the microarchitecture has halted,
+    /// and a uarch step otherwise. A live machine cannot move backwards, so
+    /// earlier cycles fail with [`ProveError::CycleInPast`]; use
+    /// [`bisect::StoredMachine::prove_step`] to reload a stored machine instead.
+    pub fn prove_step(
+        &mut self,
+        mcycle: u64,
+        uarch_cycle: u64,
+    ) -> Result<step_proof::StepProof, ProveError> {
+        step_proof::prove_step(self, mcycle, uarch_cycle)
+    }
+
+    /// Runs the microarchitecture until it reaches `uarch_cycle_end` or halts
+    pub fn run_uarch(&mut self, uarch_cycle_end: u64) -> Result<UarchBreakReason, MachineError> {
+        let mut error_collector = ErrorCollector::new();
+        let mut break_reason = 0;
+
+        unsafe {
+            let result = cartesi_machine_sys::cm_machine_run_uarch(
+                self.machine,
+                uarch_cycle_end,
+                &mut break_reason,
+                error_collector.as_out_ptr(),
+            );
+
+            error_collector.collect(result)?;
+        }
+
+        Ok(match break_reason {
+            cartesi_machine_sys::CM_UARCH_BREAK_REASON_CM_UARCH_BREAK_REASON_UARCH_HALTED => {
+                UarchBreakReason::UarchHalted
+            }
+            _ => UarchBreakReason::ReachedTargetCycle,
+        })
+    }
+
     /// Runs the machine for one micro cycle logging all accesses to the state.
     pub fn log_uarch_step(
         &mut self,
@@ -274,7 +440,7 @@ impl Machine {
                 log_type.into(),
                 one_based,
                 &mut access_log,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -284,9 +450,10 @@ impl Machine {
     }
 
     /// Checks the internal consistency of an access log
-    pub fn verify_uarch_step_log(
+    #[deprecated(note = "use `verifier::Verifier::verify_uarch_step_log` instead")]
+    pub fn verify_uarch_step_log<L: AsRef<cm_access_log>>(
         &mut self,
-        log: &log::AccessLog,
+        log: &L,
         runtime: RuntimeConfig,
         one_based: bool,
     ) -> Result<(), MachineError> {
@@ -296,10 +463,10 @@ impl Machine {
             let runtime = cm_machine_runtime_config::from(runtime);
 
             let result = cartesi_machine_sys::cm_verify_uarch_step_log(
-                log.as_ptr(),
+                log.as_ref(),
                 &runtime,
                 one_based,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -309,10 +476,11 @@ impl Machine {
     }
 
     /// Checks the validity of a state transition
-    pub fn verify_uarch_step_state_transition(
+    #[deprecated(note = "use `verifier::Verifier::verify_uarch_step_state_transition` instead")]
+    pub fn verify_uarch_step_state_transition<L: AsRef<cm_access_log>>(
         &mut self,
         root_hash_before: &hash::Hash,
-        log: &log::AccessLog,
+        log: &L,
         root_hash_after: &hash::Hash,
         runtime: RuntimeConfig,
         one_based: bool,
@@ -324,11 +492,11 @@ impl Machine {
 
             let result = cartesi_machine_sys::cm_verify_uarch_step_state_transition(
                 root_hash_before.as_ptr(),
-                log.as_ptr(),
+                log.as_ref(),
                 root_hash_after.as_ptr(),
                 &runtime,
                 one_based,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -338,10 +506,11 @@ impl Machine {
     }
 
     /// Checks the validity of a state transition caused by a uarch state reset
-    pub fn verify_uarch_reset_state_transition(
+    #[deprecated(note = "use `verifier::Verifier::verify_uarch_reset_state_transition` instead")]
+    pub fn verify_uarch_reset_state_transition<L: AsRef<cm_access_log>>(
         &mut self,
         root_hash_before: &hash::Hash,
-        log: &log::AccessLog,
+        log: &L,
         root_hash_after: &hash::Hash,
         runtime: RuntimeConfig,
         one_based: bool,
@@ -353,11 +522,11 @@ impl Machine {
 
             let result = cartesi_machine_sys::cm_verify_uarch_reset_state_transition(
                 root_hash_before.as_ptr(),
-                log.as_ptr(),
+                log.as_ref(),
                 root_hash_after.as_ptr(),
                 &runtime,
                 one_based,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -367,9 +536,10 @@ impl Machine {
     }
 
     /// Checks the internal consistency of an access log produced by cm_log_uarch_step
-    pub fn verify_uarch_reset_log(
+    #[deprecated(note = "use `verifier::Verifier::verify_uarch_reset_log` instead")]
+    pub fn verify_uarch_reset_log<L: AsRef<cm_access_log>>(
         &mut self,
-        log: &log::AccessLog,
+        log: &L,
         runtime: RuntimeConfig,
         one_based: bool,
     ) -> Result<(), MachineError> {
@@ -379,10 +549,10 @@ impl Machine {
             let runtime = cm_machine_runtime_config::from(runtime);
 
             let result = cartesi_machine_sys::cm_verify_uarch_reset_log(
-                log.as_ptr(),
+                log.as_ref(),
                 &runtime,
                 one_based,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -406,7 +576,7 @@ impl Machine {
                 address,
                 log2_size,
                 &mut proof,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -415,6 +585,41 @@ impl Machine {
         Ok(proof::MerkleTreeProof::new(proof))
     }
 
+    /// Obtains a single proof for several nodes in the Merkle tree
+    pub fn get_multiproof(
+        &mut self,
+        targets: &[(u64, usize)],
+    ) -> Result<proof::MultiProof, ProofError> {
+        let mut proofs = Vec::with_capacity(targets.len());
+
+        for &(address, log2_size) in targets {
+            proofs.push(self.get_proof(address, log2_size as i32)?.to_data());
+        }
+
+        proof::MultiProof::from_proofs(&proofs)
+    }
+
+    /// Obtains the commitment to an arbitrary word-aligned memory range
+    pub fn get_range_hash(
+        &mut self,
+        start: u64,
+        length: u64,
+    ) -> Result<merkle::RangeHash, ProofError> {
+        let mut subtrees = Vec::new();
+
+        for (address, log2_size) in merkle::split_range(start, length)? {
+            let proof = self.get_proof(address, log2_size as i32)?;
+
+            subtrees.push(merkle::Subtree {
+                address,
+                log2_size,
+                hash: proof.target_hash(),
+            });
+        }
+
+        Ok(merkle::RangeHash::new(start, length, subtrees))
+    }
+
     /// Obtains the root hash of the Merkle tree
     pub fn get_root_hash(&mut self) -> Result<hash::Hash, MachineError> {
         let mut error_collector = ErrorCollector::new();
@@ -424,7 +629,7 @@ impl Machine {
             let result = cartesi_machine_sys::cm_get_root_hash(
                 self.machine,
                 &mut hash,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -442,7 +647,7 @@ impl Machine {
             let result = cartesi_machine_sys::cm_verify_merkle_tree(
                 self.machine,
                 &mut result,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -460,7 +665,7 @@ impl Machine {
                 self.machine,
                 csr as u32,
                 value,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -479,7 +684,7 @@ impl Machine {
                 self.machine,
                 csr as u32,
                 &mut value,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -503,7 +708,7 @@ impl Machine {
                 self.machine,
                 word_address,
                 &mut word_value,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -523,7 +728,7 @@ impl Machine {
                 address,
                 data.as_mut_ptr(),
                 length,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -532,6 +737,57 @@ impl Machine {
         Ok(data)
     }
 
+    /// Read a chunk of data from the machine memory with a proof for every covered word.
+    pub fn read_memory_with_word_proofs(
+        &mut self,
+        address: u64,
+        length: u64,
+    ) -> Result<proof::AuthenticatedMemory, ProofError> {
+        self.read_memory_with_proofs(address, length, merkle::LOG2_WORD_SIZE)
+    }
+
+    /// Read a chunk of data from the machine memory with a proof for every covered page.
+    pub fn read_memory_with_page_proofs(
+        &mut self,
+        address: u64,
+        length: u64,
+    ) -> Result<proof::AuthenticatedMemory, ProofError> {
+        self.read_memory_with_proofs(address, length, merkle::LOG2_PAGE_SIZE)
+    }
+
+    fn read_memory_with_proofs(
+        &mut self,
+        address: u64,
+        length: u64,
+        log2_size: usize,
+    ) -> Result<proof::AuthenticatedMemory, ProofError> {
+        let root_hash = self.get_root_hash()?;
+        let chunk_size = 1u64 << log2_size;
+        let first = address & !(chunk_size - 1);
+        let end = address
+            .checked_add(length)
+            .ok_or(ProofError::RangeOverflow {
+                start: address,
+                length,
+            })?;
+        let mut chunks = Vec::new();
+
+        for chunk_address in (first..end).step_by(chunk_size as usize) {
+            let data = self.read_memory(chunk_address, chunk_size)?;
+            let proof = self.get_proof(chunk_address, log2_size as i32)?.to_data();
+
+            proof::verify_chunk(&data, &proof, &root_hash)?;
+            chunks.push(proof::AuthenticatedChunk { data, proof });
+        }
+
+        Ok(proof::AuthenticatedMemory {
+            address,
+            length,
+            root_hash,
+            chunks,
+        })
+    }
+
     /// Write a chunk of data to the machine memory.
     pub fn write_memory(&mut self, address: u64, data: &[u8]) -> Result<(), MachineError> {
         let mut error_collector = ErrorCollector::new();
@@ -542,7 +798,7 @@ impl Machine {
                 address,
                 data.as_ptr(),
                 data.len(),
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -566,7 +822,7 @@ impl Machine {
                 address,
                 data.as_mut_ptr(),
                 length,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -589,7 +845,7 @@ impl Machine {
                 address,
                 data.as_ptr(),
                 data.len(),
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -608,7 +864,7 @@ impl Machine {
                 self.machine,
                 i as i32,
                 &mut value,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -626,7 +882,7 @@ impl Machine {
                 self.machine,
                 i as i32,
                 value,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -655,7 +911,7 @@ impl Machine {
                 self.machine,
                 i as i32,
                 &mut value,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -673,7 +929,7 @@ impl Machine {
                 self.machine,
                 i as i32,
                 value,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -687,6 +943,51 @@ impl Machine {
         unsafe { cartesi_machine_sys::cm_get_f_address(i as i32) }
     }
 
+    /// Returns the description of every memory range in the machine
+    pub fn get_memory_ranges(&mut self) -> Result<Vec<MemoryRangeDescription>, MachineError> {
+        let mut error_collector = ErrorCollector::new();
+        let mut ranges = std::ptr::null_mut();
+
+        unsafe {
+            let result = cartesi_machine_sys::cm_get_memory_ranges(
+                self.machine,
+                &mut ranges,
+                error_collector.as_out_ptr(),
+            );
+
+            error_collector.collect(result)?;
+        }
+
+        let descriptions = unsafe {
+            let entries = if (*ranges).entry.is_null() {
+                &[]
+            } else {
+                std::slice::from_raw_parts((*ranges).entry, (*ranges).count)
+            };
+
+            entries
+                .iter()
+                .map(|entry| MemoryRangeDescription {
+                    start: entry.start,
+                    length: entry.length,
+                    description: if entry.description.is_null() {
+                        String::new()
+                    } else {
+                        std::ffi::CStr::from_ptr(entry.description)
+                            .to_string_lossy()
+                            .into_owned()
+                    },
+                })
+                .collect()
+        };
+
+        unsafe {
+            cartesi_machine_sys::cm_delete_memory_range_descr_array(ranges);
+        }
+
+        Ok(descriptions)
+    }
+
     /// Returns copy of initialization config.
     pub fn get_initial_config(&mut self) -> Result<MachineConfig, MachineError> {
         let mut error_collector = ErrorCollector::new();
@@ -696,19 +997,15 @@ impl Machine {
             let result = cartesi_machine_sys::cm_get_initial_config(
                 self.machine,
                 &mut config,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
         }
 
-        let new_config: MachineConfig = unsafe { (*config).into() };
+        let config = unsafe { ForeignMachineConfig::from_raw(config) };
 
-        unsafe {
-            cartesi_machine_sys::cm_delete_machine_config(config);
-        }
-
-        Ok(new_config)
+        Ok(MachineConfig::from(&config))
     }
 
     /// Returns copy of default system config.
@@ -719,19 +1016,15 @@ impl Machine {
         unsafe {
             let result = cartesi_machine_sys::cm_get_default_config(
                 &mut config,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
         }
 
-        let new_config: MachineConfig = unsafe { (*config).into() };
+        let config = unsafe { ForeignMachineConfig::from_raw(config) };
 
-        unsafe {
-            cartesi_machine_sys::cm_delete_machine_config(config);
-        }
-
-        Ok(new_config)
+        Ok(MachineConfig::from(&config))
     }
 
     /// Replaces a memory range
@@ -740,20 +1033,19 @@ impl Machine {
         new_range: configuration::MemoryRangeConfig,
     ) -> Result<(), MachineError> {
         let mut error_collector = ErrorCollector::new();
-        let mut range: cm_memory_range_config = new_range.into();
+        let mut strings = CStringArena::default();
+        let mut range = new_range.to_cm("memory_range", &mut strings)?;
 
         unsafe {
             let result = cartesi_machine_sys::cm_replace_memory_range(
                 self.machine,
                 &mut range,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
         }
 
-        free_cm_memory_range_config_cstr(&mut range);
-
         Ok(())
     }
 
@@ -766,7 +1058,7 @@ impl Machine {
             let result = cartesi_machine_sys::cm_verify_dirty_page_maps(
                 self.machine,
                 &mut result,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
@@ -790,7 +1082,7 @@ impl Machine {
                 log_type.into(),
                 one_based,
                 &mut access_log,
-                &mut error_collector.as_mut_ptr(),
+                error_collector.as_out_ptr(),
             );
 
             error_collector.collect(result)?;
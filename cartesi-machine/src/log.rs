@@ -1,8 +1,16 @@
 //! Logging utilities for Cartesi Machine.
 
+use std::ffi::{c_char, CString};
+
+use cartesi_machine_sys::{cm_access, cm_access_log, cm_bracket_note, cm_hash, cm_hash_array};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::AccessLogError;
 use crate::{ffi, hash::Hash};
 
 /// Type of state access
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum AccessType {
     /// Read operation
     Read = 0,
@@ -10,7 +18,7 @@ pub enum AccessType {
     Write,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(C)]
 /// Type of access log
 pub struct AccessLogType {
@@ -34,7 +42,8 @@ impl From<cartesi_machine_sys::cm_access_log_type> for AccessLogType {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[repr(C)]
 /// Bracket type
 pub enum BracketType {
@@ -67,7 +76,16 @@ impl<'a> BracketNote<'a>  {
     }
 
     pub fn text(&self) -> String {
-        unsafe { ffi::from_cstr((*self.ptr).text) }.unwrap()
+        unsafe { ffi::from_cstr((*self.ptr).text) }.unwrap_or_default()
+    }
+
+    /// Copies the bracket note out of the emulator
+    pub fn to_data(&self) -> BracketNoteData {
+        BracketNoteData {
+            kind: self.kind(),
+            r#where: self.r#where(),
+            text: self.text(),
+        }
     }
 }
 
@@ -105,9 +123,9 @@ impl<'a>  Access<'a> {
         Hash::new(unsafe { (*self.ptr).read_hash })
     }
 
-    /// Data before access
+    /// Data before access (empty if not logged)
     pub fn read_data(&self) -> &[u8] {
-        unsafe { std::slice::from_raw_parts((*self.ptr).read_data, (*self.ptr).read_data_size) }
+        unsafe { data_slice((*self.ptr).read_data, (*self.ptr).read_data_size) }
     }
 
     /// Hash of data after access (if writing)
@@ -115,17 +133,50 @@ impl<'a>  Access<'a> {
         Hash::new(unsafe { (*self.ptr).written_hash })
     }
 
-    /// Data after access (if writing)
+    /// Data after access (empty if not writing or not logged)
     pub fn written_data(&self) -> &[u8] {
-        unsafe { std::slice::from_raw_parts((*self.ptr).written_data, (*self.ptr).written_data_size) }
+        unsafe { data_slice((*self.ptr).written_data, (*self.ptr).written_data_size) }
     }
 
-    /// Sibling hashes towards root
-    pub fn sibling_hashes(&self) -> Vec<Hash> {
-        let sibling_hashes = unsafe { *(*self.ptr).sibling_hashes };
-        let sibling_hashes = unsafe { std::slice::from_raw_parts(sibling_hashes.entry, sibling_hashes.count) };
+    /// Sibling hashes towards root (if the log includes proofs)
+    pub fn sibling_hashes(&self) -> Option<Vec<Hash>> {
+        let sibling_hashes = unsafe { (*self.ptr).sibling_hashes };
+
+        if sibling_hashes.is_null() {
+            return None;
+        }
 
-        sibling_hashes.iter().map(|hash| Hash::new(*hash)).collect()
+        let sibling_hashes = unsafe { *sibling_hashes };
+        let sibling_hashes = unsafe { data_slice(sibling_hashes.entry, sibling_hashes.count) };
+
+        Some(sibling_hashes.iter().map(|hash| Hash::new(*hash)).collect())
+    }
+
+    /// Copies the access out of the emulator
+    pub fn to_data(&self) -> AccessData {
+        let access_type = self.access_type();
+        let read_data = self.read_data();
+        let written_data = self.written_data();
+
+        AccessData {
+            access_type,
+            address: self.address(),
+            log2_size: self.log2_size(),
+            read_hash: self.read_hash(),
+            read_data: (!read_data.is_empty()).then(|| read_data.to_vec()),
+            written_hash: (access_type == AccessType::Write).then(|| self.written_hash()),
+            written_data: (!written_data.is_empty()).then(|| written_data.to_vec()),
+            sibling_hashes: self.sibling_hashes(),
+        }
+    }
+}
+
+/// Views C memory as a slice, accepting null pointers for empty data
+unsafe fn data_slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
     }
 }
 
@@ -143,10 +194,6 @@ impl AccessLog {
         Self(ptr)
     }
 
-    pub(crate) fn as_ptr(&self) -> &cartesi_machine_sys::cm_access_log {
-        unsafe { &*self.0 }
-    }
-
     pub fn accesses(&self) -> Vec<Access> {
         let accesses = unsafe { (*self.0).accesses };
         let accesses = unsafe { std::slice::from_raw_parts(accesses.entry, accesses.count) };
@@ -171,4 +218,546 @@ impl AccessLog {
     pub fn log_type(&self) -> AccessLogType {
         unsafe { std::mem::transmute((*self.0).log_type) }
     }
-}
\ No newline at end of file
+
+    /// Copies the whole log out of the emulator
+    pub fn to_data(&self) -> AccessLogData {
+        AccessLogData {
+            log_type: self.log_type(),
+            accesses: self.accesses().iter().map(Access::to_data).collect(),
+            brackets: self.brackets().iter().map(BracketNote::to_data).collect(),
+            notes: self.notes(),
+        }
+    }
+}
+
+impl AsRef<cm_access_log> for AccessLog {
+    fn as_ref(&self) -> &cm_access_log {
+        unsafe { &*self.0 }
+    }
+}
+
+/// Bracket note owned by Rust
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BracketNoteData {
+    /// Bracket type
+    #[serde(rename = "type")]
+    pub kind: BracketType,
+    /// Index of the access the bracket points to
+    pub r#where: u64,
+    /// Note text
+    pub text: String,
+}
+
+/// Record of an access to the machine state owned by Rust
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessData {
+    /// Type of access
+    #[serde(rename = "type")]
+    pub access_type: AccessType,
+    /// Address of access
+    pub address: u64,
+    /// Log2 of size of access
+    pub log2_size: i32,
+    /// Hash of data before access
+    pub read_hash: Hash,
+    /// Data before access, absent when too large for the log type
+    #[serde(
+        rename = "read",
+        default,
+        with = "hex_data",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub read_data: Option<Vec<u8>>,
+    /// Hash of data after access, present only when writing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written_hash: Option<Hash>,
+    /// Data after access, absent when reading or too large for the log type
+    #[serde(
+        rename = "written",
+        default,
+        with = "hex_data",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub written_data: Option<Vec<u8>>,
+    /// Sibling hashes towards root, present only when the log includes proofs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sibling_hashes: Option<Vec<Hash>>,
+}
+
+/// Log of state accesses owned by Rust
+///
+/// Serializes to the JSON layout `cartesi-machine --log-uarch-step
+/// --json-steps=<file>` dumps for each step: accesses carry `type`,
+/// `address`, `log2_size`, `read_hash`, `read`, `written_hash`, `written`
+/// and `sibling_hashes`, with hashes and data as `0x`-prefixed hex strings.
+/// Fields the log type leaves out are omitted rather than null.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessLogData {
+    /// Type of access log
+    pub log_type: AccessLogType,
+    /// Accesses in the order they happened
+    pub accesses: Vec<AccessData>,
+    /// Begin and end of annotated scopes
+    #[serde(default)]
+    pub brackets: Vec<BracketNoteData>,
+    /// Annotation of each access
+    #[serde(default)]
+    pub notes: Vec<String>,
+}
+
+impl AccessLogData {
+    /// Encodes the log as JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Decodes a log from JSON
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Optional byte strings as `0x`-prefixed hex
+mod hex_data {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        data: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match data {
+            Some(data) => serializer.serialize_str(&format!("0x{}", hex::encode(data))),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(string) => hex::decode(string.trim_start_matches("0x"))
+                .map(Some)
+                .map_err(D::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+/// An access log laid out in memory owned by Rust, viewable as a `cm_access_log`.
+pub struct OwnedAccessLog {
+    log: cm_access_log,
+    _accesses: Vec<cm_access>,
+    _sibling_arrays: Vec<cm_hash_array>,
+    _sibling_hashes: Vec<Vec<cm_hash>>,
+    _data: Vec<Vec<u8>>,
+    _brackets: Vec<cm_bracket_note>,
+    _texts: Vec<CString>,
+    _notes: Vec<*const c_char>,
+}
+
+impl AsRef<cm_access_log> for OwnedAccessLog {
+    fn as_ref(&self) -> &cm_access_log {
+        &self.log
+    }
+}
+
+impl TryFrom<&AccessLogData> for OwnedAccessLog {
+    type Error = AccessLogError;
+
+    /// Lays out the log, failing if a bracket text or note contains a NUL byte
+    fn try_from(log: &AccessLogData) -> Result<Self, AccessLogError> {
+        // Every buffer is filled before any pointer into it is taken, so the
+        // pointers stay valid for as long as the buffers are kept alive here.
+        let mut sibling_hashes = log
+            .accesses
+            .iter()
+            .map(|access| {
+                let hashes = access.sibling_hashes.as_deref().unwrap_or_default();
+                hashes.iter().map(to_cm_hash).collect::<Vec<cm_hash>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut data = log
+            .accesses
+            .iter()
+            .flat_map(|access| [access.read_data.clone(), access.written_data.clone()])
+            .map(Option::unwrap_or_default)
+            .collect::<Vec<_>>();
+
+        let mut sibling_arrays = sibling_hashes
+            .iter_mut()
+            .map(|hashes| cm_hash_array {
+                entry: hashes.as_mut_ptr(),
+                count: hashes.len(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut accesses = Vec::with_capacity(log.accesses.len());
+
+        for (index, access) in log.accesses.iter().enumerate() {
+            let (read, written) = data[2 * index..].split_at_mut(1);
+            let (read, written) = (&mut read[0], &mut written[0]);
+
+            accesses.push(cm_access {
+                type_: access.access_type as cartesi_machine_sys::CM_ACCESS_TYPE,
+                address: access.address,
+                log2_size: access.log2_size,
+                read_hash: to_cm_hash(&access.read_hash),
+                read_data: data_ptr(read),
+                read_data_size: read.len(),
+                written_hash: access
+                    .written_hash
+                    .as_ref()
+                    .map(to_cm_hash)
+                    .unwrap_or_default(),
+                written_data: data_ptr(written),
+                written_data_size: written.len(),
+                sibling_hashes: match access.sibling_hashes {
+                    Some(_) => &mut sibling_arrays[index],
+                    None => std::ptr::null_mut(),
+                },
+            });
+        }
+
+        let texts = log
+            .brackets
+            .iter()
+            .enumerate()
+            .map(|(index, bracket)| (format!("brackets[{}].text", index), &bracket.text))
+            .chain(
+                log.notes
+                    .iter()
+                    .enumerate()
+                    .map(|(index, note)| (format!("notes[{}]", index), note)),
+            )
+            .map(|(path, text)| {
+                CString::new(text.as_str()).map_err(|error| AccessLogError::NulByte {
+                    path,
+                    position: error.nul_position(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut brackets = log
+            .brackets
+            .iter()
+            .zip(&texts)
+            .map(|(bracket, text)| cm_bracket_note {
+                type_: bracket.kind as cartesi_machine_sys::CM_BRACKET_TYPE,
+                where_: bracket.r#where,
+                text: text.as_ptr() as *mut c_char,
+            })
+            .collect::<Vec<_>>();
+
+        let mut notes = texts[log.brackets.len()..]
+            .iter()
+            .map(|note| note.as_ptr())
+            .collect::<Vec<_>>();
+
+        let log = cm_access_log {
+            accesses: cartesi_machine_sys::cm_access_array {
+                entry: accesses.as_mut_ptr(),
+                count: accesses.len(),
+            },
+            brackets: cartesi_machine_sys::cm_bracket_note_array {
+                entry: brackets.as_mut_ptr(),
+                count: brackets.len(),
+            },
+            notes: cartesi_machine_sys::cm_note_array {
+                entry: notes.as_mut_ptr(),
+                count: notes.len(),
+            },
+            log_type: log.log_type.into(),
+        };
+
+        Ok(Self {
+            log,
+            _accesses: accesses,
+            _sibling_arrays: sibling_arrays,
+            _sibling_hashes: sibling_hashes,
+            _data: data,
+            _brackets: brackets,
+            _texts: texts,
+            _notes: notes,
+        })
+    }
+}
+
+fn to_cm_hash(hash: &Hash) -> cm_hash {
+    hash.as_bytes().try_into().unwrap()
+}
+
+fn data_ptr(data: &mut [u8]) -> *mut u8 {
+    if data.is_empty() {
+        std::ptr::null_mut()
+    } else {
+        data.as_mut_ptr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle;
+    use crate::replay::UarchLayout;
+    use crate::testing::{addi_step, leaked_bytes};
+
+    /// Copies a log laid out in C memory back into Rust
+    fn copy_out(log: &cm_access_log) -> AccessLogData {
+        let accesses = unsafe { data_slice(log.accesses.entry, log.accesses.count) };
+        let brackets = unsafe { data_slice(log.brackets.entry, log.brackets.count) };
+        let notes = unsafe { data_slice(log.notes.entry, log.notes.count) };
+
+        AccessLogData {
+            log_type: log.log_type.into(),
+            accesses: accesses
+                .iter()
+                .map(|access| Access::new(access).to_data())
+                .collect(),
+            brackets: brackets
+                .iter()
+                .map(|bracket| BracketNote::new(bracket).to_data())
+                .collect(),
+            notes: notes
+                .iter()
+                .map(|note| ffi::from_cstr(*note).unwrap())
+                .collect(),
+        }
+    }
+
+    /// The `addi` step, optionally without proofs, with an annotated scope and
+    /// a page-sized read that keeps its data only in large-data logs
+    fn log(proofs: bool, large_data: bool) -> AccessLogData {
+        let (_, mut log, _) = addi_step(&UarchLayout::default());
+        let page = (0..4096).map(|i| i as u8).collect::<Vec<_>>();
+
+        log.accesses.push(AccessData {
+            access_type: AccessType::Read,
+            address: 0x80000000,
+            log2_size: 12,
+            read_hash: merkle::hash_data(&page),
+            read_data: large_data.then_some(page),
+            written_hash: None,
+            written_data: None,
+            sibling_hashes: Some(vec![merkle::keccak(b"sibling"); 52]),
+        });
+
+        if !proofs {
+            for access in &mut log.accesses {
+                access.sibling_hashes = None;
+            }
+        }
+
+        log.log_type = AccessLogType {
+            proofs,
+            annotations: true,
+            large_data,
+        };
+        log.brackets = vec![
+            BracketNoteData {
+                kind: BracketType::Begin,
+                r#where: 0,
+                text: "step".to_string(),
+            },
+            BracketNoteData {
+                kind: BracketType::End,
+                r#where: 9,
+                text: "step".to_string(),
+            },
+        ];
+        log.notes = (0..9).map(|i| format!("access {}", i)).collect();
+        log
+    }
+
+    fn hash(byte: u8) -> Hash {
+        Hash::new([byte; 32])
+    }
+
+    fn hex(byte: u8, length: usize) -> String {
+        format!("0x{}", format!("{:02x}", byte).repeat(length))
+    }
+
+    /// A read without data next to a logged write with proofs
+    fn small_log() -> AccessLogData {
+        AccessLogData {
+            log_type: AccessLogType {
+                proofs: true,
+                annotations: true,
+                large_data: false,
+            },
+            accesses: vec![
+                AccessData {
+                    access_type: AccessType::Read,
+                    address: 0x1000,
+                    log2_size: 12,
+                    read_hash: hash(0xaa),
+                    read_data: None,
+                    written_hash: None,
+                    written_data: None,
+                    sibling_hashes: None,
+                },
+                AccessData {
+                    access_type: AccessType::Write,
+                    address: 0x400008,
+                    log2_size: 3,
+                    read_hash: hash(0x01),
+                    read_data: Some(vec![0, 1, 2, 3, 4, 5, 6, 7]),
+                    written_hash: Some(hash(0x02)),
+                    written_data: Some(vec![0xff; 8]),
+                    sibling_hashes: Some(vec![hash(0x03), hash(0x04)]),
+                },
+            ],
+            brackets: vec![BracketNoteData {
+                kind: BracketType::Begin,
+                r#where: 1,
+                text: "write".to_string(),
+            }],
+            notes: vec!["page".to_string(), "x1".to_string()],
+        }
+    }
+
+    #[test]
+    fn json_layout_is_pinned() {
+        let json: serde_json::Value = serde_json::from_str(&small_log().to_json()).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "log_type": { "proofs": true, "annotations": true, "large_data": false },
+                "accesses": [
+                    {
+                        "type": "read",
+                        "address": 0x1000,
+                        "log2_size": 12,
+                        "read_hash": hex(0xaa, 32),
+                    },
+                    {
+                        "type": "write",
+                        "address": 0x400008,
+                        "log2_size": 3,
+                        "read_hash": hex(0x01, 32),
+                        "read": "0x0001020304050607",
+                        "written_hash": hex(0x02, 32),
+                        "written": "0xffffffffffffffff",
+                        "sibling_hashes": [hex(0x03, 32), hex(0x04, 32)],
+                    },
+                ],
+                "brackets": [{ "type": "begin", "where": 1, "text": "write" }],
+                "notes": ["page", "x1"],
+            })
+        );
+    }
+
+    #[test]
+    fn json_round_trips() {
+        for log in [small_log(), log(true, true), log(false, false)] {
+            assert_eq!(AccessLogData::from_json(&log.to_json()).unwrap(), log);
+        }
+
+        // Brackets and notes may be left out entirely
+        let mut log = small_log();
+        log.brackets.clear();
+        log.notes.clear();
+        let mut json = serde_json::to_value(&log).unwrap();
+        json.as_object_mut().unwrap().remove("brackets");
+        json.as_object_mut().unwrap().remove("notes");
+        assert_eq!(AccessLogData::from_json(&json.to_string()).unwrap(), log);
+    }
+
+    #[test]
+    fn json_rejects_malformed_values() {
+        let json = serde_json::to_value(small_log()).unwrap();
+
+        for (pointer, value) in [
+            ("/accesses/1/read", "0x0g"),
+            ("/accesses/1/written", "0x123"),
+            ("/accesses/0/read_hash", "0x00"),
+            ("/accesses/1/written_hash", &hex(0x02, 33)),
+            ("/accesses/1/sibling_hashes/0", "zz"),
+            ("/accesses/0/type", "execute"),
+        ] {
+            let mut json = json.clone();
+            *json.pointer_mut(pointer).unwrap() = value.into();
+
+            assert!(
+                AccessLogData::from_json(&json.to_string()).is_err(),
+                "{} = {}",
+                pointer,
+                value
+            );
+        }
+
+        let error = AccessLogData::from_json(
+            &serde_json::json!({
+                "log_type": { "proofs": false, "annotations": false, "large_data": false },
+                "accesses": [{ "type": "read", "address": 0, "log2_size": 3, "read_hash": "0x00" }],
+            })
+            .to_string(),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("hash must be 32 bytes long"));
+    }
+
+    #[test]
+    fn owned_log_round_trips() {
+        for proofs in [false, true] {
+            for large_data in [false, true] {
+                let log = log(proofs, large_data);
+                let owned = OwnedAccessLog::try_from(&log).unwrap();
+
+                assert_eq!(
+                    copy_out(owned.as_ref()),
+                    log,
+                    "proofs {} large_data {}",
+                    proofs,
+                    large_data
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn owned_log_outlives_moves() {
+        let log = log(true, true);
+        let owned = OwnedAccessLog::try_from(&log).unwrap();
+        let owned = Box::new(owned);
+        let owned = vec![*owned].pop().unwrap();
+
+        assert_eq!(copy_out(owned.as_ref()), log);
+    }
+
+    #[test]
+    fn owned_log_frees_everything() {
+        let log = log(true, true);
+
+        let leaked = leaked_bytes(|| drop(OwnedAccessLog::try_from(&log).unwrap()));
+
+        assert_eq!(leaked, 0);
+    }
+
+    #[test]
+    fn owned_log_rejects_nul_bytes() {
+        let mut with_bracket = log(true, false);
+        with_bracket.brackets[1].text = "st\0ep".to_string();
+        let mut with_note = log(true, false);
+        with_note.notes[4] = "\0".to_string();
+
+        let leaked = leaked_bytes(|| {
+            assert_eq!(
+                OwnedAccessLog::try_from(&with_bracket).err(),
+                Some(AccessLogError::NulByte {
+                    path: "brackets[1].text".to_string(),
+                    position: 2
+                })
+            );
+            assert_eq!(
+                OwnedAccessLog::try_from(&with_note).err(),
+                Some(AccessLogError::NulByte {
+                    path: "notes[4]".to_string(),
+                    position: 0
+                })
+            );
+        });
+        assert_eq!(leaked, 0);
+    }
+}
@@ -0,0 +1,299 @@
+//! Pure-Rust Merkle tree hashing compatible with the machine state tree.
+
+use sha3::{Digest, Keccak256};
+
+use crate::errors::ProofError;
+use crate::hash::Hash;
+
+/// Log2 of the size of a tree leaf (a machine word)
+pub const LOG2_WORD_SIZE: usize = cartesi_machine_sys::CM_TREE_LOG2_WORD_SIZE as usize;
+/// Log2 of the size of a memory page
+pub const LOG2_PAGE_SIZE: usize = cartesi_machine_sys::CM_TREE_LOG2_PAGE_SIZE as usize;
+/// Log2 of the size of the whole address space
+pub const LOG2_ROOT_SIZE: usize = cartesi_machine_sys::CM_TREE_LOG2_ROOT_SIZE as usize;
+
+/// Hashes a byte string with Keccak-256.
+pub fn keccak(data: &[u8]) -> Hash {
+    Hash::new(Keccak256::digest(data).into())
+}
+
+/// Hashes the concatenation of two child nodes into their parent node.
+pub fn hash_children(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Keccak256::new();
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    Hash::new(hasher.finalize().into())
+}
+
+/// Computes the hash of the Merkle subtree spanning `data`.
+///
+/// The length of `data` must be a power of two no smaller than a word.
+pub fn hash_data(data: &[u8]) -> Hash {
+    assert!(
+        data.len().is_power_of_two() && data.len() >= 1 << LOG2_WORD_SIZE,
+        "data length must be a power of two no smaller than a word"
+    );
+
+    if data.len() == 1 << LOG2_WORD_SIZE {
+        keccak(data)
+    } else {
+        let (left, right) = data.split_at(data.len() / 2);
+        hash_children(&hash_data(left), &hash_data(right))
+    }
+}
+
+/// Computes the root hash implied by a target node and its sibling hashes.
+///
+/// Sibling hashes are ordered from the root towards the target, the same
+/// order used by the emulator in `cm_merkle_tree_proof` and `cm_access`.
+pub fn root_from_siblings(
+    target_address: u64,
+    log2_target_size: usize,
+    target_hash: &Hash,
+    sibling_hashes: &[Hash],
+) -> Hash {
+    let log2_root_size = log2_target_size + sibling_hashes.len();
+    let mut hash = target_hash.clone();
+
+    for log2_size in log2_target_size..log2_root_size {
+        let sibling = &sibling_hashes[log2_root_size - 1 - log2_size];
+
+        hash = if bit(target_address, log2_size) {
+            hash_children(sibling, &hash)
+        } else {
+            hash_children(&hash, sibling)
+        };
+    }
+
+    hash
+}
+
+fn bit(address: u64, log2_size: usize) -> bool {
+    log2_size < 64 && address & (1 << log2_size) != 0
+}
+
+/// Aligned Merkle subtree that is part of a range decomposition
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subtree {
+    /// Address of the subtree
+    pub address: u64,
+    /// Log2 of the size of the subtree
+    pub log2_size: usize,
+    /// Hash of the subtree
+    pub hash: Hash,
+}
+
+/// Commitment to an arbitrary memory range
+///
+/// The range is split into the largest aligned subtrees that fit, from the
+/// lowest address up. The commitment is the Keccak-256 hash of the range start
+/// and length (as big-endian 64-bit integers) followed by the subtree hashes in
+/// address order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeHash {
+    /// Address of the first byte in the range
+    pub start: u64,
+    /// Number of bytes in the range
+    pub length: u64,
+    /// Aligned subtrees covering the range, in address order
+    pub subtrees: Vec<Subtree>,
+    /// Commitment to the whole range
+    pub commitment: Hash,
+}
+
+impl RangeHash {
+    /// Builds the commitment from the hashes of the subtrees of a range
+    pub fn new(start: u64, length: u64, subtrees: Vec<Subtree>) -> Self {
+        let mut hasher = Keccak256::new();
+        hasher.update(start.to_be_bytes());
+        hasher.update(length.to_be_bytes());
+
+        for subtree in &subtrees {
+            hasher.update(subtree.hash.as_bytes());
+        }
+
+        Self {
+            start,
+            length,
+            subtrees,
+            commitment: Hash::new(hasher.finalize().into()),
+        }
+    }
+}
+
+/// Splits `[start, start + length)` into the largest aligned subtrees that fit.
+///
+/// Both `start` and `length` must be multiples of the word size. The range may
+/// end at the very top of the address space.
+pub fn split_range(start: u64, length: u64) -> Result<Vec<(u64, usize)>, ProofError> {
+    let word_mask = (1 << LOG2_WORD_SIZE) - 1;
+
+    if start & word_mask != 0 || length & word_mask != 0 {
+        return Err(ProofError::UnalignedRange { start, length });
+    }
+    if length != 0 && start.checked_add(length - 1).is_none() {
+        return Err(ProofError::RangeOverflow { start, length });
+    }
+
+    let mut subtrees = Vec::new();
+    let mut address = start;
+    let mut remaining = length;
+
+    while remaining > 0 {
+        let log2_alignment = address.trailing_zeros() as usize;
+        let log2_remaining = (63 - remaining.leading_zeros()) as usize;
+        let log2_size = log2_alignment.min(log2_remaining);
+
+        subtrees.push((address, log2_size));
+        remaining -= 1 << log2_size;
+        address = address.wrapping_add(1 << log2_size);
+    }
+
+    Ok(subtrees)
+}
+
+/// Computes the range commitment of `data` placed at address `start`.
+pub fn hash_range(start: u64, data: &[u8]) -> Result<RangeHash, ProofError> {
+    let subtrees = split_range(start, data.len() as u64)?
+        .into_iter()
+        .map(|(address, log2_size)| {
+            let offset = (address - start) as usize;
+            Subtree {
+                address,
+                log2_size,
+                hash: hash_data(&data[offset..offset + (1 << log2_size)]),
+            }
+        })
+        .collect();
+
+    Ok(RangeHash::new(start, data.len() as u64, subtrees))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SparseTree;
+
+    #[test]
+    fn splits_unaligned_starts() {
+        assert_eq!(
+            split_range(0x1008, 0x1000).unwrap(),
+            [
+                (0x1008, 3),
+                (0x1010, 4),
+                (0x1020, 5),
+                (0x1040, 6),
+                (0x1080, 7),
+                (0x1100, 8),
+                (0x1200, 9),
+                (0x1400, 10),
+                (0x1800, 11),
+                (0x2000, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_lengths_that_are_not_powers_of_two() {
+        assert_eq!(
+            split_range(0x4000, 0x1818).unwrap(),
+            [(0x4000, 12), (0x5000, 11), (0x5800, 4), (0x5810, 3)]
+        );
+        assert_eq!(split_range(0x4000, 0x1000).unwrap(), [(0x4000, 12)]);
+        assert!(split_range(0x4000, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn splits_from_address_zero() {
+        assert_eq!(split_range(0, 0x18).unwrap(), [(0, 4), (0x10, 3)]);
+        assert_eq!(split_range(0, 1 << 63).unwrap(), [(0, 63)]);
+        assert_eq!(
+            split_range(0, u64::MAX - 7).unwrap().len(),
+            61,
+            "one subtree per set bit of the length"
+        );
+    }
+
+    #[test]
+    fn splits_ranges_ending_at_top_of_address_space() {
+        assert_eq!(split_range(u64::MAX - 7, 8).unwrap(), [(u64::MAX - 7, 3)]);
+        assert_eq!(
+            split_range(0xffff_ffff_ffff_f000, 0x1000).unwrap(),
+            [(0xffff_ffff_ffff_f000, 12)]
+        );
+        assert_eq!(split_range(1 << 63, 1 << 63).unwrap(), [(1 << 63, 63)]);
+        assert_eq!(
+            split_range(u64::MAX - 0x17, 0x18).unwrap(),
+            [(u64::MAX - 0x17, 3), (u64::MAX - 0xf, 4)]
+        );
+    }
+
+    #[test]
+    fn rejects_unaligned_and_overflowing_ranges() {
+        assert!(matches!(
+            split_range(0x1004, 8),
+            Err(ProofError::UnalignedRange {
+                start: 0x1004,
+                length: 8
+            })
+        ));
+        assert!(matches!(
+            split_range(0x1000, 12),
+            Err(ProofError::UnalignedRange { .. })
+        ));
+        assert!(matches!(
+            split_range(u64::MAX - 7, 16),
+            Err(ProofError::RangeOverflow {
+                start: 0xfffffffffffffff8,
+                length: 16
+            })
+        ));
+        assert!(matches!(
+            split_range(16, u64::MAX - 7),
+            Err(ProofError::RangeOverflow { .. })
+        ));
+    }
+
+    #[test]
+    fn range_hash_matches_tree() {
+        let mut tree = SparseTree::default();
+        let start = 0x1008;
+        let data = (0..0x218u64)
+            .flat_map(|i| (i * 0x01010101 + 1).to_le_bytes())
+            .collect::<Vec<u8>>();
+        for (i, word) in data.chunks(8).enumerate() {
+            tree.write(
+                start + 8 * i as u64,
+                u64::from_le_bytes(word.try_into().unwrap()),
+            );
+        }
+
+        let range = hash_range(start, &data).unwrap();
+
+        assert_eq!(range.start, start);
+        assert_eq!(range.length, data.len() as u64);
+        assert_eq!(
+            range
+                .subtrees
+                .iter()
+                .map(|subtree| (subtree.address, subtree.log2_size))
+                .collect::<Vec<_>>(),
+            split_range(start, data.len() as u64).unwrap()
+        );
+        for subtree in &range.subtrees {
+            assert_eq!(subtree.hash, tree.node(subtree.address, subtree.log2_size));
+        }
+
+        // The commitment binds the range bounds as well as the contents
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&start.to_be_bytes());
+        bytes.extend_from_slice(&(data.len() as u64).to_be_bytes());
+        for subtree in &range.subtrees {
+            bytes.extend_from_slice(subtree.hash.as_bytes());
+        }
+        assert_eq!(range.commitment, keccak(&bytes));
+
+        let shifted = hash_range(start + 8, &data[..data.len() - 8]).unwrap();
+        assert_ne!(shifted.commitment, range.commitment);
+    }
+}
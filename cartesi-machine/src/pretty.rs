@@ -0,0 +1,299 @@
+//! Human-readable rendering of access logs.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use crate::errors::MachineError;
+use crate::hash::Hash;
+use crate::log::{AccessData, AccessLogData, AccessType, BracketType};
+use crate::{merkle, Machine, CSR};
+
+/// Resolves machine addresses to the names of the state they hold
+#[derive(Clone, Debug, Default)]
+pub struct StateNames {
+    words: BTreeMap<u64, String>,
+    ranges: Vec<(u64, u64, String)>,
+}
+
+impl StateNames {
+    /// Collects the names of the registers and memory ranges of a machine
+    pub fn from_machine(machine: &mut Machine) -> Result<Self, MachineError> {
+        let mut names = Self::default();
+
+        for csr in CSR::ALL {
+            names.insert_word(machine.get_csr_address(csr), csr.name());
+        }
+
+        for i in 0..32 {
+            names.insert_word(machine.get_x_address(i), format!("x{}", i));
+            names.insert_word(machine.get_f_address(i), format!("f{}", i));
+            names.insert_word(machine.get_uarch_x_address(i), format!("uarch.x{}", i));
+        }
+
+        for range in machine.get_memory_ranges()? {
+            let name = range.description.to_lowercase().replace(' ', "_");
+            names.insert_range(range.start, range.length, name);
+        }
+
+        Ok(names)
+    }
+
+    /// Names the word at `address`
+    pub fn insert_word(&mut self, address: u64, name: impl Into<String>) {
+        self.words.insert(address, name.into());
+    }
+
+    /// Names the memory range `[start, start + length)`
+    pub fn insert_range(&mut self, start: u64, length: u64, name: impl Into<String>) {
+        self.ranges.push((start, length, name.into()));
+    }
+
+    /// Name of the state at `address`, falling back to the address in hex
+    pub fn resolve(&self, address: u64) -> String {
+        if let Some(name) = self.words.get(&address) {
+            return name.clone();
+        }
+
+        self.ranges
+            .iter()
+            .find(|(start, length, _)| address >= *start && address - start < *length)
+            .map(|(start, _, name)| match address - start {
+                0 => name.clone(),
+                offset => format!("{}+0x{:x}", name, offset),
+            })
+            .unwrap_or_else(|| format!("0x{:x}", address))
+    }
+}
+
+impl AccessLogData {
+    /// Renders the log with accesses nested in their brackets
+    pub fn display<'a>(&'a self, names: &'a StateNames) -> PrettyAccessLog<'a> {
+        PrettyAccessLog { log: self, names }
+    }
+}
+
+/// Human-readable view of an access log, created by [`AccessLogData::display`]
+pub struct PrettyAccessLog<'a> {
+    log: &'a AccessLogData,
+    names: &'a StateNames,
+}
+
+impl<'a> PrettyAccessLog<'a> {
+    fn write_brackets(
+        &self,
+        f: &mut Formatter<'_>,
+        index: usize,
+        depth: &mut usize,
+    ) -> fmt::Result {
+        for bracket in self
+            .log
+            .brackets
+            .iter()
+            .filter(|b| b.r#where == index as u64)
+        {
+            match bracket.kind {
+                BracketType::Begin => {
+                    writeln!(f, "{}begin {}", indent(*depth), bracket.text)?;
+                    *depth += 1;
+                }
+                BracketType::End => {
+                    *depth = depth.saturating_sub(1);
+                    writeln!(f, "{}end {}", indent(*depth), bracket.text)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_access(&self, f: &mut Formatter<'_>, access: &AccessData) -> fmt::Result {
+        let kind = match access.access_type {
+            AccessType::Read => "read",
+            AccessType::Write => "write",
+        };
+
+        write!(f, "{} {}", kind, self.names.resolve(access.address))?;
+
+        if access.log2_size as usize != merkle::LOG2_WORD_SIZE {
+            write!(f, " (2^{} bytes)", access.log2_size)?;
+        }
+
+        write!(
+            f,
+            ": {}",
+            value(access.read_data.as_deref(), Some(&access.read_hash))
+        )?;
+
+        if access.access_type == AccessType::Write {
+            write!(
+                f,
+                " -> {}",
+                value(access.written_data.as_deref(), access.written_hash.as_ref())
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Display for PrettyAccessLog<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut depth = 0;
+
+        for (index, access) in self.log.accesses.iter().enumerate() {
+            self.write_brackets(f, index, &mut depth)?;
+
+            write!(f, "{}{}: ", indent(depth), index)?;
+            self.write_access(f, access)?;
+
+            match self.log.notes.get(index) {
+                Some(note) if !note.is_empty() => writeln!(f, "  # {}", note)?,
+                _ => writeln!(f)?,
+            }
+        }
+
+        self.write_brackets(f, self.log.accesses.len(), &mut depth)
+    }
+}
+
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth)
+}
+
+/// Renders a word as an integer and anything larger as an abbreviated hash
+pub(crate) fn value(data: Option<&[u8]>, hash: Option<&Hash>) -> String {
+    match (data, hash) {
+        (Some(data), _) if data.len() == 1 << merkle::LOG2_WORD_SIZE => {
+            format!("0x{:016x}", u64::from_le_bytes(data.try_into().unwrap()))
+        }
+        (_, Some(hash)) => format!("hash 0x{}…", hex::encode(&hash.as_bytes()[..4])),
+        _ => "?".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::BracketNoteData;
+    use crate::replay::UarchLayout;
+    use crate::testing::{addi_step, PC};
+
+    /// Names of the 0.15 layout, inserted the way [`StateNames::from_machine`] does
+    fn names(layout: &UarchLayout) -> StateNames {
+        let mut names = StateNames::default();
+        names.insert_word(0x200, CSR::Pc.name());
+        names.insert_word(layout.pc, CSR::UarchPc.name());
+        names.insert_word(layout.cycle, CSR::UarchCycle.name());
+        names.insert_word(layout.halt_flag, CSR::UarchHaltFlag.name());
+        for i in 0..32 {
+            names.insert_word(8 * i, format!("x{}", i));
+            names.insert_word(layout.x[i as usize], format!("uarch.x{}", i));
+        }
+        names.insert_range(0x0, 0x1000, "shadow_state");
+        names.insert_range(0x400000, 0x1000, "shadow_uarch_state");
+        names.insert_range(PC, 0x200000, "uarch_ram");
+        names
+    }
+
+    #[test]
+    fn resolves_registers_before_ranges() {
+        let layout = UarchLayout::default();
+        let names = names(&layout);
+
+        assert_eq!(names.resolve(0x0), "x0");
+        assert_eq!(names.resolve(0x28), "x5");
+        assert_eq!(names.resolve(0xf8), "x31");
+        assert_eq!(names.resolve(0x200), "pc");
+        assert_eq!(names.resolve(layout.halt_flag), "uarch.halt_flag");
+        assert_eq!(names.resolve(layout.cycle), "uarch.cycle");
+        assert_eq!(names.resolve(layout.pc), "uarch.pc");
+        assert_eq!(names.resolve(layout.x[0]), "uarch.x0");
+        assert_eq!(names.resolve(layout.x[31]), "uarch.x31");
+    }
+
+    #[test]
+    fn resolves_unnamed_words_to_range_offsets() {
+        let layout = UarchLayout::default();
+        let names = names(&layout);
+
+        assert_eq!(names.resolve(0x300), "shadow_state+0x300");
+        assert_eq!(names.resolve(layout.x[31] + 8), "shadow_uarch_state+0x118");
+        assert_eq!(names.resolve(PC), "uarch_ram");
+        assert_eq!(names.resolve(PC + 0x1ffff8), "uarch_ram+0x1ffff8");
+    }
+
+    #[test]
+    fn resolves_unknown_addresses_to_hex() {
+        let names = names(&UarchLayout::default());
+
+        assert_eq!(names.resolve(0x1000), "0x1000");
+        assert_eq!(names.resolve(PC + 0x200000), "0x800000");
+        assert_eq!(names.resolve(u64::MAX), "0xffffffffffffffff");
+        assert_eq!(StateNames::default().resolve(0x28), "0x28");
+    }
+
+    #[test]
+    fn renders_addi_step() {
+        let layout = UarchLayout::default();
+        let (_, mut log, _) = addi_step(&layout);
+        log.brackets = vec![
+            BracketNoteData {
+                kind: BracketType::Begin,
+                r#where: 0,
+                text: "step".to_string(),
+            },
+            BracketNoteData {
+                kind: BracketType::Begin,
+                r#where: 5,
+                text: "retire".to_string(),
+            },
+            BracketNoteData {
+                kind: BracketType::End,
+                r#where: 8,
+                text: "retire".to_string(),
+            },
+            BracketNoteData {
+                kind: BracketType::End,
+                r#where: 8,
+                text: "step".to_string(),
+            },
+        ];
+        log.notes = ["cycle", "", "pc", "insn", "x6", "x5", "pc", "cycle"]
+            .map(String::from)
+            .to_vec();
+
+        assert_eq!(
+            log.display(&names(&layout)).to_string(),
+            "\
+begin step
+  0: read uarch.cycle: 0x0000000000000005  # cycle
+  1: read uarch.halt_flag: 0x0000000000000000
+  2: read uarch.pc: 0x0000000000600000  # pc
+  3: read uarch_ram: 0x0000000000730293  # insn
+  4: read uarch.x6: 0x0000000000000023  # x6
+  begin retire
+    5: write uarch.x5: 0x0000000000000000 -> 0x000000000000002a  # x5
+    6: write uarch.pc: 0x0000000000600000 -> 0x0000000000600004  # pc
+    7: write uarch.cycle: 0x0000000000000005 -> 0x0000000000000006  # cycle
+  end retire
+end step
+"
+        );
+    }
+
+    #[test]
+    fn renders_large_and_unknown_accesses() {
+        let layout = UarchLayout::default();
+        let (_, mut log, _) = addi_step(&layout);
+        log.accesses.truncate(1);
+        log.accesses[0].address = 0x80000000;
+        log.accesses[0].log2_size = 12;
+        log.accesses[0].read_data = None;
+
+        let hash = hex::encode(&log.accesses[0].read_hash.as_bytes()[..4]);
+        assert_eq!(
+            log.display(&StateNames::default()).to_string(),
+            format!("0: read 0x80000000 (2^12 bytes): hash 0x{}…\n", hash)
+        );
+    }
+}
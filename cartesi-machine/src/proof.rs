@@ -1,6 +1,10 @@
 //! Structures for merkle proofs
 
+use std::collections::BTreeMap;
+
+use crate::errors::ProofError;
 use crate::hash::Hash;
+use crate::merkle;
 
 /// Merkle tree proof structure
 pub struct MerkleTreeProof(*mut cartesi_machine_sys::cm_merkle_tree_proof);
@@ -48,4 +52,747 @@ impl MerkleTreeProof {
 
         sibling_hashes.iter().map(|hash| Hash::new(*hash)).collect()
     }
-}
\ No newline at end of file
+
+    /// Copies the proof out of the emulator
+    pub fn to_data(&self) -> MerkleTreeProofData {
+        MerkleTreeProofData {
+            target_address: self.target_address(),
+            log2_target_size: self.log2_target_size(),
+            target_hash: self.target_hash(),
+            log2_root_size: self.log2_root_size(),
+            root_hash: self.root_hash(),
+            sibling_hashes: self.sibling_hashes(),
+        }
+    }
+}
+
+/// Merkle tree proof owned by Rust
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTreeProofData {
+    /// Address of the target node
+    pub target_address: u64,
+    /// Log2 of size of target node
+    pub log2_target_size: usize,
+    /// Hash of target node
+    pub target_hash: Hash,
+    /// Log2 of size of root node
+    pub log2_root_size: usize,
+    /// Hash of root node
+    pub root_hash: Hash,
+    /// Sibling hashes towards root, starting next to the root
+    pub sibling_hashes: Vec<Hash>,
+}
+
+impl MerkleTreeProofData {
+    /// Recomputes the root hash from the target hash and the sibling hashes
+    pub fn compute_root_hash(&self) -> Hash {
+        merkle::root_from_siblings(
+            self.target_address,
+            self.log2_target_size,
+            &self.target_hash,
+            &self.sibling_hashes,
+        )
+    }
+
+    /// Checks that the proof is well formed and leads to its root hash
+    ///
+    /// The target address must be aligned to the target size and lie inside
+    /// the tree, since the root hash only depends on the address bits between
+    /// the two sizes.
+    pub fn verify(&self) -> bool {
+        self.log2_target_size >= merkle::LOG2_WORD_SIZE
+            && self.log2_root_size <= merkle::LOG2_ROOT_SIZE
+            && self.log2_target_size.checked_add(self.sibling_hashes.len())
+                == Some(self.log2_root_size)
+            && is_aligned(self.target_address, self.log2_target_size)
+            && is_in_tree(self.target_address, self.log2_root_size)
+            && self.compute_root_hash() == self.root_hash
+    }
+}
+
+/// Whether `address` is the start of a node of size `2^log2_size`
+fn is_aligned(address: u64, log2_size: usize) -> bool {
+    log2_size >= 64 || address & ((1 << log2_size) - 1) == 0
+}
+
+/// Whether `address` lies inside a tree of size `2^log2_root_size`
+fn is_in_tree(address: u64, log2_root_size: usize) -> bool {
+    log2_root_size >= 64 || address >> log2_root_size == 0
+}
+
+/// Chunk of memory together with the proof of its contents
+#[derive(Clone, Debug)]
+pub struct AuthenticatedChunk {
+    /// Contents of the node targeted by the proof
+    pub data: Vec<u8>,
+    /// Proof for the node holding `data`
+    pub proof: MerkleTreeProofData,
+}
+
+/// Memory contents authenticated against a root hash
+#[derive(Clone, Debug)]
+pub struct AuthenticatedMemory {
+    /// Address of the first requested byte
+    pub address: u64,
+    /// Number of requested bytes
+    pub length: u64,
+    /// Root hash all proofs lead to
+    pub root_hash: Hash,
+    /// Aligned chunks covering the requested range, in address order
+    pub chunks: Vec<AuthenticatedChunk>,
+}
+
+impl AuthenticatedMemory {
+    /// Requested bytes, cut out of the covering chunks
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let end = self.address.saturating_add(self.length);
+
+        for chunk in &self.chunks {
+            let chunk_start = chunk.proof.target_address;
+            let chunk_end = chunk_start.saturating_add(chunk.data.len() as u64);
+            let from = self.address.max(chunk_start);
+            let to = end.min(chunk_end);
+
+            if from < to {
+                data.extend_from_slice(
+                    &chunk.data[(from - chunk_start) as usize..(to - chunk_start) as usize],
+                );
+            }
+        }
+
+        data
+    }
+
+    /// Checks every chunk against its proof and every proof against `root_hash`
+    pub fn verify(&self, root_hash: &Hash) -> Result<(), ProofError> {
+        if &self.root_hash != root_hash {
+            return Err(ProofError::RootHashMismatch {
+                address: self.address,
+                expected: root_hash.clone(),
+                found: self.root_hash.clone(),
+            });
+        }
+
+        let mut next_address = self.address;
+        let end = self
+            .address
+            .checked_add(self.length)
+            .ok_or(ProofError::RangeOverflow {
+                start: self.address,
+                length: self.length,
+            })?;
+
+        for chunk in &self.chunks {
+            let proof = &chunk.proof;
+            verify_chunk(&chunk.data, proof, root_hash)?;
+
+            if proof.target_address > next_address {
+                return Err(ProofError::MissingData {
+                    address: next_address,
+                });
+            }
+
+            let chunk_end = proof
+                .target_address
+                .checked_add(chunk.data.len() as u64)
+                .ok_or(ProofError::InvalidProof {
+                    address: proof.target_address,
+                    log2_size: proof.log2_target_size,
+                })?;
+            next_address = next_address.max(chunk_end);
+        }
+
+        if next_address < end {
+            return Err(ProofError::MissingData {
+                address: next_address,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that `data` is the contents of the node proven by `proof` under `root_hash`
+pub(crate) fn verify_chunk(
+    data: &[u8],
+    proof: &MerkleTreeProofData,
+    root_hash: &Hash,
+) -> Result<(), ProofError> {
+    if &proof.root_hash != root_hash {
+        return Err(ProofError::RootHashMismatch {
+            address: proof.target_address,
+            expected: root_hash.clone(),
+            found: proof.root_hash.clone(),
+        });
+    }
+
+    // The size comes from an untrusted proof, so bound it before shifting or hashing
+    if proof.log2_target_size < merkle::LOG2_WORD_SIZE || proof.log2_target_size >= 64 {
+        return Err(ProofError::InvalidProof {
+            address: proof.target_address,
+            log2_size: proof.log2_target_size,
+        });
+    }
+
+    if data.len() as u64 != 1 << proof.log2_target_size
+        || merkle::hash_data(data) != proof.target_hash
+    {
+        return Err(ProofError::TargetHashMismatch {
+            address: proof.target_address,
+            log2_size: proof.log2_target_size,
+        });
+    }
+
+    if !proof.verify() {
+        return Err(ProofError::InvalidProof {
+            address: proof.target_address,
+            log2_size: proof.log2_target_size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Node whose hash is proven by a [MultiProof]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiProofTarget {
+    /// Address of the target node
+    pub address: u64,
+    /// Log2 of size of target node
+    pub log2_size: usize,
+    /// Hash of target node
+    pub hash: Hash,
+}
+
+/// Proof for several nodes of the same tree sharing common sibling hashes
+///
+/// Sibling hashes are listed in the order a verifier needs them: level by
+/// level from the leaves up, and by address within a level. Siblings that
+/// can be computed from other targets are omitted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiProof {
+    /// Log2 of size of root node
+    pub log2_root_size: usize,
+    /// Hash of root node
+    pub root_hash: Hash,
+    /// Proven nodes, in address order
+    pub targets: Vec<MultiProofTarget>,
+    /// Sibling hashes not covered by the targets
+    pub sibling_hashes: Vec<Hash>,
+}
+
+impl MultiProof {
+    /// Merges single-node proofs of the same tree into a multiproof
+    pub fn from_proofs(proofs: &[MerkleTreeProofData]) -> Result<Self, ProofError> {
+        let first = proofs.first().ok_or(ProofError::MalformedMultiProof)?;
+
+        for proof in proofs {
+            if proof.root_hash != first.root_hash || proof.log2_root_size != first.log2_root_size {
+                return Err(ProofError::RootHashMismatch {
+                    address: proof.target_address,
+                    expected: first.root_hash.clone(),
+                    found: proof.root_hash.clone(),
+                });
+            }
+
+            if !proof.verify() {
+                return Err(ProofError::InvalidProof {
+                    address: proof.target_address,
+                    log2_size: proof.log2_target_size,
+                });
+            }
+        }
+
+        let mut proofs = proofs.iter().collect::<Vec<_>>();
+        proofs.sort_by_key(|proof| proof.target_address);
+
+        let targets = proofs
+            .iter()
+            .map(|proof| MultiProofTarget {
+                address: proof.target_address,
+                log2_size: proof.log2_target_size,
+                hash: proof.target_hash.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut sibling_hashes = Vec::new();
+        fold_multiproof(&targets, first.log2_root_size, |level, target| {
+            let proof = proofs[target];
+            let hash = proof.sibling_hashes[proof.log2_root_size - 1 - level].clone();
+            sibling_hashes.push(hash.clone());
+            Ok(hash)
+        })?;
+
+        Ok(Self {
+            log2_root_size: first.log2_root_size,
+            root_hash: first.root_hash.clone(),
+            targets,
+            sibling_hashes,
+        })
+    }
+
+    /// Recomputes the root hash from the targets and the sibling hashes
+    pub fn compute_root_hash(&self) -> Result<Hash, ProofError> {
+        let mut sibling_hashes = self.sibling_hashes.iter();
+
+        let root_hash = fold_multiproof(&self.targets, self.log2_root_size, |_, _| {
+            sibling_hashes
+                .next()
+                .cloned()
+                .ok_or(ProofError::MalformedMultiProof)
+        })?;
+
+        if sibling_hashes.next().is_some() {
+            return Err(ProofError::MalformedMultiProof);
+        }
+
+        Ok(root_hash)
+    }
+
+    /// Checks that the multiproof leads to `root_hash`
+    pub fn verify(&self, root_hash: &Hash) -> Result<(), ProofError> {
+        let found = self.compute_root_hash()?;
+
+        if &found != root_hash || &self.root_hash != root_hash {
+            return Err(ProofError::RootHashMismatch {
+                address: 0,
+                expected: root_hash.clone(),
+                found,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Hashes the targets up to the root, asking `sibling` for every missing
+/// sibling hash with its level and the index of a target below it.
+fn fold_multiproof<F>(
+    targets: &[MultiProofTarget],
+    log2_root_size: usize,
+    mut sibling: F,
+) -> Result<Hash, ProofError>
+where
+    F: FnMut(usize, usize) -> Result<Hash, ProofError>,
+{
+    let mut order = (0..targets.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| (targets[index].log2_size, targets[index].address));
+
+    for target in targets {
+        if target.log2_size < merkle::LOG2_WORD_SIZE
+            || target.log2_size >= log2_root_size
+            || !is_aligned(target.address, target.log2_size)
+            || !is_in_tree(target.address, log2_root_size)
+        {
+            return Err(ProofError::InvalidProof {
+                address: target.address,
+                log2_size: target.log2_size,
+            });
+        }
+    }
+
+    let first_level = match order.first() {
+        Some(&index) => targets[index].log2_size,
+        None => return Err(ProofError::MalformedMultiProof),
+    };
+
+    let mut pending = order.into_iter().peekable();
+    let mut nodes: BTreeMap<u64, (Hash, usize)> = BTreeMap::new();
+
+    for level in first_level..log2_root_size {
+        while let Some(&index) = pending.peek() {
+            let target = &targets[index];
+
+            if target.log2_size != level {
+                break;
+            }
+
+            if nodes
+                .insert(target.address, (target.hash.clone(), index))
+                .is_some()
+            {
+                return Err(ProofError::OverlappingTargets {
+                    address: target.address,
+                    log2_size: target.log2_size,
+                });
+            }
+
+            pending.next();
+        }
+
+        let bit = 1u64 << level;
+        let mut parents = BTreeMap::new();
+        let mut level_nodes = std::mem::take(&mut nodes).into_iter().peekable();
+
+        while let Some((address, (hash, target))) = level_nodes.next() {
+            let parent_hash = if address & bit == 0 {
+                let right = match level_nodes.peek() {
+                    Some((next, _)) if *next == address | bit => level_nodes.next().unwrap().1 .0,
+                    _ => sibling(level, target)?,
+                };
+                merkle::hash_children(&hash, &right)
+            } else {
+                merkle::hash_children(&sibling(level, target)?, &hash)
+            };
+
+            parents.insert(address & !bit, (parent_hash, target));
+        }
+
+        nodes = parents;
+    }
+
+    // Everything must have folded into the root
+    let mut nodes = nodes.into_iter();
+    match (nodes.next(), nodes.next()) {
+        (Some((0, (root_hash, _))), None) => Ok(root_hash),
+        _ => Err(ProofError::MalformedMultiProof),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SparseTree;
+
+    /// Builds a proof for `data` at `address` with arbitrary sibling hashes
+    fn prove(address: u64, data: &[u8]) -> MerkleTreeProofData {
+        let log2_target_size = data.len().trailing_zeros() as usize;
+        let sibling_hashes = (0..merkle::LOG2_ROOT_SIZE - log2_target_size)
+            .map(|level| merkle::keccak(&[level as u8]))
+            .collect::<Vec<_>>();
+        let target_hash = merkle::hash_data(data);
+        let root_hash =
+            merkle::root_from_siblings(address, log2_target_size, &target_hash, &sibling_hashes);
+
+        MerkleTreeProofData {
+            target_address: address,
+            log2_target_size,
+            target_hash,
+            log2_root_size: merkle::LOG2_ROOT_SIZE,
+            root_hash,
+            sibling_hashes,
+        }
+    }
+
+    fn memory(address: u64, length: u64, chunk_address: u64) -> AuthenticatedMemory {
+        let data = (0..8).collect::<Vec<u8>>();
+        let proof = prove(chunk_address, &data);
+
+        AuthenticatedMemory {
+            address,
+            length,
+            root_hash: proof.root_hash.clone(),
+            chunks: vec![AuthenticatedChunk { data, proof }],
+        }
+    }
+
+    #[test]
+    fn verifies_and_cuts_out_requested_bytes() {
+        let memory = memory(0x1002, 4, 0x1000);
+        let root_hash = memory.root_hash.clone();
+
+        assert!(memory.verify(&root_hash).is_ok());
+        assert_eq!(memory.data(), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn rejects_tampered_data() {
+        let mut memory = memory(0x1000, 8, 0x1000);
+        let root_hash = memory.root_hash.clone();
+        memory.chunks[0].data[0] ^= 1;
+
+        assert!(matches!(
+            memory.verify(&root_hash),
+            Err(ProofError::TargetHashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_root_hash() {
+        let memory = memory(0x1000, 8, 0x1000);
+
+        assert!(matches!(
+            memory.verify(&merkle::keccak(b"other")),
+            Err(ProofError::RootHashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_uncovered_range() {
+        let memory = memory(0x1000, 16, 0x1000);
+        let root_hash = memory.root_hash.clone();
+
+        assert!(matches!(
+            memory.verify(&root_hash),
+            Err(ProofError::MissingData { address: 0x1008 })
+        ));
+    }
+
+    #[test]
+    fn rejects_oversized_target_without_panicking() {
+        for log2_target_size in [2, 64, 65, usize::MAX] {
+            let mut memory = memory(0x1000, 8, 0x1000);
+            let root_hash = memory.root_hash.clone();
+            memory.chunks[0].proof.log2_target_size = log2_target_size;
+
+            assert!(matches!(
+                memory.verify(&root_hash),
+                Err(ProofError::InvalidProof { .. })
+            ));
+            assert!(!memory.chunks[0].proof.verify());
+        }
+    }
+
+    #[test]
+    fn rejects_shifted_target_address() {
+        let memory = memory(0x1000, 8, 0x1000);
+        let root_hash = memory.root_hash.clone();
+
+        // Bits below the target size do not change the computed root hash
+        let mut shifted = memory.clone();
+        shifted.address = 0x1003;
+        shifted.length = 5;
+        shifted.chunks[0].proof.target_address = 0x1003;
+        assert_eq!(shifted.chunks[0].proof.compute_root_hash(), root_hash);
+
+        assert!(!shifted.chunks[0].proof.verify());
+        assert!(matches!(
+            shifted.verify(&root_hash),
+            Err(ProofError::InvalidProof {
+                address: 0x1003,
+                log2_size: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_target_outside_tree() {
+        let data = (0..8).collect::<Vec<u8>>();
+        let mut proof = prove(0x1000, &data);
+        proof.sibling_hashes.drain(..32);
+        proof.log2_root_size = 32;
+        proof.root_hash = proof.compute_root_hash();
+        assert!(proof.verify());
+
+        // Bits above the root size do not change the computed root hash either
+        proof.target_address |= 1 << 40;
+        assert_eq!(proof.compute_root_hash(), proof.root_hash);
+        assert!(!proof.verify());
+    }
+
+    #[test]
+    fn rejects_tampered_sibling() {
+        let mut memory = memory(0x1000, 8, 0x1000);
+        let root_hash = memory.root_hash.clone();
+        assert!(memory.chunks[0].proof.verify());
+
+        memory.chunks[0].proof.sibling_hashes[10] = merkle::keccak(b"tampered");
+
+        assert!(!memory.chunks[0].proof.verify());
+        assert!(matches!(
+            memory.verify(&root_hash),
+            Err(ProofError::InvalidProof { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_range_past_end_of_address_space() {
+        let memory = memory(u64::MAX - 3, 8, 0x1000);
+        let root_hash = memory.root_hash.clone();
+
+        assert!(matches!(
+            memory.verify(&root_hash),
+            Err(ProofError::RangeOverflow { .. })
+        ));
+    }
+
+    #[test]
+    fn data_ignores_claimed_length() {
+        let memory = memory(0x1000, u64::MAX, 0x1000);
+
+        assert_eq!(memory.data(), (0..8).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn rejects_chunk_past_end_of_address_space() {
+        let memory = memory(u64::MAX - 7, 4, u64::MAX - 7);
+        let root_hash = memory.root_hash.clone();
+
+        assert!(matches!(
+            memory.verify(&root_hash),
+            Err(ProofError::InvalidProof { .. })
+        ));
+        assert_eq!(memory.data(), vec![0, 1, 2, 3]);
+    }
+
+    /// Tree with a few words set, all proofs below coming from the same root
+    fn tree() -> SparseTree {
+        let mut tree = SparseTree::default();
+        for (address, value) in [(0x1000, 1), (0x1008, 2), (0x2000, 3), (0x80000000, 4)] {
+            tree.write(address, value);
+        }
+        tree
+    }
+
+    fn prove_word(tree: &SparseTree, address: u64) -> MerkleTreeProofData {
+        MerkleTreeProofData {
+            target_address: address,
+            log2_target_size: merkle::LOG2_WORD_SIZE,
+            target_hash: merkle::keccak(&tree.read(address).to_le_bytes()),
+            log2_root_size: merkle::LOG2_ROOT_SIZE,
+            root_hash: tree.root(),
+            sibling_hashes: tree.proof(address),
+        }
+    }
+
+    #[test]
+    fn multiproof_shares_siblings() {
+        let tree = tree();
+        let proofs = [0x2000, 0x1008, 0x1000]
+            .map(|address| prove_word(&tree, address))
+            .to_vec();
+        let multiproof = MultiProof::from_proofs(&proofs).unwrap();
+
+        // The two adjacent words prove each other, and join the third one at
+        // 0x2000, leaving 9 + 10 siblings below the join and 50 above it
+        assert_eq!(multiproof.sibling_hashes.len(), 69);
+        assert!(
+            multiproof.sibling_hashes.len()
+                < proofs.iter().map(|proof| proof.sibling_hashes.len()).sum()
+        );
+        assert_eq!(
+            multiproof
+                .targets
+                .iter()
+                .map(|target| target.address)
+                .collect::<Vec<_>>(),
+            [0x1000, 0x1008, 0x2000]
+        );
+    }
+
+    #[test]
+    fn multiproof_round_trip() {
+        let tree = tree();
+        let proofs = [0x1000, 0x80000000, 0x2000, 0x1008, 0x40]
+            .map(|address| prove_word(&tree, address))
+            .to_vec();
+        let multiproof = MultiProof::from_proofs(&proofs).unwrap();
+
+        assert_eq!(multiproof.compute_root_hash().unwrap(), tree.root());
+        assert!(multiproof.verify(&tree.root()).is_ok());
+
+        let single = MultiProof::from_proofs(&proofs[..1]).unwrap();
+        assert_eq!(
+            single.sibling_hashes,
+            proofs[0]
+                .sibling_hashes
+                .iter()
+                .rev()
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+        assert!(single.verify(&tree.root()).is_ok());
+    }
+
+    #[test]
+    fn multiproof_rejects_overlapping_targets() {
+        let tree = tree();
+        let proof = prove_word(&tree, 0x1000);
+
+        assert!(matches!(
+            MultiProof::from_proofs(&[proof.clone(), proof]),
+            Err(ProofError::OverlappingTargets {
+                address: 0x1000,
+                log2_size: 3
+            })
+        ));
+
+        // A node holding another target overlaps it too
+        let mut multiproof = MultiProof::from_proofs(&[prove_word(&tree, 0x1008)]).unwrap();
+        multiproof.targets.push(MultiProofTarget {
+            address: 0x1000,
+            log2_size: 4,
+            hash: merkle::keccak(b"node"),
+        });
+        assert!(matches!(
+            multiproof.compute_root_hash(),
+            Err(ProofError::OverlappingTargets {
+                address: 0x1000,
+                log2_size: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn multiproof_rejects_wrong_sibling_count() {
+        let tree = tree();
+        let multiproof =
+            MultiProof::from_proofs(&[prove_word(&tree, 0x1000), prove_word(&tree, 0x2000)])
+                .unwrap();
+
+        let mut extra = multiproof.clone();
+        extra.sibling_hashes.push(merkle::keccak(b"extra"));
+        assert!(matches!(
+            extra.verify(&tree.root()),
+            Err(ProofError::MalformedMultiProof)
+        ));
+
+        let mut missing = multiproof.clone();
+        missing.sibling_hashes.pop();
+        assert!(matches!(
+            missing.verify(&tree.root()),
+            Err(ProofError::MalformedMultiProof)
+        ));
+
+        let mut empty = multiproof;
+        empty.targets.clear();
+        assert!(matches!(
+            empty.compute_root_hash(),
+            Err(ProofError::MalformedMultiProof)
+        ));
+    }
+
+    #[test]
+    fn multiproof_rejects_tampered_target_hash() {
+        let tree = tree();
+        let mut multiproof =
+            MultiProof::from_proofs(&[prove_word(&tree, 0x1000), prove_word(&tree, 0x1008)])
+                .unwrap();
+        multiproof.targets[1].hash = merkle::keccak(&5u64.to_le_bytes());
+
+        assert!(matches!(
+            multiproof.verify(&tree.root()),
+            Err(ProofError::RootHashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn multiproof_rejects_targets_outside_tree() {
+        let multiproof = MultiProof {
+            log2_root_size: 32,
+            root_hash: merkle::keccak(b"root"),
+            targets: vec![
+                MultiProofTarget {
+                    address: 0x1000,
+                    log2_size: 3,
+                    hash: merkle::keccak(b"inside"),
+                },
+                MultiProofTarget {
+                    address: 1 << 40,
+                    log2_size: 3,
+                    hash: merkle::keccak(b"outside"),
+                },
+            ],
+            sibling_hashes: vec![merkle::keccak(b"sibling"); 58],
+        };
+
+        assert!(matches!(
+            multiproof.compute_root_hash(),
+            Err(ProofError::InvalidProof {
+                address: 0x10000000000,
+                log2_size: 3
+            })
+        ));
+    }
+}
@@ -0,0 +1,727 @@
+//! Pure-Rust replay of microarchitecture step logs.
+//!
+//! The replay runs the RV64I microarchitecture interpreter with every state
+//! access served from the log. Each access must be the one the interpreter
+//! performs next, and must be proven against the root hash the previous
+//! accesses left behind. This mirrors `cm_verify_uarch_step_log` and
+//! `cm_verify_uarch_step_state_transition` without linking the emulator.
+
+use crate::calldata::Reader;
+use crate::errors::ReplayError;
+use crate::hash::Hash;
+use crate::log::{AccessData, AccessLogData, AccessType};
+use crate::{merkle, Machine, CSR};
+
+/// Addresses of the microarchitecture state in the machine address space
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UarchLayout {
+    /// Address of the halt flag
+    pub halt_flag: u64,
+    /// Address of the cycle counter
+    pub cycle: u64,
+    /// Address of the program counter
+    pub pc: u64,
+    /// Address of each general-purpose register
+    pub x: [u64; 32],
+}
+
+/// Start of the shadow uarch state range in emulator version 0.15
+pub const SHADOW_UARCH_STATE_START: u64 = 0x400000;
+
+impl Default for UarchLayout {
+    /// Layout of the shadow uarch state in emulator version 0.15
+    fn default() -> Self {
+        Self {
+            halt_flag: SHADOW_UARCH_STATE_START,
+            cycle: SHADOW_UARCH_STATE_START + 8,
+            pc: SHADOW_UARCH_STATE_START + 16,
+            x: std::array::from_fn(|i| SHADOW_UARCH_STATE_START + 24 + 8 * i as u64),
+        }
+    }
+}
+
+impl UarchLayout {
+    /// Queries the layout from the emulator
+    pub fn from_machine(machine: &mut Machine) -> Self {
+        Self {
+            halt_flag: machine.get_csr_address(CSR::UarchHaltFlag),
+            cycle: machine.get_csr_address(CSR::UarchCycle),
+            pc: machine.get_csr_address(CSR::UarchPc),
+            x: std::array::from_fn(|i| machine.get_uarch_x_address(i as u32)),
+        }
+    }
+}
+
+/// Checks the internal consistency of a uarch step log
+pub fn verify_uarch_step_log(
+    log: &AccessLogData,
+    layout: &UarchLayout,
+    one_based: bool,
+) -> Result<(), ReplayError> {
+    replay(None, log, layout, one_based).map(|_| ())
+}
+
+/// Checks the validity of a state transition caused by a uarch step
+pub fn verify_uarch_step_state_transition(
+    root_hash_before: &Hash,
+    log: &AccessLogData,
+    root_hash_after: &Hash,
+    layout: &UarchLayout,
+    one_based: bool,
+) -> Result<(), ReplayError> {
+    let found = replay(Some(root_hash_before), log, layout, one_based)?;
+
+    if &found != root_hash_after {
+        return Err(ReplayError::FinalRootHashMismatch {
+            expected: root_hash_after.clone(),
+            found,
+        });
+    }
+
+    Ok(())
+}
+
+/// Replays a step, starting from the root hash implied by the first access
+/// when `root_hash_before` is `None`
+fn replay(
+    root_hash_before: Option<&Hash>,
+    log: &AccessLogData,
+    layout: &UarchLayout,
+    one_based: bool,
+) -> Result<Hash, ReplayError> {
+    if !log.log_type.proofs {
+        return Err(ReplayError::MissingProofs);
+    }
+
+    let mut state = ReplayState {
+        source: Source::Log(&log.accesses),
+        next: 0,
+        root_hash: root_hash_before.cloned(),
+        one_based,
+        layout,
+    };
+
+    state.step()?;
+
+    if state.next != log.accesses.len() {
+        return Err(ReplayError::TooManyAccesses {
+            count: log.accesses.len(),
+            used: state.next,
+        });
+    }
+
+    Ok(state.root_hash.expect("the step made at least one access"))
+}
+
+/// Decodes the accesses of a step from calldata, replaying the step to learn
+/// what each access is
+pub(crate) fn decode_step<'a>(
+    reader: &mut Reader<'a>,
+    layout: &'a UarchLayout,
+) -> Result<Vec<AccessData>, ReplayError> {
+    let mut state = ReplayState {
+        source: Source::Calldata {
+            reader,
+            decoded: Vec::new(),
+        },
+        next: 0,
+        root_hash: None,
+        one_based: false,
+        layout,
+    };
+
+    state.step()?;
+
+    match state.source {
+        Source::Calldata { decoded, .. } => Ok(decoded),
+        Source::Log(_) => unreachable!("the source was calldata"),
+    }
+}
+
+/// Where the replay takes its accesses from
+enum Source<'a, 'r> {
+    /// Accesses of a log, checked against the ones the replay performs
+    Log(&'a [AccessData]),
+    /// Values and proofs of a calldata stream, completed by the replay
+    Calldata {
+        reader: &'r mut Reader<'a>,
+        decoded: Vec<AccessData>,
+    },
+}
+
+struct ReplayState<'a, 'r> {
+    source: Source<'a, 'r>,
+    next: usize,
+    root_hash: Option<Hash>,
+    one_based: bool,
+    layout: &'a UarchLayout,
+}
+
+impl<'a, 'r> ReplayState<'a, 'r> {
+    fn index(&self) -> usize {
+        self.next + self.one_based as usize
+    }
+
+    /// Consumes the next access, checking it is the expected one and proving
+    /// the value it read against the current root hash
+    fn access(
+        &mut self,
+        access_type: AccessType,
+        address: u64,
+    ) -> Result<(AccessData, u64), ReplayError> {
+        let index = self.index();
+        let access = match &mut self.source {
+            Source::Log(accesses) => accesses
+                .get(self.next)
+                .ok_or(ReplayError::TooFewAccesses)?
+                .clone(),
+            Source::Calldata { reader, decoded } => {
+                let data = reader.take_word().ok_or(ReplayError::TooFewAccesses)?;
+                let sibling_hashes = reader
+                    .take_sibling_hashes(merkle::LOG2_WORD_SIZE)
+                    .ok_or(ReplayError::TooFewAccesses)?;
+
+                decoded.push(AccessData {
+                    access_type,
+                    address,
+                    log2_size: merkle::LOG2_WORD_SIZE as i32,
+                    read_hash: merkle::keccak(&data),
+                    read_data: Some(data.to_vec()),
+                    written_hash: None,
+                    written_data: None,
+                    sibling_hashes: Some(sibling_hashes),
+                });
+                decoded.last().unwrap().clone()
+            }
+        };
+
+        if access.access_type != access_type
+            || access.address != address
+            || access.log2_size != merkle::LOG2_WORD_SIZE as i32
+        {
+            return Err(ReplayError::UnexpectedAccess {
+                index,
+                expected_type: access_type,
+                expected_address: address,
+                found_type: access.access_type,
+                found_address: access.address,
+                found_log2_size: access.log2_size,
+            });
+        }
+
+        let sibling_hashes = access
+            .sibling_hashes
+            .as_ref()
+            .ok_or(ReplayError::MissingProofs)?;
+        let read_value = word_value(access.read_data.as_deref(), &access.read_hash, index)?;
+
+        if sibling_hashes.len() != merkle::LOG2_ROOT_SIZE - merkle::LOG2_WORD_SIZE {
+            return Err(ReplayError::RootHashMismatch { index });
+        }
+
+        let root_hash = merkle::root_from_siblings(
+            address,
+            merkle::LOG2_WORD_SIZE,
+            &access.read_hash,
+            sibling_hashes,
+        );
+
+        match &self.root_hash {
+            Some(expected) if expected != &root_hash => {
+                return Err(ReplayError::RootHashMismatch { index })
+            }
+            Some(_) => {}
+            None => self.root_hash = Some(root_hash),
+        }
+
+        self.next += 1;
+        Ok((access, read_value))
+    }
+
+    fn read_word(&mut self, address: u64) -> Result<u64, ReplayError> {
+        Ok(self.access(AccessType::Read, address)?.1)
+    }
+
+    fn write_word(&mut self, address: u64, value: u64) -> Result<(), ReplayError> {
+        let index = self.index();
+        let (access, _) = self.access(AccessType::Write, address)?;
+
+        let written_hash = match &mut self.source {
+            Source::Log(_) => {
+                let written_hash = access
+                    .written_hash
+                    .ok_or(ReplayError::MissingData { index })?;
+                let written_value =
+                    word_value(access.written_data.as_deref(), &written_hash, index)?;
+
+                if written_value != value {
+                    return Err(ReplayError::WrittenValueMismatch {
+                        index,
+                        expected: value,
+                        found: written_value,
+                    });
+                }
+
+                written_hash
+            }
+            Source::Calldata { decoded, .. } => {
+                let data = value.to_le_bytes();
+                let written_hash = merkle::keccak(&data);
+                let decoded = decoded.last_mut().unwrap();
+
+                decoded.written_data = Some(data.to_vec());
+                decoded.written_hash = Some(written_hash.clone());
+                written_hash
+            }
+        };
+
+        self.root_hash = Some(merkle::root_from_siblings(
+            address,
+            merkle::LOG2_WORD_SIZE,
+            &written_hash,
+            access.sibling_hashes.as_ref().unwrap(),
+        ));
+
+        Ok(())
+    }
+
+    fn read_x(&mut self, i: u32) -> Result<u64, ReplayError> {
+        self.read_word(self.layout.x[i as usize])
+    }
+
+    fn write_x(&mut self, i: u32, value: u64) -> Result<(), ReplayError> {
+        if i != 0 {
+            self.write_word(self.layout.x[i as usize], value)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_memory(&mut self, address: u64, size: u32) -> Result<u64, ReplayError> {
+        check_alignment(address, size)?;
+
+        let word = self.read_word(address & !7)?;
+        let shift = (address & 7) * 8;
+
+        Ok((word >> shift) & mask(size))
+    }
+
+    fn write_memory(&mut self, address: u64, size: u32, value: u64) -> Result<(), ReplayError> {
+        check_alignment(address, size)?;
+
+        if size == 8 {
+            return self.write_word(address, value);
+        }
+
+        let word = self.read_word(address & !7)?;
+        let shift = (address & 7) * 8;
+        let word = (word & !(mask(size) << shift)) | ((value & mask(size)) << shift);
+
+        self.write_word(address & !7, word)
+    }
+
+    fn step(&mut self) -> Result<(), ReplayError> {
+        let cycle = self.read_word(self.layout.cycle)?;
+
+        if cycle == u64::MAX {
+            return Ok(());
+        }
+
+        if self.read_word(self.layout.halt_flag)? != 0 {
+            return Ok(());
+        }
+
+        let pc = self.read_word(self.layout.pc)?;
+        let insn = self.read_memory(pc, 4)? as u32;
+
+        self.execute(insn, pc)?;
+        self.write_word(self.layout.cycle, cycle + 1)
+    }
+
+    fn execute(&mut self, insn: u32, pc: u64) -> Result<(), ReplayError> {
+        let opcode = insn & 0x7f;
+        let rd = (insn >> 7) & 0x1f;
+        let funct3 = (insn >> 12) & 0x7;
+        let rs1 = (insn >> 15) & 0x1f;
+        let rs2 = (insn >> 20) & 0x1f;
+        let funct7 = insn >> 25;
+        let illegal = ReplayError::IllegalInstruction { pc, insn };
+
+        let next_pc = match opcode {
+            // LUI
+            0x37 => {
+                self.write_x(rd, u_imm(insn))?;
+                pc.wrapping_add(4)
+            }
+            // AUIPC
+            0x17 => {
+                self.write_x(rd, pc.wrapping_add(u_imm(insn)))?;
+                pc.wrapping_add(4)
+            }
+            // JAL
+            0x6f => {
+                self.write_x(rd, pc.wrapping_add(4))?;
+                pc.wrapping_add(j_imm(insn))
+            }
+            // JALR
+            0x67 if funct3 == 0 => {
+                let rs1val = self.read_x(rs1)?;
+                self.write_x(rd, pc.wrapping_add(4))?;
+                rs1val.wrapping_add(i_imm(insn)) & !1
+            }
+            // BEQ, BNE, BLT, BGE, BLTU, BGEU
+            0x63 => {
+                let rs1val = self.read_x(rs1)?;
+                let rs2val = self.read_x(rs2)?;
+                let taken = match funct3 {
+                    0 => rs1val == rs2val,
+                    1 => rs1val != rs2val,
+                    4 => (rs1val as i64) < (rs2val as i64),
+                    5 => (rs1val as i64) >= (rs2val as i64),
+                    6 => rs1val < rs2val,
+                    7 => rs1val >= rs2val,
+                    _ => return Err(illegal),
+                };
+
+                if taken {
+                    pc.wrapping_add(b_imm(insn))
+                } else {
+                    pc.wrapping_add(4)
+                }
+            }
+            // LB, LH, LW, LD, LBU, LHU, LWU
+            0x03 => {
+                let (size, signed) = match funct3 {
+                    0 => (1, true),
+                    1 => (2, true),
+                    2 => (4, true),
+                    3 => (8, false),
+                    4 => (1, false),
+                    5 => (2, false),
+                    6 => (4, false),
+                    _ => return Err(illegal),
+                };
+
+                let rs1val = self.read_x(rs1)?;
+                let value = self.read_memory(rs1val.wrapping_add(i_imm(insn)), size)?;
+                let value = if signed {
+                    sign_extend(value, size * 8)
+                } else {
+                    value
+                };
+
+                self.write_x(rd, value)?;
+                pc.wrapping_add(4)
+            }
+            // SB, SH, SW, SD
+            0x23 => {
+                let size = match funct3 {
+                    0..=3 => 1 << funct3,
+                    _ => return Err(illegal),
+                };
+
+                let rs1val = self.read_x(rs1)?;
+                let rs2val = self.read_x(rs2)?;
+                self.write_memory(rs1val.wrapping_add(s_imm(insn)), size, rs2val)?;
+                pc.wrapping_add(4)
+            }
+            // ADDI, SLLI, SLTI, SLTIU, XORI, SRLI, SRAI, ORI, ANDI
+            0x13 => {
+                let rs1val = self.read_x(rs1)?;
+                let imm = i_imm(insn);
+                let shamt = (insn >> 20) & 0x3f;
+                let funct6 = insn >> 26;
+
+                let value = match (funct3, funct6) {
+                    (0, _) => rs1val.wrapping_add(imm),
+                    (1, 0) => rs1val << shamt,
+                    (2, _) => ((rs1val as i64) < (imm as i64)) as u64,
+                    (3, _) => (rs1val < imm) as u64,
+                    (4, _) => rs1val ^ imm,
+                    (5, 0) => rs1val >> shamt,
+                    (5, 0x10) => ((rs1val as i64) >> shamt) as u64,
+                    (6, _) => rs1val | imm,
+                    (7, _) => rs1val & imm,
+                    _ => return Err(illegal),
+                };
+
+                self.write_x(rd, value)?;
+                pc.wrapping_add(4)
+            }
+            // ADDIW, SLLIW, SRLIW, SRAIW
+            0x1b => {
+                let rs1val = self.read_x(rs1)? as u32;
+                let shamt = (insn >> 20) & 0x1f;
+
+                let value = match (funct3, funct7) {
+                    (0, _) => rs1val.wrapping_add(i_imm(insn) as u32),
+                    (1, 0) => rs1val << shamt,
+                    (5, 0) => rs1val >> shamt,
+                    (5, 0x20) => ((rs1val as i32) >> shamt) as u32,
+                    _ => return Err(illegal),
+                };
+
+                self.write_x(rd, value as i32 as u64)?;
+                pc.wrapping_add(4)
+            }
+            // ADD, SUB, SLL, SLT, SLTU, XOR, SRL, SRA, OR, AND
+            0x33 => {
+                let rs1val = self.read_x(rs1)?;
+                let rs2val = self.read_x(rs2)?;
+                let shamt = rs2val & 0x3f;
+
+                let value = match (funct3, funct7) {
+                    (0, 0) => rs1val.wrapping_add(rs2val),
+                    (0, 0x20) => rs1val.wrapping_sub(rs2val),
+                    (1, 0) => rs1val << shamt,
+                    (2, 0) => ((rs1val as i64) < (rs2val as i64)) as u64,
+                    (3, 0) => (rs1val < rs2val) as u64,
+                    (4, 0) => rs1val ^ rs2val,
+                    (5, 0) => rs1val >> shamt,
+                    (5, 0x20) => ((rs1val as i64) >> shamt) as u64,
+                    (6, 0) => rs1val | rs2val,
+                    (7, 0) => rs1val & rs2val,
+                    _ => return Err(illegal),
+                };
+
+                self.write_x(rd, value)?;
+                pc.wrapping_add(4)
+            }
+            // ADDW, SUBW, SLLW, SRLW, SRAW
+            0x3b => {
+                let rs1val = self.read_x(rs1)? as u32;
+                let rs2val = self.read_x(rs2)? as u32;
+                let shamt = rs2val & 0x1f;
+
+                let value = match (funct3, funct7) {
+                    (0, 0) => rs1val.wrapping_add(rs2val),
+                    (0, 0x20) => rs1val.wrapping_sub(rs2val),
+                    (1, 0) => rs1val << shamt,
+                    (5, 0) => rs1val >> shamt,
+                    (5, 0x20) => ((rs1val as i32) >> shamt) as u32,
+                    _ => return Err(illegal),
+                };
+
+                self.write_x(rd, value as i32 as u64)?;
+                pc.wrapping_add(4)
+            }
+            // FENCE
+            0x0f if funct3 == 0 => pc.wrapping_add(4),
+            // ECALL
+            0x73 if insn == 0x73 => {
+                let function = self.read_x(17)?;
+
+                match function {
+                    UARCH_ECALL_FN_HALT => self.write_word(self.layout.halt_flag, 1)?,
+                    UARCH_ECALL_FN_PUTCHAR => {
+                        self.read_x(16)?;
+                    }
+                    _ => return Err(ReplayError::UnsupportedEcall { function }),
+                }
+
+                pc.wrapping_add(4)
+            }
+            _ => return Err(illegal),
+        };
+
+        self.write_word(self.layout.pc, next_pc)
+    }
+}
+
+const UARCH_ECALL_FN_HALT: u64 = 1;
+const UARCH_ECALL_FN_PUTCHAR: u64 = 2;
+
+fn word_value(data: Option<&[u8]>, hash: &Hash, index: usize) -> Result<u64, ReplayError> {
+    let data: [u8; 8] = data
+        .and_then(|data| data.try_into().ok())
+        .ok_or(ReplayError::MissingData { index })?;
+
+    if &merkle::keccak(&data) != hash {
+        return Err(ReplayError::DataHashMismatch { index });
+    }
+
+    Ok(u64::from_le_bytes(data))
+}
+
+fn check_alignment(address: u64, size: u32) -> Result<(), ReplayError> {
+    if address & (size as u64 - 1) != 0 {
+        return Err(ReplayError::MisalignedAccess { address, size });
+    }
+
+    Ok(())
+}
+
+fn mask(size: u32) -> u64 {
+    if size == 8 {
+        u64::MAX
+    } else {
+        (1 << (size * 8)) - 1
+    }
+}
+
+fn sign_extend(value: u64, bits: u32) -> u64 {
+    let shift = 64 - bits;
+    (((value << shift) as i64) >> shift) as u64
+}
+
+fn i_imm(insn: u32) -> u64 {
+    ((insn as i32) >> 20) as i64 as u64
+}
+
+fn s_imm(insn: u32) -> u64 {
+    ((((insn as i32) >> 25) << 5) | ((insn >> 7) & 0x1f) as i32) as i64 as u64
+}
+
+fn b_imm(insn: u32) -> u64 {
+    ((((insn as i32) >> 31) << 12)
+        | (((insn >> 7) & 0x1) << 11) as i32
+        | (((insn >> 25) & 0x3f) << 5) as i32
+        | (((insn >> 8) & 0xf) << 1) as i32) as i64 as u64
+}
+
+fn u_imm(insn: u32) -> u64 {
+    (insn & 0xfffff000) as i32 as i64 as u64
+}
+
+fn j_imm(insn: u32) -> u64 {
+    ((((insn as i32) >> 31) << 20)
+        | (insn & 0xff000) as i32
+        | (((insn >> 20) & 0x1) << 11) as i32
+        | (((insn >> 21) & 0x3ff) << 1) as i32) as i64 as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{addi_step, PC};
+
+    fn check(log: &AccessLogData, before: &Hash, after: &Hash) -> Result<(), ReplayError> {
+        let layout = UarchLayout::default();
+        verify_uarch_step_log(log, &layout, false)?;
+        verify_uarch_step_state_transition(before, log, after, &layout, false)
+    }
+
+    #[test]
+    fn accepts_recorded_step() {
+        let (before, log, after) = addi_step(&UarchLayout::default());
+
+        assert_eq!(check(&log, &before, &after), Ok(()));
+    }
+
+    #[test]
+    fn rejects_log_without_proofs() {
+        let (before, mut log, after) = addi_step(&UarchLayout::default());
+        log.log_type.proofs = false;
+
+        assert_eq!(
+            check(&log, &before, &after),
+            Err(ReplayError::MissingProofs)
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_root_hashes() {
+        let (before, log, after) = addi_step(&UarchLayout::default());
+        let layout = UarchLayout::default();
+
+        assert_eq!(
+            verify_uarch_step_state_transition(&after, &log, &after, &layout, false),
+            Err(ReplayError::RootHashMismatch { index: 0 })
+        );
+        assert_eq!(
+            verify_uarch_step_state_transition(&before, &log, &before, &layout, false),
+            Err(ReplayError::FinalRootHashMismatch {
+                expected: before.clone(),
+                found: after,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_extra_accesses() {
+        let (before, mut log, after) = addi_step(&UarchLayout::default());
+        let last = log.accesses.pop().unwrap();
+
+        assert_eq!(
+            check(&log, &before, &after),
+            Err(ReplayError::TooFewAccesses)
+        );
+
+        log.accesses.push(last.clone());
+        log.accesses.push(last);
+        assert_eq!(
+            check(&log, &before, &after),
+            Err(ReplayError::TooManyAccesses { count: 9, used: 8 })
+        );
+    }
+
+    #[test]
+    fn rejects_unexpected_access() {
+        let layout = UarchLayout::default();
+        let (before, mut log, after) = addi_step(&layout);
+        log.accesses[4].address = layout.x[7];
+
+        assert_eq!(
+            check(&log, &before, &after),
+            Err(ReplayError::UnexpectedAccess {
+                index: 4,
+                expected_type: AccessType::Read,
+                expected_address: layout.x[6],
+                found_type: AccessType::Read,
+                found_address: layout.x[7],
+                found_log2_size: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_data() {
+        let (before, mut log, after) = addi_step(&UarchLayout::default());
+        log.accesses[3].read_data.as_mut().unwrap()[0] ^= 1;
+
+        assert_eq!(
+            check(&log, &before, &after),
+            Err(ReplayError::DataHashMismatch { index: 3 })
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_proof() {
+        let (before, mut log, after) = addi_step(&UarchLayout::default());
+        log.accesses[2].sibling_hashes.as_mut().unwrap()[10] = merkle::keccak(b"forged");
+
+        assert_eq!(
+            check(&log, &before, &after),
+            Err(ReplayError::RootHashMismatch { index: 2 })
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_written_value() {
+        let (before, mut log, after) = addi_step(&UarchLayout::default());
+        let forged = (PC + 8).to_le_bytes();
+        log.accesses[6].written_hash = Some(merkle::keccak(&forged));
+        log.accesses[6].written_data = Some(forged.to_vec());
+
+        assert_eq!(
+            check(&log, &before, &after),
+            Err(ReplayError::WrittenValueMismatch {
+                index: 6,
+                expected: PC + 4,
+                found: PC + 8,
+            })
+        );
+    }
+
+    #[test]
+    fn indices_follow_one_based_flag() {
+        let layout = UarchLayout::default();
+        let (_, mut log, _) = addi_step(&layout);
+        log.accesses[3].read_data.as_mut().unwrap()[0] ^= 1;
+
+        assert_eq!(
+            verify_uarch_step_log(&log, &layout, true),
+            Err(ReplayError::DataHashMismatch { index: 4 })
+        );
+    }
+}
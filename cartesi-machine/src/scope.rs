@@ -0,0 +1,243 @@
+//! Hierarchical view of the brackets of an access log.
+
+use std::ops::Range;
+
+use crate::errors::BracketError;
+use crate::log::{AccessData, AccessLogData, BracketType};
+
+/// Named scope delimited by a pair of begin and end brackets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scope {
+    /// Text of the brackets
+    pub name: String,
+    /// Indices of the accesses covered by the scope
+    pub accesses: Range<usize>,
+    /// Scopes nested directly inside this one, in order
+    pub children: Vec<Scope>,
+}
+
+impl Scope {
+    /// Accesses of `log` covered by the scope
+    pub fn accesses<'a>(&self, log: &'a AccessLogData) -> &'a [AccessData] {
+        &log.accesses[self.accesses.clone()]
+    }
+}
+
+impl AccessLogData {
+    /// Builds the tree of scopes described by the brackets
+    ///
+    /// Returns the outermost scopes, in order.
+    pub fn scopes(&self) -> Result<Vec<Scope>, BracketError> {
+        let mut roots = Vec::new();
+        let mut stack: Vec<(usize, Scope)> = Vec::new();
+        let mut last_where = 0;
+
+        for (index, bracket) in self.brackets.iter().enumerate() {
+            let r#where = bracket.r#where as usize;
+
+            if r#where > self.accesses.len() {
+                return Err(BracketError::OutOfRange { index });
+            }
+
+            if r#where < last_where {
+                return Err(BracketError::OutOfOrder { index });
+            }
+
+            last_where = r#where;
+
+            match bracket.kind {
+                BracketType::Begin => stack.push((
+                    index,
+                    Scope {
+                        name: bracket.text.clone(),
+                        accesses: r#where..r#where,
+                        children: Vec::new(),
+                    },
+                )),
+                BracketType::End => {
+                    let (_, mut scope) = stack.pop().ok_or(BracketError::UnmatchedEnd { index })?;
+
+                    if scope.name != bracket.text {
+                        return Err(BracketError::MismatchedEnd {
+                            index,
+                            expected: scope.name,
+                            found: bracket.text.clone(),
+                        });
+                    }
+
+                    scope.accesses.end = r#where;
+
+                    match stack.last_mut() {
+                        Some((_, parent)) => parent.children.push(scope),
+                        None => roots.push(scope),
+                    }
+                }
+            }
+        }
+
+        match stack.first() {
+            Some((index, _)) => Err(BracketError::UnclosedBegin { index: *index }),
+            None => Ok(roots),
+        }
+    }
+
+    /// Checks that the brackets are ordered and balanced
+    pub fn validate_brackets(&self) -> Result<(), BracketError> {
+        self.scopes().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::BracketNoteData;
+    use crate::replay::UarchLayout;
+    use crate::testing::addi_step;
+
+    fn begin(r#where: u64, text: &str) -> BracketNoteData {
+        BracketNoteData {
+            kind: BracketType::Begin,
+            r#where,
+            text: text.to_string(),
+        }
+    }
+
+    fn end(r#where: u64, text: &str) -> BracketNoteData {
+        BracketNoteData {
+            kind: BracketType::End,
+            r#where,
+            text: text.to_string(),
+        }
+    }
+
+    /// The 8 accesses of the addi step, bracketed by `brackets`
+    fn log(brackets: Vec<BracketNoteData>) -> AccessLogData {
+        let (_, mut log, _) = addi_step(&UarchLayout::default());
+        log.brackets = brackets;
+        log
+    }
+
+    fn scope(name: &str, accesses: Range<usize>, children: Vec<Scope>) -> Scope {
+        Scope {
+            name: name.to_string(),
+            accesses,
+            children,
+        }
+    }
+
+    #[test]
+    fn builds_nested_scopes() {
+        let log = log(vec![
+            begin(0, "step"),
+            begin(0, "fetch"),
+            end(2, "fetch"),
+            begin(2, "execute"),
+            begin(3, "read x"),
+            end(4, "read x"),
+            end(8, "execute"),
+            end(8, "step"),
+            begin(8, "empty"),
+            end(8, "empty"),
+        ]);
+
+        let scopes = log.scopes().unwrap();
+        assert_eq!(
+            scopes,
+            [
+                scope(
+                    "step",
+                    0..8,
+                    vec![
+                        scope("fetch", 0..2, vec![]),
+                        scope("execute", 2..8, vec![scope("read x", 3..4, vec![])]),
+                    ],
+                ),
+                scope("empty", 8..8, vec![]),
+            ]
+        );
+        assert_eq!(scopes[0].children[0].accesses(&log), &log.accesses[0..2]);
+        assert!(scopes[1].accesses(&log).is_empty());
+        assert_eq!(log.validate_brackets(), Ok(()));
+    }
+
+    #[test]
+    fn accepts_logs_without_brackets() {
+        assert_eq!(log(vec![]).scopes(), Ok(vec![]));
+        assert_eq!(log(vec![]).validate_brackets(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_unmatched_end() {
+        let log = log(vec![begin(0, "step"), end(4, "step"), end(5, "step")]);
+
+        assert_eq!(log.scopes(), Err(BracketError::UnmatchedEnd { index: 2 }));
+        assert_eq!(
+            log.validate_brackets(),
+            Err(BracketError::UnmatchedEnd { index: 2 })
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_name() {
+        let log = log(vec![
+            begin(0, "step"),
+            begin(1, "fetch"),
+            end(2, "step"),
+            end(3, "fetch"),
+        ]);
+
+        let error = BracketError::MismatchedEnd {
+            index: 2,
+            expected: "fetch".to_string(),
+            found: "step".to_string(),
+        };
+        assert_eq!(log.scopes(), Err(error.clone()));
+        assert_eq!(log.validate_brackets(), Err(error));
+    }
+
+    #[test]
+    fn rejects_unclosed_begin() {
+        // The outermost open scope is reported
+        let log = log(vec![
+            begin(0, "step"),
+            begin(1, "fetch"),
+            end(2, "fetch"),
+            begin(3, "execute"),
+        ]);
+
+        assert_eq!(log.scopes(), Err(BracketError::UnclosedBegin { index: 0 }));
+        assert_eq!(
+            log.validate_brackets(),
+            Err(BracketError::UnclosedBegin { index: 0 })
+        );
+    }
+
+    #[test]
+    fn rejects_brackets_out_of_order() {
+        let log = log(vec![begin(3, "step"), end(2, "step")]);
+
+        assert_eq!(log.scopes(), Err(BracketError::OutOfOrder { index: 1 }));
+        assert_eq!(
+            log.validate_brackets(),
+            Err(BracketError::OutOfOrder { index: 1 })
+        );
+    }
+
+    #[test]
+    fn rejects_brackets_past_the_accesses() {
+        // Pointing one past the last access closes a scope at the end of the log
+        assert_eq!(
+            log(vec![begin(0, "step"), end(8, "step")])
+                .scopes()
+                .map(|scopes| scopes.len()),
+            Ok(1)
+        );
+
+        let log = log(vec![begin(0, "step"), end(9, "step")]);
+        assert_eq!(log.scopes(), Err(BracketError::OutOfRange { index: 1 }));
+        assert_eq!(
+            log.validate_brackets(),
+            Err(BracketError::OutOfRange { index: 1 })
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Self-contained proofs of single uarch transitions.
+
+use serde::{Deserialize, Serialize};
+
+use crate::bisect::StoredMachine;
+use crate::errors::ProveError;
+use crate::hash::Hash;
+use crate::log::{AccessLogData, AccessLogType, OwnedAccessLog};
+use crate::verifier::Verifier;
+use crate::{BreakReason, Machine};
+
+/// Version of the [`StepProof`] format
+pub const STEP_PROOF_VERSION: u32 = 1;
+
+/// Version of the emulator the proofs are produced with
+pub fn emulator_version() -> String {
+    format!(
+        "{}.{}.{}",
+        cartesi_machine_sys::CM_VERSION_MAJOR,
+        cartesi_machine_sys::CM_VERSION_MINOR,
+        cartesi_machine_sys::CM_VERSION_PATCH
+    )
+}
+
+/// Kind of transition proven by a [`StepProof`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepKind {
+    /// A single uarch step, logged with `log_uarch_step`
+    Step,
+    /// The uarch reset that ends an mcycle, logged with `log_uarch_reset`
+    Reset,
+}
+
+/// Proof of the transition at a given mcycle and uarch cycle
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepProof {
+    /// Version of the proof format
+    pub version: u32,
+    /// Version of the emulator that produced the log
+    pub emulator_version: String,
+    /// mcycle the transition belongs to
+    pub mcycle: u64,
+    /// uarch cycle the transition starts at
+    pub uarch_cycle: u64,
+    /// Kind of transition
+    pub kind: StepKind,
+    /// Root hash before the transition
+    pub root_hash_before: Hash,
+    /// Root hash after the transition
+    pub root_hash_after: Hash,
+    /// Access log of the transition, with proofs
+    pub log: AccessLogData,
+}
+
+impl StepProof {
+    /// Checks the transition with the emulator verifier
+    pub fn verify(&self, verifier: &Verifier) -> Result<(), ProveError> {
+        if self.version != STEP_PROOF_VERSION {
+            return Err(ProveError::UnsupportedVersion {
+                version: self.version,
+            });
+        }
+
+        if self.emulator_version != emulator_version() {
+            return Err(ProveError::EmulatorVersionMismatch {
+                expected: emulator_version(),
+                found: self.emulator_version.clone(),
+            });
+        }
+
+        let log = OwnedAccessLog::try_from(&self.log)?;
+
+        match self.kind {
+            StepKind::Step => verifier.verify_uarch_step_state_transition(
+                &self.root_hash_before,
+                &log,
+                &self.root_hash_after,
+            )?,
+            StepKind::Reset => verifier.verify_uarch_reset_state_transition(
+                &self.root_hash_before,
+                &log,
+                &self.root_hash_after,
+            )?,
+        }
+
+        Ok(())
+    }
+}
+
+impl StoredMachine {
+    /// Positions the machine at `mcycle` and `uarch_cycle` and proves the next transition
+    ///
+    /// The machine is reloaded from its directory when it is already past the
+    /// requested cycle, so transitions can be proven in any order.
+    pub fn prove_step(&mut self, mcycle: u64, uarch_cycle: u64) -> Result<StepProof, ProveError> {
+        prove_step(self.rewind_to(mcycle, uarch_cycle)?, mcycle, uarch_cycle)
+    }
+}
+
+/// Runs `machine` forward to `mcycle` and `uarch_cycle`, then logs the next transition
+pub(crate) fn prove_step(
+    machine: &mut Machine,
+    mcycle: u64,
+    uarch_cycle: u64,
+) -> Result<StepProof, ProveError> {
+    let current = machine.read_mcycle()?;
+
+    if current > mcycle || (current == mcycle && machine.read_uarch_cycle()? > uarch_cycle) {
+        return Err(ProveError::CycleInPast {
+            mcycle: current,
+            uarch_cycle: machine.read_uarch_cycle()?,
+        });
+    }
+
+    if current < mcycle {
+        // Complete any mcycle the microarchitecture is in the middle of
+        if machine.read_uarch_cycle()? > 0 {
+            machine.run_uarch(u64::MAX)?;
+            machine.reset_uarch()?;
+        }
+
+        while machine.read_mcycle()? < mcycle {
+            if !matches!(machine.run(mcycle)?, BreakReason::YieldedAutomatically) {
+                break;
+            }
+        }
+
+        if machine.read_mcycle()? != mcycle {
+            return Err(ProveError::CycleUnreachable {
+                mcycle,
+                uarch_cycle,
+            });
+        }
+    }
+
+    machine.run_uarch(uarch_cycle)?;
+
+    if machine.read_uarch_cycle()? != uarch_cycle {
+        return Err(ProveError::CycleUnreachable {
+            mcycle,
+            uarch_cycle,
+        });
+    }
+
+    let log_type = AccessLogType {
+        proofs: true,
+        annotations: false,
+        large_data: false,
+    };
+    let root_hash_before = machine.get_root_hash()?;
+
+    let (kind, log) = if machine.read_uarch_halt_flag()? {
+        (StepKind::Reset, machine.log_uarch_reset(log_type, false)?)
+    } else {
+        (StepKind::Step, machine.log_uarch_step(log_type, false)?)
+    };
+
+    Ok(StepProof {
+        version: STEP_PROOF_VERSION,
+        emulator_version: emulator_version(),
+        mcycle,
+        uarch_cycle,
+        kind,
+        root_hash_before,
+        root_hash_after: machine.get_root_hash()?,
+        log: log.to_data(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::RuntimeConfig;
+    use crate::replay::UarchLayout;
+    use crate::testing::addi_step;
+
+    fn proof() -> StepProof {
+        let (root_hash_before, log, root_hash_after) = addi_step(&UarchLayout::default());
+
+        StepProof {
+            version: STEP_PROOF_VERSION,
+            emulator_version: emulator_version(),
+            mcycle: 3,
+            uarch_cycle: 5,
+            kind: StepKind::Step,
+            root_hash_before,
+            root_hash_after,
+            log,
+        }
+    }
+
+    #[test]
+    fn survives_json_round_trip() {
+        let json = serde_json::to_string(&proof()).unwrap();
+
+        assert_eq!(serde_json::from_str::<StepProof>(&json).unwrap(), proof());
+        assert!(json.contains(r#""kind":"step""#));
+        assert!(json.contains(r#""log_type":{"proofs":true"#));
+    }
+
+    #[test]
+    fn rejects_other_versions_before_verifying() {
+        let verifier = Verifier::new(RuntimeConfig::default());
+
+        let mut other = proof();
+        other.version += 1;
+        assert!(matches!(
+            other.verify(&verifier),
+            Err(ProveError::UnsupportedVersion { version }) if version == STEP_PROOF_VERSION + 1
+        ));
+
+        let mut other = proof();
+        other.emulator_version = "0.0.0".to_string();
+        assert!(matches!(
+            other.verify(&verifier),
+            Err(ProveError::EmulatorVersionMismatch { found, .. }) if found == "0.0.0"
+        ));
+    }
+}
@@ -0,0 +1,255 @@
+//! Streaming access logs of consecutive microarchitecture steps.
+//!
+//! Each log is copied out of the emulator and its C allocation released as
+//! soon as it is produced, so memory use is bounded by a single step log.
+
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{MachineError, StreamError};
+use crate::log::{AccessLogData, AccessLogType};
+use crate::Machine;
+
+/// Access log of one item of a uarch step stream
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum UarchLog {
+    /// Log of the step that started at `uarch_cycle`
+    Step {
+        uarch_cycle: u64,
+        log: AccessLogData,
+    },
+    /// Log of the reset that follows the halt of the microarchitecture
+    Reset { log: AccessLogData },
+}
+
+/// Iterator over the logs of consecutive uarch steps, created by [`Machine::log_uarch_steps`]
+///
+/// Steps are logged until the microarchitecture halts, after which the
+/// reset is logged and the iterator ends. A step that leaves the uarch cycle
+/// unchanged without halting, as at the maximum cycle, is the last one logged.
+pub struct UarchStepLogs<'a> {
+    machine: &'a mut Machine,
+    log_type: AccessLogType,
+    one_based: bool,
+    done: bool,
+}
+
+impl<'a> UarchStepLogs<'a> {
+    pub(crate) fn new(machine: &'a mut Machine, log_type: AccessLogType, one_based: bool) -> Self {
+        Self {
+            machine,
+            log_type,
+            one_based,
+            done: false,
+        }
+    }
+
+    fn next_log(&mut self) -> Result<UarchLog, MachineError> {
+        if self.machine.read_uarch_halt_flag()? {
+            self.done = true;
+
+            let log = self
+                .machine
+                .log_uarch_reset(self.log_type, self.one_based)?;
+
+            return Ok(UarchLog::Reset { log: log.to_data() });
+        }
+
+        let uarch_cycle = self.machine.read_uarch_cycle()?;
+        let log = self.machine.log_uarch_step(self.log_type, self.one_based)?;
+
+        // Stepping again would log the same cycle forever
+        if self.machine.read_uarch_cycle()? == uarch_cycle
+            && !self.machine.read_uarch_halt_flag()?
+        {
+            self.done = true;
+        }
+
+        Ok(UarchLog::Step {
+            uarch_cycle,
+            log: log.to_data(),
+        })
+    }
+}
+
+impl<'a> Iterator for UarchStepLogs<'a> {
+    type Item = Result<UarchLog, MachineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let log = self.next_log();
+
+        if log.is_err() {
+            self.done = true;
+        }
+
+        Some(log)
+    }
+}
+
+/// Writes each log as a line of JSON, returning the number of logs written
+pub fn write_json_lines<I, W>(logs: I, mut writer: W) -> Result<usize, StreamError>
+where
+    I: IntoIterator<Item = Result<UarchLog, MachineError>>,
+    W: Write,
+{
+    let mut count = 0;
+
+    for log in logs {
+        serde_json::to_writer(&mut writer, &log?).map_err(std::io::Error::from)?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+
+    writer.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::errors::ConfigIssue;
+    use crate::replay::UarchLayout;
+    use crate::testing::addi_step;
+
+    fn step(uarch_cycle: u64) -> UarchLog {
+        let (_, log, _) = addi_step(&UarchLayout::default());
+        UarchLog::Step { uarch_cycle, log }
+    }
+
+    fn reset() -> UarchLog {
+        let (_, mut log, _) = addi_step(&UarchLayout::default());
+        log.accesses.truncate(1);
+        UarchLog::Reset { log }
+    }
+
+    fn lines(output: &[u8]) -> Vec<UarchLog> {
+        std::str::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// Sink shared with the test, failing once it holds `limit` bytes
+    #[derive(Clone, Default)]
+    struct Sink {
+        output: Rc<RefCell<Vec<u8>>>,
+        flushes: Rc<RefCell<usize>>,
+        limit: Option<usize>,
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let mut output = self.output.borrow_mut();
+            let room = self.limit.map_or(buf.len(), |limit| limit - output.len());
+            if room == 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::WriteZero,
+                    "sink full",
+                ));
+            }
+
+            let written = room.min(buf.len());
+            output.extend_from_slice(&buf[..written]);
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            *self.flushes.borrow_mut() += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_each_log_before_pulling_the_next() {
+        let sink = Sink::default();
+        let output = sink.output.clone();
+        let logs = vec![step(0), step(1), reset()];
+
+        // Every log must already be on its own line when the next one is produced
+        let produced = logs.clone().into_iter().enumerate().map(|(index, log)| {
+            assert_eq!(lines(&output.borrow()).len(), index);
+            Ok(log)
+        });
+
+        assert_eq!(write_json_lines(produced, sink.clone()).unwrap(), 3);
+        assert_eq!(lines(&sink.output.borrow()), logs);
+        assert_eq!(*sink.flushes.borrow(), 1);
+    }
+
+    #[test]
+    fn writes_the_reset_once_at_the_end() {
+        let sink = Sink::default();
+
+        write_json_lines(
+            [step(5), step(6), step(7), reset()].into_iter().map(Ok),
+            sink.clone(),
+        )
+        .unwrap();
+
+        let output = sink.output.borrow();
+        let written = lines(&output);
+        assert_eq!(written.len(), 4);
+        assert_eq!(
+            written
+                .iter()
+                .filter(|log| matches!(log, UarchLog::Reset { .. }))
+                .count(),
+            1
+        );
+        assert!(matches!(written.last(), Some(UarchLog::Reset { .. })));
+        assert!(std::str::from_utf8(&output)
+            .unwrap()
+            .lines()
+            .all(|line| line.starts_with(r#"{"kind":"#)));
+    }
+
+    #[test]
+    fn propagates_sink_errors() {
+        let sink = Sink {
+            limit: Some(100),
+            ..Sink::default()
+        };
+        let pulled = Rc::new(RefCell::new(0));
+
+        let logs = [step(0), step(1), reset()].into_iter().map(|log| {
+            *pulled.borrow_mut() += 1;
+            Ok(log)
+        });
+
+        let error = write_json_lines(logs, sink.clone()).unwrap_err();
+        assert!(matches!(
+            error,
+            StreamError::Io(ref error) if error.kind() == std::io::ErrorKind::WriteZero
+        ));
+
+        // Nothing more is logged once the sink fails
+        assert_eq!(*pulled.borrow(), 1);
+        assert_eq!(sink.output.borrow().len(), 100);
+    }
+
+    #[test]
+    fn propagates_machine_errors() {
+        let sink = Sink::default();
+        let logs = vec![
+            Ok(step(0)),
+            Err(MachineError::from(ConfigIssue::new("uarch", "failed"))),
+            Ok(reset()),
+        ];
+
+        let error = write_json_lines(logs, sink.clone()).unwrap_err();
+        assert!(
+            matches!(error, StreamError::Machine(ref error) if error.message() == "uarch: failed")
+        );
+        assert_eq!(lines(&sink.output.borrow()), [step(0)]);
+    }
+}
@@ -0,0 +1,245 @@
+//! Fixtures shared by the unit tests.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+use crate::configuration::{
+    ClintConfig, DtbConfig, HtifConfig, MachineConfig, MemoryRangeConfig, RamConfig, RollupConfig,
+    TlbConfig, UarchConfig, UarchRamConfig,
+};
+use crate::hash::Hash;
+use crate::log::{AccessData, AccessLogData, AccessLogType, AccessType};
+use crate::merkle::{self, LOG2_ROOT_SIZE, LOG2_WORD_SIZE};
+use crate::replay::UarchLayout;
+
+/// Machine state tree holding only a few non-zero words
+#[derive(Clone, Default)]
+pub(crate) struct SparseTree {
+    words: BTreeMap<u64, u64>,
+}
+
+impl SparseTree {
+    pub(crate) fn read(&self, address: u64) -> u64 {
+        self.words.get(&address).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn write(&mut self, address: u64, value: u64) {
+        self.words.insert(address, value);
+    }
+
+    pub(crate) fn root(&self) -> Hash {
+        self.node(0, LOG2_ROOT_SIZE)
+    }
+
+    /// Sibling hashes of a word, ordered from the root
+    pub(crate) fn proof(&self, address: u64) -> Vec<Hash> {
+        (LOG2_WORD_SIZE..LOG2_ROOT_SIZE)
+            .rev()
+            .map(|log2_size| {
+                let sibling = (address ^ (1 << log2_size)) & !((1 << log2_size) - 1);
+                self.node(sibling, log2_size)
+            })
+            .collect()
+    }
+
+    /// Hash of the node of size `2^log2_size` at `address`
+    pub(crate) fn node(&self, address: u64, log2_size: usize) -> Hash {
+        let end = address as u128 + (1u128 << log2_size);
+        let mut words = self
+            .words
+            .range(address..)
+            .take_while(|(word, _)| (**word as u128) < end);
+
+        if log2_size == LOG2_WORD_SIZE {
+            let value = words.next().map_or(0, |(_, value)| *value);
+            return merkle::keccak(&value.to_le_bytes());
+        }
+
+        if words.next().is_none() {
+            return pristine(log2_size);
+        }
+
+        let half = 1 << (log2_size - 1);
+        merkle::hash_children(
+            &self.node(address, log2_size - 1),
+            &self.node(address + half, log2_size - 1),
+        )
+    }
+}
+
+fn pristine(log2_size: usize) -> Hash {
+    let mut hash = merkle::keccak(&[0; 8]);
+    for _ in LOG2_WORD_SIZE..log2_size {
+        hash = merkle::hash_children(&hash, &hash);
+    }
+    hash
+}
+
+/// Records the accesses a step makes to a [`SparseTree`]
+pub(crate) struct Recorder {
+    pub(crate) tree: SparseTree,
+    pub(crate) accesses: Vec<AccessData>,
+}
+
+impl Recorder {
+    pub(crate) fn read(&mut self, address: u64) -> u64 {
+        let value = self.tree.read(address);
+        self.accesses
+            .push(self.access(AccessType::Read, address, value));
+        value
+    }
+
+    pub(crate) fn write(&mut self, address: u64, value: u64) {
+        let mut access = self.access(AccessType::Write, address, self.tree.read(address));
+        access.written_data = Some(value.to_le_bytes().to_vec());
+        access.written_hash = Some(merkle::keccak(&value.to_le_bytes()));
+        self.accesses.push(access);
+        self.tree.write(address, value);
+    }
+
+    fn access(&self, access_type: AccessType, address: u64, value: u64) -> AccessData {
+        AccessData {
+            access_type,
+            address,
+            log2_size: LOG2_WORD_SIZE as i32,
+            read_hash: merkle::keccak(&value.to_le_bytes()),
+            read_data: Some(value.to_le_bytes().to_vec()),
+            written_hash: None,
+            written_data: None,
+            sibling_hashes: Some(self.tree.proof(address)),
+        }
+    }
+}
+
+/// Address of the instruction executed by [`addi_step`]
+pub(crate) const PC: u64 = 0x600000;
+
+/// `addi x5, x6, 7`
+const ADDI: u64 = (7 << 20) | (6 << 15) | (5 << 7) | 0x13;
+
+/// Records a uarch step executing an `addi`, returning the root hash before
+/// the step, the log, and the root hash after the step
+pub(crate) fn addi_step(layout: &UarchLayout) -> (Hash, AccessLogData, Hash) {
+    let mut tree = SparseTree::default();
+    tree.write(layout.cycle, 5);
+    tree.write(layout.pc, PC);
+    tree.write(layout.x[6], 35);
+    tree.write(PC, ADDI);
+
+    let before = tree.root();
+    let mut recorder = Recorder {
+        tree,
+        accesses: Vec::new(),
+    };
+
+    let cycle = recorder.read(layout.cycle);
+    recorder.read(layout.halt_flag);
+    let pc = recorder.read(layout.pc);
+    recorder.read(pc);
+    let x6 = recorder.read(layout.x[6]);
+    recorder.write(layout.x[5], x6 + 7);
+    recorder.write(layout.pc, pc + 4);
+    recorder.write(layout.cycle, cycle + 1);
+
+    let log = AccessLogData {
+        log_type: AccessLogType {
+            proofs: true,
+            annotations: false,
+            large_data: false,
+        },
+        accesses: recorder.accesses,
+        brackets: Vec::new(),
+        notes: Vec::new(),
+    };
+
+    (before, log, recorder.tree.root())
+}
+
+/// Configuration with every field zeroed or empty, built without the emulator
+pub(crate) fn machine_config() -> MachineConfig {
+    let range = MemoryRangeConfig {
+        start: 0,
+        length: 0,
+        shared: false,
+        image_filename: None,
+    };
+
+    MachineConfig {
+        // Plain integer registers, for which all zeros is a valid value
+        processor: unsafe { std::mem::zeroed() },
+        ram: RamConfig {
+            length: 1 << 20,
+            image_filename: None,
+        },
+        dtb: DtbConfig {
+            bootargs: None,
+            init: None,
+            entrypoint: None,
+            image_filename: None,
+        },
+        flash_drive: Vec::new(),
+        tlb: TlbConfig {
+            image_filename: None,
+        },
+        clint: ClintConfig { mtimecmp: 0 },
+        htif: HtifConfig {
+            fromhost: 0,
+            tohost: 0,
+            console_getchar: false,
+            yield_manual: false,
+            yield_automatic: false,
+        },
+        rollup: RollupConfig {
+            has_value: false,
+            rx_buffer: range.clone(),
+            tx_buffer: range,
+        },
+        uarch: UarchConfig {
+            processor: unsafe { std::mem::zeroed() },
+            ram: UarchRamConfig {
+                image_filename: None,
+            },
+        },
+    }
+}
+
+/// System allocator that tracks the bytes each thread keeps allocated
+struct CountingAllocator;
+
+thread_local! {
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+fn count(bytes: isize) {
+    // Fails only while the thread is being torn down
+    let _ = LIVE_BYTES.try_with(|live| live.set(live.get() + bytes));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size() as isize);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count(-(layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size as isize - layout.size() as isize);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Bytes `f` leaves allocated on the current thread, which is zero unless it
+/// leaks
+pub(crate) fn leaked_bytes(f: impl FnOnce()) -> isize {
+    let before = LIVE_BYTES.with(Cell::get);
+    f();
+    LIVE_BYTES.with(Cell::get) - before
+}
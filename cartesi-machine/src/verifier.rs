@@ -0,0 +1,130 @@
+//! Verification of uarch access logs without a machine instance.
+
+use cartesi_machine_sys::{cm_access_log, cm_machine_runtime_config};
+
+use crate::configuration::RuntimeConfig;
+use crate::errors::{ErrorCollector, VerificationError};
+use crate::hash::Hash;
+
+/// Checks uarch step and reset logs with the emulator verifier
+#[derive(Debug, Default, Clone)]
+pub struct Verifier {
+    runtime: RuntimeConfig,
+    one_based: bool,
+}
+
+impl Verifier {
+    /// Creates a verifier with the given runtime configuration
+    pub fn new(runtime: RuntimeConfig) -> Self {
+        Self {
+            runtime,
+            one_based: false,
+        }
+    }
+
+    /// Sets whether access indices in error messages start at one
+    pub fn one_based(mut self, one_based: bool) -> Self {
+        self.one_based = one_based;
+        self
+    }
+
+    /// Checks the internal consistency of a uarch step log
+    pub fn verify_uarch_step_log<L: AsRef<cm_access_log>>(
+        &self,
+        log: &L,
+    ) -> Result<(), VerificationError> {
+        let mut error_collector = ErrorCollector::new();
+
+        unsafe {
+            let runtime = cm_machine_runtime_config::from(self.runtime.clone());
+
+            let result = cartesi_machine_sys::cm_verify_uarch_step_log(
+                log.as_ref(),
+                &runtime,
+                self.one_based,
+                error_collector.as_out_ptr(),
+            );
+
+            error_collector.collect(result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the validity of a state transition caused by a uarch step
+    pub fn verify_uarch_step_state_transition<L: AsRef<cm_access_log>>(
+        &self,
+        root_hash_before: &Hash,
+        log: &L,
+        root_hash_after: &Hash,
+    ) -> Result<(), VerificationError> {
+        let mut error_collector = ErrorCollector::new();
+
+        unsafe {
+            let runtime = cm_machine_runtime_config::from(self.runtime.clone());
+
+            let result = cartesi_machine_sys::cm_verify_uarch_step_state_transition(
+                root_hash_before.as_ptr(),
+                log.as_ref(),
+                root_hash_after.as_ptr(),
+                &runtime,
+                self.one_based,
+                error_collector.as_out_ptr(),
+            );
+
+            error_collector.collect(result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the validity of a state transition caused by a uarch reset
+    pub fn verify_uarch_reset_state_transition<L: AsRef<cm_access_log>>(
+        &self,
+        root_hash_before: &Hash,
+        log: &L,
+        root_hash_after: &Hash,
+    ) -> Result<(), VerificationError> {
+        let mut error_collector = ErrorCollector::new();
+
+        unsafe {
+            let runtime = cm_machine_runtime_config::from(self.runtime.clone());
+
+            let result = cartesi_machine_sys::cm_verify_uarch_reset_state_transition(
+                root_hash_before.as_ptr(),
+                log.as_ref(),
+                root_hash_after.as_ptr(),
+                &runtime,
+                self.one_based,
+                error_collector.as_out_ptr(),
+            );
+
+            error_collector.collect(result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the internal consistency of a uarch reset log
+    pub fn verify_uarch_reset_log<L: AsRef<cm_access_log>>(
+        &self,
+        log: &L,
+    ) -> Result<(), VerificationError> {
+        let mut error_collector = ErrorCollector::new();
+
+        unsafe {
+            let runtime = cm_machine_runtime_config::from(self.runtime.clone());
+
+            let result = cartesi_machine_sys::cm_verify_uarch_reset_log(
+                log.as_ref(),
+                &runtime,
+                self.one_based,
+                error_collector.as_out_ptr(),
+            );
+
+            error_collector.collect(result)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use cartesi_machine::configuration::RuntimeConfig;
+use cartesi_machine::log::{AccessLogData, AccessLogType, OwnedAccessLog};
+use cartesi_machine::replay::UarchLayout;
+use cartesi_machine::verifier::Verifier;
+
+const LOG_TYPE: AccessLogType = AccessLogType {
+    proofs: true,
+    annotations: false,
+    large_data: false,
+};
+
+#[test]
+fn decoded_log_passes_the_emulator_verifier() {
+    let mut machine = common::machine();
+    let layout = UarchLayout::from_machine(&mut machine);
+    let verifier = Verifier::new(RuntimeConfig::default());
+
+    for _ in 0..16 {
+        let before = machine.get_root_hash().unwrap();
+        let log = machine.log_uarch_step(LOG_TYPE, false).unwrap().to_data();
+        let after = machine.get_root_hash().unwrap();
+
+        let calldata = log.to_calldata().unwrap();
+        let decoded = AccessLogData::from_calldata(&calldata, &layout).unwrap();
+
+        assert_eq!(decoded.accesses, log.accesses);
+        verifier
+            .verify_uarch_step_state_transition(
+                &before,
+                &OwnedAccessLog::try_from(&decoded).unwrap(),
+                &after,
+            )
+            .unwrap();
+    }
+}
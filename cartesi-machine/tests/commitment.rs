@@ -0,0 +1,25 @@
+mod common;
+
+use cartesi_machine::commitment::CommitmentBuilder;
+
+#[test]
+fn manual_yields_do_not_end_the_run() {
+    let mut machine = common::machine();
+    machine.set_iflags_y().unwrap();
+
+    let start = machine.read_mcycle().unwrap();
+    let commitment = CommitmentBuilder::new(4, 2)
+        .build_mcycle(&mut machine)
+        .unwrap();
+
+    assert_eq!(machine.read_mcycle().unwrap(), start + 4 * 16);
+    assert!(!machine.read_iflags_y().unwrap());
+    assert_ne!(commitment.leaves()[2], commitment.leaves()[3]);
+
+    for index in 0..4 {
+        assert!(commitment
+            .proof(index)
+            .unwrap()
+            .verify(&commitment.root_hash()));
+    }
+}
@@ -0,0 +1,14 @@
+//! Helpers shared by the integration tests, which need the emulator library.
+
+#![allow(dead_code)]
+
+use cartesi_machine::configuration::{MachineConfig, RuntimeConfig};
+use cartesi_machine::Machine;
+
+/// Creates a machine with a small RAM and no images
+pub fn machine() -> Machine {
+    let mut config = MachineConfig::default();
+    config.ram.length = 1 << 20;
+
+    Machine::create(config, RuntimeConfig::default()).unwrap()
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use cartesi_machine::configuration::{
+    MachineConfig, MachineConfigBuilder, MemoryRangeConfig, RamConfig, RuntimeConfig,
+};
+use cartesi_machine::errors::ErrorCode;
+use cartesi_machine::Machine;
+
+/// Strings with NUL bytes are rejected as invalid arguments instead of panicking
+#[test]
+fn nul_bytes_are_invalid_arguments() {
+    let mut config = MachineConfig::default();
+    config.ram.length = 1 << 20;
+    config.dtb.bootargs = Some("quiet\0console=hvc0".to_string());
+
+    let Err(error) = Machine::create(config, RuntimeConfig::default()) else {
+        panic!("created a machine with a NUL byte in its bootargs");
+    };
+    assert_eq!(error.code(), ErrorCode::InvalidArgument);
+    assert_eq!(error.message(), "dtb.bootargs: contains a NUL byte at 5");
+
+    let mut machine = common::machine();
+    let error = machine
+        .replace_memory_range(MemoryRangeConfig {
+            start: 0x80000000000000,
+            length: 0x1000,
+            shared: false,
+            image_filename: Some("\0".to_string()),
+        })
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidArgument);
+    assert_eq!(
+        error.message(),
+        "memory_range.image_filename: contains a NUL byte at 0"
+    );
+}
+
+/// Strings handed to the emulator stay valid until it has copied them
+#[test]
+fn created_machines_keep_their_strings() {
+    let mut config = MachineConfig::default();
+    config.ram.length = 1 << 20;
+    config.dtb.bootargs = Some("quiet console=hvc0".to_string());
+    config.dtb.init = Some("echo init\n".to_string());
+    config.flash_drive.clear();
+
+    let mut machine = Machine::create(config.clone(), RuntimeConfig::default()).unwrap();
+    let initial = machine.get_initial_config().unwrap();
+
+    assert_eq!(initial.dtb.bootargs, config.dtb.bootargs);
+    assert_eq!(initial.dtb.init, config.dtb.init);
+}
+
+/// The builder starts from the emulator defaults
+#[test]
+fn builder_starts_from_the_defaults() {
+    let expected = MachineConfig {
+        ram: RamConfig {
+            length: 1 << 20,
+            image_filename: None,
+        },
+        ..MachineConfig::default()
+    };
+
+    let config = MachineConfigBuilder::new()
+        .ram(1 << 20, None)
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        serde_json::to_value(config).unwrap(),
+        serde_json::to_value(expected).unwrap()
+    );
+}
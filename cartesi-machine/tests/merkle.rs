@@ -0,0 +1,30 @@
+mod common;
+
+use cartesi_machine::merkle;
+
+/// RAM start in the default machine layout
+const RAM_START: u64 = 0x80000000;
+
+/// The emulator tree and the pure-Rust recomputation commit to the same ranges
+#[test]
+fn range_hash_matches_recomputation() {
+    let mut machine = common::machine();
+    let data = (0..0x3000u32)
+        .map(|i| (i * 7 + i / 256) as u8)
+        .collect::<Vec<u8>>();
+    machine.write_memory(RAM_START + 0x800, &data).unwrap();
+
+    for (start, length) in [
+        (RAM_START, 0x1000),
+        (RAM_START + 0x808, 0x2ff0),
+        (RAM_START + 0x1000, 0x1818),
+        (RAM_START + 0x3ff8, 8),
+        (RAM_START, 0x10000),
+    ] {
+        let expected =
+            merkle::hash_range(start, &machine.read_memory(start, length).unwrap()).unwrap();
+        let range = machine.get_range_hash(start, length).unwrap();
+
+        assert_eq!(range, expected, "range 0x{:x}+0x{:x}", start, length);
+    }
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use cartesi_machine::pretty::StateNames;
+use cartesi_machine::CSR;
+
+/// Registers resolve to their names and memory ranges to offsets into them
+#[test]
+fn names_come_from_the_machine() {
+    let mut machine = common::machine();
+    let names = StateNames::from_machine(&mut machine).unwrap();
+
+    for i in 0..32 {
+        assert_eq!(names.resolve(machine.get_x_address(i)), format!("x{}", i));
+        assert_eq!(names.resolve(machine.get_f_address(i)), format!("f{}", i));
+        assert_eq!(
+            names.resolve(machine.get_uarch_x_address(i)),
+            format!("uarch.x{}", i)
+        );
+    }
+
+    for csr in CSR::ALL {
+        assert_eq!(names.resolve(machine.get_csr_address(csr)), csr.name());
+    }
+
+    assert_eq!(names.resolve(0x80000000), "ram");
+    assert_eq!(names.resolve(0x80001008), "ram+0x1008");
+    assert_eq!(names.resolve(0xfffffffffffffff8), "0xfffffffffffffff8");
+}
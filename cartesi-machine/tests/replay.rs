@@ -0,0 +1,127 @@
+mod common;
+
+use cartesi_machine::configuration::RuntimeConfig;
+use cartesi_machine::hash::Hash;
+use cartesi_machine::log::{AccessLogData, AccessLogType, AccessType, OwnedAccessLog};
+use cartesi_machine::merkle;
+use cartesi_machine::replay::{self, UarchLayout, SHADOW_UARCH_STATE_START};
+use cartesi_machine::verifier::Verifier;
+
+const LOG_TYPE: AccessLogType = AccessLogType {
+    proofs: true,
+    annotations: false,
+    large_data: false,
+};
+
+/// Copies of `log` with one thing changed each
+fn tampered(log: &AccessLogData) -> Vec<AccessLogData> {
+    let mut logs = Vec::new();
+    let last = log.accesses.len() - 1;
+
+    let mut missing = log.clone();
+    missing.accesses.pop();
+    logs.push(missing);
+
+    let mut extra = log.clone();
+    extra.accesses.push(log.accesses[last].clone());
+    logs.push(extra);
+
+    for index in 0..log.accesses.len() {
+        let mut address = log.clone();
+        address.accesses[index].address ^= 8;
+        logs.push(address);
+
+        let mut access_type = log.clone();
+        access_type.accesses[index].access_type = match log.accesses[index].access_type {
+            AccessType::Read => AccessType::Write,
+            AccessType::Write => AccessType::Read,
+        };
+        logs.push(access_type);
+
+        let mut read = log.clone();
+        read.accesses[index].read_data.as_mut().unwrap()[0] ^= 1;
+        logs.push(read);
+
+        let mut proof = log.clone();
+        proof.accesses[index].sibling_hashes.as_mut().unwrap()[0] = merkle::keccak(b"forged");
+        logs.push(proof);
+
+        if log.accesses[index].access_type == AccessType::Write {
+            let mut written = log.clone();
+            let access = &mut written.accesses[index];
+            access.written_data.as_mut().unwrap()[0] ^= 1;
+            access.written_hash = Some(merkle::keccak(access.written_data.as_ref().unwrap()));
+            logs.push(written);
+        }
+    }
+
+    logs
+}
+
+fn assert_agree(
+    verifier: &Verifier,
+    layout: &UarchLayout,
+    before: &Hash,
+    log: &AccessLogData,
+    after: &Hash,
+) {
+    let owned = OwnedAccessLog::try_from(log).unwrap();
+
+    assert_eq!(
+        replay::verify_uarch_step_log(log, layout, false).is_ok(),
+        verifier.verify_uarch_step_log(&owned).is_ok(),
+        "step log verification disagrees on {}",
+        log.to_json()
+    );
+    assert_eq!(
+        replay::verify_uarch_step_state_transition(before, log, after, layout, false).is_ok(),
+        verifier
+            .verify_uarch_step_state_transition(before, &owned, after)
+            .is_ok(),
+        "state transition verification disagrees on {}",
+        log.to_json()
+    );
+}
+
+#[test]
+fn replay_agrees_with_the_emulator() {
+    let mut machine = common::machine();
+    let layout = UarchLayout::from_machine(&mut machine);
+    let verifier = Verifier::new(RuntimeConfig::default());
+
+    for _ in 0..16 {
+        let before = machine.get_root_hash().unwrap();
+        let log = machine.log_uarch_step(LOG_TYPE, false).unwrap().to_data();
+        let after = machine.get_root_hash().unwrap();
+
+        replay::verify_uarch_step_state_transition(&before, &log, &after, &layout, false).unwrap();
+        assert_agree(&verifier, &layout, &before, &log, &after);
+        assert_agree(&verifier, &layout, &after, &log, &before);
+
+        for log in tampered(&log) {
+            assert_agree(&verifier, &layout, &before, &log, &after);
+        }
+    }
+}
+
+#[test]
+fn default_layout_matches_the_emulator() {
+    let mut machine = common::machine();
+    let layout = UarchLayout::from_machine(&mut machine);
+
+    assert_eq!(layout, UarchLayout::default());
+
+    let ranges = machine.get_memory_ranges().unwrap();
+    let shadow = ranges
+        .iter()
+        .find(|range| range.start == SHADOW_UARCH_STATE_START)
+        .expect("no memory range starts at the shadow uarch state");
+    let end = shadow.start + shadow.length;
+
+    for address in [layout.halt_flag, layout.cycle, layout.pc]
+        .iter()
+        .chain(&layout.x)
+    {
+        assert!((shadow.start..end).contains(address));
+    }
+}
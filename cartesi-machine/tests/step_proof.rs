@@ -0,0 +1,54 @@
+mod common;
+
+use cartesi_machine::bisect::StoredMachine;
+use cartesi_machine::configuration::RuntimeConfig;
+use cartesi_machine::errors::ProveError;
+use cartesi_machine::step_proof::{StepKind, StepProof};
+use cartesi_machine::verifier::Verifier;
+
+#[test]
+fn deserialized_proof_verifies() {
+    let mut machine = common::machine();
+    let verifier = Verifier::new(RuntimeConfig::default());
+
+    for (mcycle, uarch_cycle) in [(0, 0), (0, 7), (2, 0), (5, 3)] {
+        let proof = machine.prove_step(mcycle, uarch_cycle).unwrap();
+        assert_eq!(proof.kind, StepKind::Step);
+
+        let json = serde_json::to_string(&proof).unwrap();
+        let loaded: StepProof = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded, proof);
+        loaded.verify(&verifier).unwrap();
+
+        let mut tampered = loaded.clone();
+        tampered.root_hash_after = tampered.root_hash_before.clone();
+        assert!(matches!(
+            tampered.verify(&verifier),
+            Err(ProveError::Verification(_))
+        ));
+    }
+
+    assert!(matches!(
+        machine.prove_step(1, 0),
+        Err(ProveError::CycleInPast { .. })
+    ));
+}
+
+#[test]
+fn stored_machine_proves_past_cycles() {
+    let path = std::env::temp_dir().join(format!("step-proof-{}", std::process::id()));
+    let mut stored =
+        StoredMachine::from_machine(common::machine(), &path, RuntimeConfig::default()).unwrap();
+    let verifier = Verifier::new(RuntimeConfig::default());
+
+    let later = stored.prove_step(4, 2).unwrap();
+    let earlier = stored.prove_step(1, 6).unwrap();
+    let again = stored.prove_step(4, 2).unwrap();
+
+    std::fs::remove_dir_all(&path).unwrap();
+
+    assert_eq!(later, again);
+    earlier.verify(&verifier).unwrap();
+    later.verify(&verifier).unwrap();
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use cartesi_machine::log::AccessLogType;
+use cartesi_machine::stream::UarchLog;
+
+const LOG_TYPE: AccessLogType = AccessLogType {
+    proofs: false,
+    annotations: false,
+    large_data: false,
+};
+
+/// A step at the last uarch cycle does not advance, so it ends the stream
+#[test]
+fn stops_when_the_uarch_cycle_does_not_advance() {
+    let mut machine = common::machine();
+    machine.write_uarch_cycle(u64::MAX).unwrap();
+
+    let logs = machine
+        .log_uarch_steps(LOG_TYPE, false)
+        .take(3)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(logs.len(), 1);
+    assert!(matches!(
+        logs[0],
+        UarchLog::Step {
+            uarch_cycle: u64::MAX,
+            ..
+        }
+    ));
+}
+
+/// A machine stepped to its halt logs each cycle once, then the reset
+#[test]
+fn ends_with_a_single_reset() {
+    let mut machine = common::machine();
+
+    let logs = machine
+        .log_uarch_steps(LOG_TYPE, false)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let (reset, steps) = logs.split_last().unwrap();
+    assert!(matches!(reset, UarchLog::Reset { .. }));
+    for (cycle, log) in steps.iter().enumerate() {
+        assert!(matches!(log, UarchLog::Step { uarch_cycle, .. } if *uarch_cycle == cycle as u64));
+    }
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use cartesi_machine::configuration::MemoryRangeConfig;
+
+/// Every range the emulator maps must be known to the overlap check
+#[test]
+fn drives_over_emulator_ranges_are_rejected() {
+    let mut machine = common::machine();
+    let mut config = machine.get_initial_config().unwrap();
+    config.flash_drive.clear();
+
+    for range in machine.get_memory_ranges().unwrap() {
+        if range.length == 0 {
+            continue;
+        }
+
+        let mut overlapping = config.clone();
+        overlapping.flash_drive.push(MemoryRangeConfig {
+            start: range.start & !0xfff,
+            length: 0x1000,
+            shared: false,
+            image_filename: None,
+        });
+
+        let error = overlapping.validate().unwrap_err();
+        assert!(
+            error
+                .issues
+                .iter()
+                .any(|issue| issue.message.starts_with("overlaps")),
+            "{} at 0x{:x} is not checked: {}",
+            range.description,
+            range.start,
+            error
+        );
+    }
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use cartesi_machine::configuration::RuntimeConfig;
+use cartesi_machine::errors::VerificationErrorKind;
+use cartesi_machine::hash::Hash;
+use cartesi_machine::log::{AccessLogData, AccessLogType, OwnedAccessLog};
+use cartesi_machine::merkle;
+use cartesi_machine::verifier::Verifier;
+
+fn logged_step(proofs: bool) -> (Hash, AccessLogData, Hash) {
+    let mut machine = common::machine();
+    let log_type = AccessLogType {
+        proofs,
+        annotations: false,
+        large_data: false,
+    };
+
+    let before = machine.get_root_hash().unwrap();
+    let log = machine.log_uarch_step(log_type, false).unwrap().to_data();
+    (before, log, machine.get_root_hash().unwrap())
+}
+
+fn kind(before: &Hash, log: &AccessLogData, after: &Hash) -> VerificationErrorKind {
+    Verifier::new(RuntimeConfig::default())
+        .verify_uarch_step_state_transition(before, &OwnedAccessLog::try_from(log).unwrap(), after)
+        .unwrap_err()
+        .kind
+}
+
+/// Pins the emulator messages `VerificationError` classifies
+#[test]
+fn emulator_messages_map_to_kinds() {
+    let (before, log, after) = logged_step(true);
+    let verifier = Verifier::new(RuntimeConfig::default());
+    verifier
+        .verify_uarch_step_state_transition(
+            &before,
+            &OwnedAccessLog::try_from(&log).unwrap(),
+            &after,
+        )
+        .unwrap();
+
+    let (no_proofs_before, no_proofs, no_proofs_after) = logged_step(false);
+    assert_eq!(
+        kind(&no_proofs_before, &no_proofs, &no_proofs_after),
+        VerificationErrorKind::MissingProofs
+    );
+
+    let mut missing = log.clone();
+    missing.accesses.pop();
+    assert_eq!(
+        kind(&before, &missing, &after),
+        VerificationErrorKind::TooFewAccesses
+    );
+
+    let mut extra = log.clone();
+    extra.accesses.push(log.accesses[0].clone());
+    assert_eq!(
+        kind(&before, &extra, &after),
+        VerificationErrorKind::TooManyAccesses
+    );
+
+    let mut moved = log.clone();
+    moved.accesses[1].address ^= 8;
+    let error = Verifier::new(RuntimeConfig::default())
+        .verify_uarch_step_state_transition(
+            &before,
+            &OwnedAccessLog::try_from(&moved).unwrap(),
+            &after,
+        )
+        .unwrap_err();
+    assert_eq!(error.kind, VerificationErrorKind::UnexpectedAccess);
+    assert!(error.access.is_some());
+
+    let mut read = log.clone();
+    read.accesses[1].read_data.as_mut().unwrap()[0] ^= 1;
+    assert_eq!(
+        kind(&before, &read, &after),
+        VerificationErrorKind::DataHashMismatch
+    );
+
+    let mut forged = log.clone();
+    forged.accesses[1].sibling_hashes.as_mut().unwrap()[0] = merkle::keccak(b"forged");
+    assert_eq!(
+        kind(&before, &forged, &after),
+        VerificationErrorKind::RootHashMismatch
+    );
+
+    assert_eq!(
+        kind(&before, &log, &before),
+        VerificationErrorKind::FinalRootHashMismatch
+    );
+}